@@ -1,43 +1,100 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::fmt::{self, Display};
 use std::slice::Iter;
+use std::str::FromStr;
 
 use crate::inputs::key::Key;
 
 /// We define all available action
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum Action {
     Quit,
-    Sleep,
-    IncrementDelay,
-    DecrementDelay,
+    RunPause,
     Step,
+    RunSteps,
+    RunToBreakpoint,
+    RunToInstruction,
+    Faster,
+    Slower,
+    Select,
+    SelectCell,
+    NextStack,
+    PreviousStack,
+    ScrollUp,
+    ScrollDown,
+    Search,
+    Command,
+    MoveCursor,
+    ToggleBreakpoint,
+    ToggleHistory,
     RandomInput,
+    SaveCheckpoint,
+    LoadCheckpoint,
+    InjectSdrFile,
+    ExportGraph,
 }
 
 impl Action {
     /// All available actions
     pub fn iterator() -> Iter<'static, Action> {
-        static ACTIONS: [Action; 6] = [
+        static ACTIONS: [Action; 24] = [
             Action::Quit,
-            Action::Sleep,
-            Action::IncrementDelay,
-            Action::DecrementDelay,
+            Action::RunPause,
             Action::Step,
+            Action::RunSteps,
+            Action::RunToBreakpoint,
+            Action::RunToInstruction,
+            Action::Faster,
+            Action::Slower,
+            Action::Select,
+            Action::SelectCell,
+            Action::NextStack,
+            Action::PreviousStack,
+            Action::ScrollUp,
+            Action::ScrollDown,
+            Action::Search,
+            Action::Command,
+            Action::MoveCursor,
+            Action::ToggleBreakpoint,
+            Action::ToggleHistory,
             Action::RandomInput,
+            Action::SaveCheckpoint,
+            Action::LoadCheckpoint,
+            Action::InjectSdrFile,
+            Action::ExportGraph,
         ];
         ACTIONS.iter()
     }
 
-    /// List of key associated to action
-    pub fn keys(&self) -> &[Key] {
+    /// Keys associated to action unless the key bindings file changes
+    /// them. Directions are given in the order up, down, left, right.
+    pub fn default_keys(&self) -> &[Key] {
         match self {
             Action::Quit => &[Key::Ctrl('c'), Key::Char('q')],
-            Action::Sleep => &[Key::Char('s')],
-            Action::IncrementDelay => &[Key::Char('+')],
-            Action::DecrementDelay => &[Key::Char('-')],
+            Action::RunPause => &[Key::Char(' ')],
             Action::Step => &[Key::F5],
+            Action::RunSteps => &[Key::F6],
+            Action::RunToBreakpoint => &[Key::F8],
+            Action::RunToInstruction => &[Key::F9],
+            Action::Faster => &[Key::Char('+')],
+            Action::Slower => &[Key::Char('-')],
+            Action::Select => &[Key::Up, Key::Down, Key::Left, Key::Right],
+            Action::SelectCell => &[Key::Tab],
+            Action::NextStack => &[Key::Char(']')],
+            Action::PreviousStack => &[Key::Char('[')],
+            Action::ScrollUp => &[Key::PageUp],
+            Action::ScrollDown => &[Key::PageDown],
+            Action::Search => &[Key::Char('/')],
+            Action::Command => &[Key::Char(':')],
+            Action::MoveCursor => &[Key::Char('k'), Key::Char('j')],
+            Action::ToggleBreakpoint => &[Key::Char('b')],
+            Action::ToggleHistory => &[Key::Char('h')],
             Action::RandomInput => &[Key::Char('r')],
+            Action::SaveCheckpoint => &[Key::Char('s')],
+            Action::LoadCheckpoint => &[Key::Char('l')],
+            Action::InjectSdrFile => &[Key::Char('i')],
+            Action::ExportGraph => &[Key::Char('g')],
         }
     }
 }
@@ -47,55 +104,100 @@ impl Display for Action {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let str = match self {
             Action::Quit => "Quit",
-            Action::Sleep => "Sleep",
-            Action::IncrementDelay => "Increment delay",
-            Action::DecrementDelay => "Decrement delay",
+            Action::RunPause => "Run/Pause",
             Action::Step => "Step",
+            Action::RunSteps => "Run 1000 steps",
+            Action::RunToBreakpoint => "Run to breakpoint",
+            Action::RunToInstruction => "Run to instruction",
+            Action::Faster => "Increase step budget",
+            Action::Slower => "Decrease step budget",
+            Action::Select => "Select column",
+            Action::SelectCell => "Select cell",
+            Action::NextStack => "Next stack",
+            Action::PreviousStack => "Previous stack",
+            Action::ScrollUp => "Scroll up",
+            Action::ScrollDown => "Scroll down",
+            Action::Search => "Search bindings",
+            Action::Command => "Command",
+            Action::MoveCursor => "Move source cursor",
+            Action::ToggleBreakpoint => "Toggle breakpoint",
+            Action::ToggleHistory => "Model/History",
             Action::RandomInput => "Random Input",
+            Action::SaveCheckpoint => "Save checkpoint",
+            Action::LoadCheckpoint => "Load checkpoint",
+            Action::InjectSdrFile => "Inject SDR file",
+            Action::ExportGraph => "Export graph",
         };
         write!(f, "{}", str)
     }
 }
 
-/// The application should have some contextual actions.
-#[derive(Default, Debug, Clone)]
-pub struct Actions(Vec<Action>);
+/// Actions are named like in the code, e.g. `RunPause`
+impl FromStr for Action {
+    type Err = String;
 
-impl Actions {
-    /// Given a key, find the corresponding action
-    pub fn find(&self, key: Key) -> Option<&Action> {
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         Action::iterator()
-            .filter(|action| self.0.contains(action))
-            .find(|action| action.keys().contains(&key))
+            .find(|action| format!("{:?}", action) == s)
+            .copied()
+            .ok_or_else(|| format!("unknown action {}", s))
     }
+}
 
-    /// Get contextual actions.
-    /// (just for building a help view)
-    pub fn actions(&self) -> &[Action] {
-        self.0.as_slice()
+/// Keys of the actions
+#[derive(Debug, Clone)]
+pub struct KeyBindings(HashMap<Action, Vec<Key>>);
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self(
+            Action::iterator()
+                .map(|action| (*action, action.default_keys().to_vec()))
+                .collect(),
+        )
     }
 }
 
-impl From<Vec<Action>> for Actions {
-    /// Build contextual action
-    ///
-    /// # Panics
-    ///
-    /// If two actions have same key
-    fn from(actions: Vec<Action>) -> Self {
-        // Check key unicity
+impl KeyBindings {
+    /// Parses a key bindings file, one `Action = key, key` line per
+    /// action, `#` starts a comment. Actions that are not listed keep
+    /// their default keys.
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut bindings = KeyBindings::default();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |e: String| format!("line {}: {}", i + 1, e);
+            let (action, keys) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("expected Action = key, key in {}", line)))?;
+            let action = action.trim().parse::<Action>().map_err(error)?;
+            let keys = keys
+                .split(',')
+                .map(|key| key.trim().parse::<Key>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(error)?;
+            bindings.0.insert(action, keys);
+        }
+        bindings.check(Action::iterator().as_slice())?;
+        Ok(bindings)
+    }
+
+    pub fn keys(&self, action: Action) -> &[Key] {
+        self.0.get(&action).map_or(&[], |keys| keys.as_slice())
+    }
+
+    /// Checks that no two actions share a key
+    fn check(&self, actions: &[Action]) -> Result<(), String> {
         let mut map: HashMap<Key, Vec<Action>> = HashMap::new();
         for action in actions.iter() {
-            for key in action.keys().iter() {
-                match map.get_mut(key) {
-                    Some(vec) => vec.push(*action),
-                    None => {
-                        map.insert(*key, vec![*action]);
-                    }
-                }
+            for key in self.keys(*action).iter() {
+                map.entry(*key).or_default().push(*action);
             }
         }
-        let errors = map
+        let mut errors = map
             .iter()
             .filter(|(_, actions)| actions.len() > 1) // at least two actions share same shortcut
             .map(|(key, actions)| {
@@ -107,12 +209,63 @@ impl From<Vec<Action>> for Actions {
                 format!("Conflict key {} with actions {}", key, actions)
             })
             .collect::<Vec<_>>();
-        if !errors.is_empty() {
-            panic!("{}", errors.join("; "))
+        errors.sort();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
         }
+    }
+}
 
-        // Ok, we can create contextual actions
-        Self(actions)
+/// The application should have some contextual actions.
+#[derive(Default, Debug, Clone)]
+pub struct Actions {
+    actions: Vec<Action>,
+    bindings: KeyBindings,
+}
+
+impl Actions {
+    /// Build contextual actions, two actions must not have the same key
+    pub fn new(actions: Vec<Action>, bindings: KeyBindings) -> Result<Self, String> {
+        bindings.check(&actions)?;
+        Ok(Self { actions, bindings })
+    }
+
+    /// Given a key, find the corresponding action
+    pub fn find(&self, key: Key) -> Option<&Action> {
+        self.actions
+            .iter()
+            .find(|action| self.bindings.keys(**action).contains(&key))
+    }
+
+    /// Get contextual actions.
+    /// (just for building a help view)
+    pub fn actions(&self) -> &[Action] {
+        self.actions.as_slice()
+    }
+
+    pub fn keys(&self, action: Action) -> &[Key] {
+        self.bindings.keys(action)
+    }
+
+    /// Position of the key among the keys of the action, e.g. the
+    /// direction of a move
+    pub fn position(&self, action: Action, key: Key) -> Option<usize> {
+        self.keys(action).iter().position(|k| *k == key)
+    }
+
+    pub fn bindings(&self) -> &KeyBindings {
+        &self.bindings
+    }
+}
+
+impl TryFrom<Vec<Action>> for Actions {
+    type Error = String;
+
+    /// Build contextual actions with the default keys
+    fn try_from(actions: Vec<Action>) -> Result<Self, Self::Error> {
+        Actions::new(actions, KeyBindings::default())
     }
 }
 
@@ -122,41 +275,61 @@ mod tests {
 
     #[test]
     fn should_find_action_by_key() {
-        let actions: Actions = vec![Action::Quit, Action::Sleep].into();
+        let actions = Actions::try_from(vec![Action::Quit, Action::Step]).unwrap();
         let result = actions.find(Key::Ctrl('c'));
         assert_eq!(result, Some(&Action::Quit));
     }
 
     #[test]
     fn should_find_action_by_key_not_found() {
-        let actions: Actions = vec![Action::Quit, Action::Sleep].into();
+        let actions = Actions::try_from(vec![Action::Quit, Action::Step]).unwrap();
         let result = actions.find(Key::Alt('w'));
         assert_eq!(result, None);
     }
 
     #[test]
     fn should_create_actions_from_vec() {
-        let _actions: Actions = vec![
+        let actions = Actions::try_from(vec![
             Action::Quit,
-            Action::Sleep,
-            Action::IncrementDelay,
-            Action::DecrementDelay,
-        ]
-        .into();
+            Action::SaveCheckpoint,
+            Action::LoadCheckpoint,
+            Action::InjectSdrFile,
+            Action::ExportGraph,
+        ]);
+        assert!(actions.is_ok());
     }
 
     #[test]
-    #[should_panic]
-    fn should_panic_when_create_actions_conflict_key() {
-        let _actions: Actions = vec![
+    fn should_fail_when_create_actions_conflict_key() {
+        let actions = Actions::try_from(vec![
             Action::Quit,
-            Action::DecrementDelay,
-            Action::Sleep,
-            Action::IncrementDelay,
-            Action::IncrementDelay,
+            Action::ExportGraph,
+            Action::SaveCheckpoint,
+            Action::Step,
+            Action::Step,
             Action::Quit,
-            Action::DecrementDelay,
-        ]
-        .into();
+            Action::ExportGraph,
+        ]);
+        let error = actions.unwrap_err();
+        assert!(error.contains("Conflict key <F5> with actions Step, Step"));
+    }
+
+    #[test]
+    fn should_parse_key_bindings() {
+        let bindings = KeyBindings::parse("# Vi keys\nSelect = K, J, H, L\nToggleHistory = <Ctrl+h> # history\n").unwrap();
+        assert_eq!(bindings.keys(Action::Select), &[Key::Char('K'), Key::Char('J'), Key::Char('H'), Key::Char('L')]);
+        assert_eq!(bindings.keys(Action::ToggleHistory), &[Key::Ctrl('h')]);
+        assert_eq!(bindings.keys(Action::Step), &[Key::F5]);
+        let actions = Actions::new(vec![Action::Select, Action::ToggleHistory], bindings).unwrap();
+        assert_eq!(actions.position(Action::Select, Key::Char('H')), Some(2));
+    }
+
+    #[test]
+    fn should_reject_invalid_key_bindings() {
+        assert_eq!(KeyBindings::parse("Jump = x").unwrap_err(), "line 1: unknown action Jump");
+        assert_eq!(KeyBindings::parse("\nStep = F13").unwrap_err(), "line 2: unknown key F13");
+        assert!(KeyBindings::parse("Step").unwrap_err().starts_with("line 1: expected"));
+        let error = KeyBindings::parse("Step = q").unwrap_err();
+        assert!(error.contains("Conflict key <q> with actions"));
     }
 }
@@ -0,0 +1,103 @@
+use std::str::FromStr;
+
+/// Usage shown when a command is not understood
+pub const COMMANDS: &str = "step [N], break NAME|LINE, set NAME VALUE, inject FILE, encode INPUT, sequence FILE [N], \
+                            range MIN MAX, save FILE, load FILE, export FILE, record FILE, program FILE, connect HOST";
+
+/// Command typed in the palette, e.g. `step 1000`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    /// Executes the number of steps
+    Step(usize),
+    /// Sets or clears the breakpoint of a source line, counted from 1
+    Breakpoint(usize),
+    /// Sets or clears the breakpoint at each execution of the
+    /// instruction
+    InstructionBreakpoint(String),
+    /// Writes a parameter of the program
+    Set(String, String),
+    /// Injects the SDRs of the file
+    Inject(String),
+    /// Injects an SDR, a number or a `timestamp,value` row
+    Encode(String),
+    /// Injects the inputs of the file the number of times
+    Sequence(String, usize),
+    /// Sets the range of the scalar encoder
+    Range(f32, f32),
+    Save(String),
+    Load(String),
+    Export(String),
+    /// Writes the recorded session
+    Record(String),
+    /// Replaces the program with the file
+    Program(String),
+    /// Connects to the broker at the host, tcp:// unless the host has
+    /// a transport
+    Connect(String),
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let command = match words.as_slice() {
+            ["step"] => Some(Command::Step(1)),
+            ["step", n] => n.parse().ok().map(Command::Step),
+            ["break", line] if line.chars().all(|c| c.is_ascii_digit()) => {
+                line.parse().ok().filter(|line| *line > 0).map(Command::Breakpoint)
+            }
+            ["break", name] => Some(Command::InstructionBreakpoint(name.to_string())),
+            ["set", name, value] => Some(Command::Set(name.to_string(), value.to_string())),
+            ["inject", file] => Some(Command::Inject(file.to_string())),
+            ["encode", input @ ..] if !input.is_empty() => Some(Command::Encode(input.join(" "))),
+            ["sequence", file] => Some(Command::Sequence(file.to_string(), 1)),
+            ["sequence", file, n] => n.parse().ok().filter(|n| *n > 0).map(|n| Command::Sequence(file.to_string(), n)),
+            ["range", min, max] => match (min.parse::<f32>(), max.parse::<f32>()) {
+                (Ok(min), Ok(max)) if min < max => Some(Command::Range(min, max)),
+                _ => None,
+            },
+            ["save", file] => Some(Command::Save(file.to_string())),
+            ["load", file] => Some(Command::Load(file.to_string())),
+            ["export", file] => Some(Command::Export(file.to_string())),
+            ["record", file] => Some(Command::Record(file.to_string())),
+            ["program", file] => Some(Command::Program(file.to_string())),
+            ["connect", host] if host.contains("://") => Some(Command::Connect(host.to_string())),
+            ["connect", host] => Some(Command::Connect(format!("tcp://{}", host))),
+            _ => None,
+        };
+        command.ok_or_else(|| format!("Unknown command {}, expected {}", s.trim(), COMMANDS))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_parsed() {
+        assert_eq!("step 1000".parse(), Ok(Command::Step(1000)));
+        assert_eq!(" step ".parse(), Ok(Command::Step(1)));
+        assert_eq!("break GROW_SYNAPSES".parse(), Ok(Command::InstructionBreakpoint("GROW_SYNAPSES".to_string())));
+        assert_eq!("break 42".parse(), Ok(Command::Breakpoint(42)));
+        assert_eq!(
+            "set PERM_INCREMENT 0.02".parse(),
+            Ok(Command::Set("PERM_INCREMENT".to_string(), "0.02".to_string()))
+        );
+        assert_eq!("inject file.sdr".parse(), Ok(Command::Inject("file.sdr".to_string())));
+        assert_eq!("encode 7/2/10  0:00,21.2".parse(), Ok(Command::Encode("7/2/10 0:00,21.2".to_string())));
+        assert_eq!("sequence seq.txt 10".parse(), Ok(Command::Sequence("seq.txt".to_string(), 10)));
+        assert_eq!("range -1 1.5".parse(), Ok(Command::Range(-1.0, 1.5)));
+        assert_eq!("record session.json".parse(), Ok(Command::Record("session.json".to_string())));
+        assert_eq!("program tm.push".parse(), Ok(Command::Program("tm.push".to_string())));
+        assert_eq!("connect 10.0.0.2".parse(), Ok(Command::Connect("tcp://10.0.0.2".to_string())));
+        assert_eq!("connect ipc:///tmp/bus".parse(), Ok(Command::Connect("ipc:///tmp/bus".to_string())));
+    }
+
+    #[test]
+    fn invalid_commands_are_rejected() {
+        for command in ["", "step many", "break 0", "set PERM_INCREMENT", "encode", "sequence a 0", "range 2 1", "program", "connect a b", "jump 3"] {
+            assert!(command.parse::<Command>().is_err(), "{}", command);
+        }
+    }
+}
@@ -1,20 +1,45 @@
-use log::{debug, error, warn};
+use log::{debug, error, info, warn};
+use tui::layout::Rect;
+use tui_logger::{TuiWidgetEvent, TuiWidgetState};
 
-use self::actions::Actions;
+use self::actions::{Actions, KeyBindings};
+use self::command::Command;
 use self::state::AppState;
+use self::ui::StackHit;
 use crate::app::actions::Action;
 use crate::inputs::key::Key;
+use crate::inputs::mouse::Mouse;
 use crate::io::IoEvent;
-use crate::node::execution::PushExecutor;
-use crate::node::source::Source;
-use crate::pushr::push::state::PushState;
+use crate::node::encoder::{self, InputEncoder};
+use crate::node::listing::Listing;
+use crate::node::sdr;
+use crate::node::runtime::{RunTarget, RuntimeCommand, RuntimeHandle};
+use crate::node::snapshot::Snapshot;
 use crate::pushr::push::random::CodeGenerator;
-use crate::pushr::push::vector::{BoolVector, IntVector};
-use crate::pushr::push::item::Item;
-use crate::pushr::push::io::PushMessage;
-use crate::pushr::push::instructions::{InstructionCache, InstructionSet};
+
+/// Checkpoint file of the save and load actions
+pub const CHECKPOINT_FILE: &str = "phtm.chk";
+
+/// SDR file of the inject action
+pub const SDR_FILE: &str = "input.sdr";
+
+/// Key bindings file, the keys of the actions it does not list are kept
+pub const KEYS_FILE: &str = "phtm.keys";
+
+/// DOT file of the graph export
+pub const GRAPH_FILE: &str = "graph.dot";
+
+/// Steps of the run steps action
+pub const RUN_STEPS: usize = 1000;
+
+/// Range of the scalar encoder until it is set in the palette
+pub const ENCODER_RANGE: (f32, f32) = (0.0, 100.0);
+
+/// Items scrolled by page up and page down
+const SCROLL_STEP: usize = 5;
 
 pub mod actions;
+pub mod command;
 pub mod state;
 pub mod ui;
 
@@ -24,6 +49,39 @@ pub enum AppReturn {
     Continue,
 }
 
+/// Text typed by the user
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Prompt {
+    /// Name of the instruction to run to
+    RunTo(String),
+    /// Filter of the name bindings, applied while typing
+    Search(String),
+    /// Command of the palette
+    Command(String),
+}
+
+impl Prompt {
+    pub fn label(&self) -> &str {
+        match self {
+            Prompt::RunTo(_) => "Run to instruction",
+            Prompt::Search(_) => "Search bindings",
+            Prompt::Command(_) => "",
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        match self {
+            Prompt::RunTo(text) | Prompt::Search(text) | Prompt::Command(text) => text,
+        }
+    }
+
+    fn text_mut(&mut self) -> &mut String {
+        match self {
+            Prompt::RunTo(text) | Prompt::Search(text) | Prompt::Command(text) => text,
+        }
+    }
+}
+
 /// The main application, containing the state
 pub struct App {
     /// We could dispatch an IO event
@@ -33,68 +91,224 @@ pub struct App {
     /// State
     is_loading: bool,
     state: AppState,
-    /// Push State
-    executor: PushExecutor,
-
+    /// Node the app is attached to
+    runtime: RuntimeHandle,
+    /// Last snapshot of the node
+    snapshot: Snapshot,
+    /// Text while it is typed
+    prompt: Option<Prompt>,
+    /// Selected tab of the stack viewer, the last tab shows the bindings
+    stack_tab: usize,
+    /// First item shown by the stack viewer
+    stack_scroll: usize,
+    /// Item of the stack viewer clicked with the mouse
+    stack_item: Option<usize>,
+    /// Bindings are shown if their name contains the filter
+    binding_filter: String,
+    /// Column and cell of the model shown in the inspector
+    selection: Option<(usize, Option<usize>)>,
+    /// Program of the node, unknown for attached nodes
+    listing: Listing,
+    /// Source line of breakpoint toggles
+    cursor: usize,
+    /// Charts of the learning statistics instead of the model
+    show_history: bool,
+    /// Horizontal and vertical offset of the model view
+    model_pan: (u16, u16),
+    /// Position and offset of the model view where the mouse was pressed
+    drag: Option<((u16, u16), (u16, u16))>,
+    /// Page of the logs
+    log_state: TuiWidgetState,
+    /// Minimum and maximum of the numbers encoded as input
+    encoder_range: (f32, f32),
 }
 
 impl App {
-    pub fn new(io_tx: tokio::sync::mpsc::Sender<IoEvent>, bin: String, code: String) -> Self {
-        let actions = vec![Action::Quit].into();
+    pub fn new(io_tx: tokio::sync::mpsc::Sender<IoEvent>, runtime: RuntimeHandle, bindings: KeyBindings) -> Self {
+        let actions = Actions::new(vec![Action::Quit], bindings).unwrap_or_default();
         let is_loading = false;
         let state = AppState::default();
-        let mut executor = PushExecutor::new();
-        executor.initialize();
-
-        // Load program code
-        let program = Source::read_debug_code(code);
-        executor.load(program);
-        // Inject interpreter binary
-        executor.push_state.name_bindings.insert("BIN".to_string(), Item::id(bin)); 
+        let snapshot = runtime.snapshots.borrow().clone();
 
         Self {
             io_tx,
             actions,
             is_loading,
             state,
-            executor,
+            runtime,
+            snapshot,
+            prompt: None,
+            stack_tab: 0,
+            stack_scroll: 0,
+            stack_item: None,
+            binding_filter: String::new(),
+            selection: None,
+            listing: Listing::default(),
+            cursor: 0,
+            show_history: false,
+            model_pan: (0, 0),
+            drag: None,
+            log_state: TuiWidgetState::new(),
+            encoder_range: ENCODER_RANGE,
         }
     }
 
+    /// Encoder of typed inputs and sequence files for the input size of
+    /// the node
+    pub fn input_encoder(&self) -> Result<InputEncoder, String> {
+        let size = self.snapshot.input_size().ok_or_else(|| {
+            let name = sdr::input_size_name(self.snapshot.node_type).unwrap_or("The input size");
+            format!("{} is not defined yet", name)
+        })?;
+        let (min, max) = self.encoder_range;
+        Ok(InputEncoder { size, min, max })
+    }
+
+    /// Shows the source of the program the node runs
+    pub fn load_source(&mut self, code: &str) {
+        self.listing = Listing::new(code);
+        self.cursor = 0;
+    }
+
     /// Handle a user action
     pub async fn do_action(&mut self, key: Key) -> AppReturn {
-        if let Some(action) = self.actions.find(key) {
+        if self.prompt.is_some() {
+            self.do_prompt(key).await;
+            return AppReturn::Continue;
+        }
+        if let Some(action) = self.actions.find(key).copied() {
             debug!("Run action [{:?}]", action);
             match action {
-                Action::Quit => AppReturn::Exit,
-                Action::Sleep => {
-                    if let Some(duration) = self.state.duration().cloned() {
-                        // Sleep is an I/O action, we dispatch on the IO channel that's run on another thread
-                        self.dispatch(IoEvent::Sleep(duration)).await
+                Action::Quit => {
+                    self.command(RuntimeCommand::Shutdown(None)).await;
+                    AppReturn::Exit
+                }
+                Action::RunPause => {
+                    let command = if self.snapshot.paused {
+                        RuntimeCommand::Resume
+                    } else {
+                        RuntimeCommand::Pause
+                    };
+                    self.command(command).await;
+                    AppReturn::Continue
+                }
+                Action::Step => {
+                    self.command(RuntimeCommand::Step(1)).await;
+                    AppReturn::Continue
+                }
+                Action::RunSteps => {
+                    self.command(RuntimeCommand::Step(RUN_STEPS)).await;
+                    AppReturn::Continue
+                }
+                Action::RunToBreakpoint => {
+                    self.command(RuntimeCommand::RunUntil(RunTarget::Breakpoint)).await;
+                    AppReturn::Continue
+                }
+                // The name is typed in the prompt
+                Action::RunToInstruction => {
+                    self.prompt = Some(Prompt::RunTo(String::new()));
+                    AppReturn::Continue
+                }
+                // The budget is doubled or halved
+                Action::Faster => {
+                    let budget = self.snapshot.step_budget.saturating_mul(2);
+                    self.command(RuntimeCommand::SetStepBudget(budget)).await;
+                    AppReturn::Continue
+                }
+                Action::Slower => {
+                    let budget = self.snapshot.step_budget / 2;
+                    self.command(RuntimeCommand::SetStepBudget(budget)).await;
+                    AppReturn::Continue
+                }
+                Action::NextStack | Action::PreviousStack => {
+                    // Stacks and the bindings
+                    let tabs = self.snapshot.stacks.len() + 1;
+                    self.stack_tab = match action {
+                        Action::NextStack => (self.stack_tab + 1) % tabs,
+                        _ => (self.stack_tab + tabs - 1) % tabs,
+                    };
+                    self.stack_scroll = 0;
+                    self.stack_item = None;
+                    AppReturn::Continue
+                }
+                Action::ScrollUp => {
+                    self.stack_scroll = self.stack_scroll.saturating_sub(SCROLL_STEP);
+                    AppReturn::Continue
+                }
+                Action::ScrollDown => {
+                    self.stack_scroll += SCROLL_STEP;
+                    AppReturn::Continue
+                }
+                Action::Search => {
+                    self.stack_tab = self.snapshot.stacks.len();
+                    self.stack_scroll = 0;
+                    self.stack_item = None;
+                    self.prompt = Some(Prompt::Search(self.binding_filter.clone()));
+                    AppReturn::Continue
+                }
+                // The command is typed in the palette
+                Action::Command => {
+                    self.prompt = Some(Prompt::Command(String::new()));
+                    AppReturn::Continue
+                }
+                Action::Select => {
+                    if let Some(direction) = self.actions.position(action, key) {
+                        self.move_selection(direction).await;
                     }
                     AppReturn::Continue
                 }
-                // IncrementDelay and DecrementDelay is handled in the UI thread
-                Action::IncrementDelay => {
-                    self.state.increment_delay();
+                Action::SelectCell => {
+                    self.next_cell().await;
                     AppReturn::Continue
                 }
-                // Note, that we clamp the duration, so we stay >= 0
-                Action::DecrementDelay => {
-                    self.state.decrement_delay();
+                Action::MoveCursor => {
+                    let last = self.listing.lines().len().saturating_sub(1);
+                    self.cursor = match self.actions.position(action, key) {
+                        Some(0) => self.cursor.saturating_sub(1),
+                        _ => (self.cursor + 1).min(last),
+                    };
                     AppReturn::Continue
                 }
-                Action::Step => {
-                    self.executor.step();
+                Action::ToggleHistory => {
+                    self.show_history = !self.show_history;
+                    AppReturn::Continue
+                }
+                // Comments and empty lines are never executed
+                Action::ToggleBreakpoint => {
+                    let line = (self.cursor..self.listing.lines().len()).find(|i| !self.listing.code(*i).is_empty());
+                    if let Some(line) = line {
+                        self.cursor = line;
+                        self.command(RuntimeCommand::ToggleBreakpoint(line)).await;
+                    }
                     AppReturn::Continue
                 }
                 Action::RandomInput => {
-                    if let Some(random_input) = CodeGenerator::random_bool_vector(1024, 0.05) {
-                        let input_msg = PushMessage::new(IntVector::new(vec![]), random_input);
-                        self.executor.push_state.input_stack.push(input_msg);
+                    let random_input = self
+                        .input_encoder()
+                        .and_then(|e| CodeGenerator::random_bool_vector(e.size as i32, encoder::SPARSITY).ok_or_else(String::new));
+                    match random_input {
+                        Ok(random_input) => self.command(RuntimeCommand::Inject(random_input.values)).await,
+                        Err(e) => warn!("{}", e),
                     }
                     AppReturn::Continue
                 }
+                // File operations are dispatched on the IO channel that's run on another thread
+                Action::SaveCheckpoint => {
+                    self.dispatch(IoEvent::SaveCheckpoint(CHECKPOINT_FILE.to_string())).await;
+                    AppReturn::Continue
+                }
+                Action::LoadCheckpoint => {
+                    self.dispatch(IoEvent::LoadCheckpoint(CHECKPOINT_FILE.to_string())).await;
+                    AppReturn::Continue
+                }
+                Action::InjectSdrFile => {
+                    self.dispatch(IoEvent::InjectSdrFile(SDR_FILE.to_string())).await;
+                    AppReturn::Continue
+                }
+                Action::ExportGraph => {
+                    self.dispatch(IoEvent::ExportGraph(GRAPH_FILE.to_string())).await;
+                    AppReturn::Continue
+                }
             }
         } else {
             warn!("No action accociated to {}", key);
@@ -102,14 +316,230 @@ impl App {
         }
     }
 
-    /// We could update the app or dispatch event on tick
+    /// Handle a mouse event, the panes are laid out for the size of the
+    /// terminal. A click selects a source line, a tab or an item of the
+    /// stack viewer or a column or cell of the model, the wheel scrolls
+    /// the pane below it and a drag pans the model.
+    pub async fn do_mouse(&mut self, mouse: Mouse, size: Rect) -> AppReturn {
+        let (panes, position) = match (ui::layout(size), mouse.position()) {
+            (Ok(panes), Some(position)) => (panes, position),
+            _ => return AppReturn::Continue,
+        };
+        let inside = |area: Rect| {
+            (area.x..area.x + area.width).contains(&position.0) && (area.y..area.y + area.height).contains(&position.1)
+        };
+        let model = inside(panes.model) && !self.show_history;
+        match mouse {
+            Mouse::Down(_, row) if inside(panes.source) => {
+                if let Some(line) = ui::source_line_at(&self.listing, self.cursor, panes.source, row) {
+                    self.cursor = line;
+                }
+            }
+            Mouse::Down(..) if inside(panes.stacks) => {
+                let hit = ui::stack_hit_at(
+                    &self.snapshot,
+                    self.stack_tab,
+                    self.stack_scroll,
+                    &self.binding_filter,
+                    panes.stacks,
+                    position,
+                );
+                match hit {
+                    Some(StackHit::Tab(tab)) => {
+                        self.stack_tab = tab;
+                        self.stack_scroll = 0;
+                        self.stack_item = None;
+                    }
+                    Some(StackHit::Item(item)) => self.stack_item = Some(item),
+                    None => (),
+                }
+            }
+            Mouse::Down(..) if model => self.drag = Some((position, self.model_pan)),
+            Mouse::Drag(column, row) => {
+                if let Some(((x, y), (pan_x, pan_y))) = self.drag {
+                    let pan = |pan: u16, from: u16, to: u16| pan.saturating_add(from).saturating_sub(to);
+                    self.pan_model((pan(pan_x, x, column), pan(pan_y, y, row)));
+                }
+            }
+            // A press and release at the same position is a click
+            Mouse::Up(..) => {
+                if let Some((origin, _)) = self.drag.take() {
+                    let hit = self.snapshot.model.as_ref().and_then(|m| ui::model_at(m, self.model_pan, panes.model, position));
+                    if let (true, Some((column, cell))) = (origin == position, hit) {
+                        self.select(column, cell).await;
+                    }
+                }
+            }
+            Mouse::ScrollUp(..) | Mouse::ScrollDown(..) => {
+                let up = matches!(mouse, Mouse::ScrollUp(..));
+                if inside(panes.source) {
+                    let last = self.listing.lines().len().saturating_sub(1);
+                    self.cursor = if up { self.cursor.saturating_sub(1) } else { (self.cursor + 1).min(last) };
+                } else if inside(panes.stacks) {
+                    self.stack_scroll = if up { self.stack_scroll.saturating_sub(1) } else { self.stack_scroll + 1 };
+                } else if inside(panes.logs) {
+                    let event = if up { TuiWidgetEvent::PrevPageKey } else { TuiWidgetEvent::NextPageKey };
+                    self.log_state.transition(&event);
+                } else if model {
+                    let (x, y) = self.model_pan;
+                    self.pan_model((x, if up { y.saturating_sub(1) } else { y.saturating_add(1) }));
+                }
+            }
+            _ => (),
+        }
+        AppReturn::Continue
+    }
+
+    /// Edits the prompt, enter runs to the instruction or the command or
+    /// keeps the filter, escape cancels
+    async fn do_prompt(&mut self, key: Key) {
+        let prompt = match self.prompt.as_mut() {
+            Some(prompt) => prompt,
+            None => return,
+        };
+        match key {
+            // Only commands have arguments
+            Key::Char(c) if !c.is_whitespace() || matches!(prompt, Prompt::Command(_)) => prompt.text_mut().push(c),
+            Key::Backspace => {
+                prompt.text_mut().pop();
+            }
+            Key::Enter => match self.prompt.take() {
+                Some(Prompt::RunTo(name)) if !name.is_empty() => {
+                    self.command(RuntimeCommand::RunUntil(RunTarget::Instruction(name))).await;
+                }
+                Some(Prompt::Command(text)) if !text.trim().is_empty() => match text.parse::<Command>() {
+                    Ok(command) => self.run_command(command).await,
+                    Err(e) => error!("{}", e),
+                },
+                _ => (),
+            },
+            Key::Esc => {
+                if let Some(Prompt::Search(_)) = self.prompt.take() {
+                    self.binding_filter.clear();
+                }
+            }
+            _ => (),
+        }
+        if let Some(Prompt::Search(filter)) = &self.prompt {
+            self.binding_filter = filter.clone();
+            self.stack_scroll = 0;
+            self.stack_item = None;
+        }
+    }
+
+    /// Runs a command of the palette
+    async fn run_command(&mut self, command: Command) {
+        debug!("Run command [{:?}]", command);
+        match command {
+            Command::Step(steps) => self.command(RuntimeCommand::Step(steps)).await,
+            // Comments and empty lines are never executed
+            Command::Breakpoint(line) => {
+                if line > self.listing.lines().len() || self.listing.code(line - 1).is_empty() {
+                    warn!("Line {} has no code", line);
+                } else {
+                    self.cursor = line - 1;
+                    self.command(RuntimeCommand::ToggleBreakpoint(line - 1)).await;
+                }
+            }
+            Command::InstructionBreakpoint(name) => self.command(RuntimeCommand::ToggleInstructionBreakpoint(name)).await,
+            Command::Set(name, value) => self.command(RuntimeCommand::SetParameter(name, value)).await,
+            Command::Inject(file) => self.dispatch(IoEvent::InjectSdrFile(file)).await,
+            Command::Encode(input) => match self.input_encoder().and_then(|e| e.encode(&input)) {
+                Ok(sdr) => self.command(RuntimeCommand::Inject(sdr)).await,
+                Err(e) => error!("{}", e),
+            },
+            Command::Sequence(file, times) => self.dispatch(IoEvent::InjectSequence(file, times)).await,
+            Command::Range(min, max) => {
+                self.encoder_range = (min, max);
+                info!("Numbers are encoded in [{}, {}]", min, max);
+            }
+            Command::Save(file) => self.dispatch(IoEvent::SaveCheckpoint(file)).await,
+            Command::Load(file) => self.dispatch(IoEvent::LoadCheckpoint(file)).await,
+            Command::Export(file) => self.dispatch(IoEvent::ExportGraph(file)).await,
+            Command::Record(file) => self.dispatch(IoEvent::SaveSession(file)).await,
+            Command::Program(file) => self.dispatch(IoEvent::LoadProgram(file)).await,
+            Command::Connect(host) => self.dispatch(IoEvent::ConnectBus(host)).await,
+        }
+    }
+
+    /// Moves the selected column in the grid of the model, the direction
+    /// is the position of the key in the keys of the select action: up,
+    /// down, left, right
+    async fn move_selection(&mut self, direction: usize) {
+        let model = match &self.snapshot.model {
+            Some(model) if !model.columns.is_empty() => model,
+            _ => return,
+        };
+        let (count, row) = (model.columns.len(), model.edge_length.max(1));
+        let column = match self.selection {
+            None => 0,
+            Some((column, _)) => match direction {
+                0 if column >= row => column - row,
+                1 if column + row < count => column + row,
+                2 => column.saturating_sub(1),
+                3 => (column + 1).min(count - 1),
+                _ => column,
+            },
+        };
+        self.select(column, None).await;
+    }
+
+    /// Cycles through the cells of the selected column and the column
+    async fn next_cell(&mut self) {
+        let (column, cell) = match self.selection {
+            Some(selection) => selection,
+            None => return,
+        };
+        let cells = self
+            .snapshot
+            .model
+            .as_ref()
+            .and_then(|m| m.columns.get(column))
+            .map_or(0, |c| c.cells.len());
+        let cell = match cell {
+            None if cells > 0 => Some(0),
+            Some(i) if i + 1 < cells => Some(i + 1),
+            _ => None,
+        };
+        self.select(column, cell).await;
+    }
+
+    async fn select(&mut self, column: usize, cell: Option<usize>) {
+        let id = self.snapshot.model.as_ref().and_then(|m| m.columns.get(column)).and_then(|c| match cell {
+            Some(i) => c.cells.get(i).map(|(id, _)| *id),
+            None => Some(c.id),
+        });
+        self.selection = Some((column, cell));
+        self.command(RuntimeCommand::Inspect(id)).await;
+    }
+
+    /// Pans the model view, at most until its last line and column
+    fn pan_model(&mut self, (x, y): (u16, u16)) {
+        let (width, height) = self.snapshot.model.as_ref().map_or((0, 0), ui::model_size);
+        self.model_pan = (x.min(width.saturating_sub(1)), y.min(height.saturating_sub(1)));
+    }
+
+    /// The node runs on its own, the tick refreshes its snapshot. The
+    /// cursor follows the next instruction while the node is paused.
     pub async fn update_on_tick(&mut self) -> AppReturn {
-        // here we just increment a counter
         self.state.incr_tick();
-        self.executor.step();
+        let (line, paused) = (self.snapshot.line, self.snapshot.paused);
+        self.snapshot = self.runtime.snapshots.borrow_and_update().clone();
+        if let Some(next) = self.snapshot.line {
+            if self.snapshot.paused && (self.snapshot.line != line || !paused) {
+                self.cursor = next;
+            }
+        }
         AppReturn::Continue
     }
 
+    /// Send a command to the node
+    pub async fn command(&mut self, command: RuntimeCommand) {
+        if let Err(e) = self.runtime.commands.send(command).await {
+            error!("Node not reachable: {}", e);
+        }
+    }
+
     /// Send a network event to the IO thread
     pub async fn dispatch(&mut self, action: IoEvent) {
         // `is_loading` will be set to false again after the async action has finished in io/handler.rs
@@ -128,8 +558,52 @@ impl App {
         &self.state
     }
 
-    pub fn push_state(&self) -> &PushState {
-        &self.executor.push_state
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+
+    pub fn selection(&self) -> Option<(usize, Option<usize>)> {
+        self.selection
+    }
+
+    pub fn prompt(&self) -> Option<&Prompt> {
+        self.prompt.as_ref()
+    }
+
+    pub fn stack_tab(&self) -> usize {
+        self.stack_tab
+    }
+
+    pub fn stack_scroll(&self) -> usize {
+        self.stack_scroll
+    }
+
+    pub fn stack_item(&self) -> Option<usize> {
+        self.stack_item
+    }
+
+    pub fn model_pan(&self) -> (u16, u16) {
+        self.model_pan
+    }
+
+    pub fn log_state(&self) -> &TuiWidgetState {
+        &self.log_state
+    }
+
+    pub fn binding_filter(&self) -> &str {
+        &self.binding_filter
+    }
+
+    pub fn listing(&self) -> &Listing {
+        &self.listing
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn show_history(&self) -> bool {
+        self.show_history
     }
 
     pub fn is_loading(&self) -> bool {
@@ -138,21 +612,40 @@ impl App {
 
     pub fn initialized(&mut self) {
         // Update contextual actions
-        self.actions = vec![
+        let actions = vec![
             Action::Quit,
-            Action::Sleep,
-            Action::IncrementDelay,
-            Action::DecrementDelay,
-        ]
-        .into();
+            Action::RunPause,
+            Action::Step,
+            Action::RunSteps,
+            Action::RunToBreakpoint,
+            Action::RunToInstruction,
+            Action::Faster,
+            Action::Slower,
+            Action::Select,
+            Action::SelectCell,
+            Action::NextStack,
+            Action::PreviousStack,
+            Action::ScrollUp,
+            Action::ScrollDown,
+            Action::Search,
+            Action::Command,
+            Action::MoveCursor,
+            Action::ToggleBreakpoint,
+            Action::ToggleHistory,
+            Action::RandomInput,
+            Action::SaveCheckpoint,
+            Action::LoadCheckpoint,
+            Action::InjectSdrFile,
+            Action::ExportGraph,
+        ];
+        match Actions::new(actions, self.actions.bindings().clone()) {
+            Ok(actions) => self.actions = actions,
+            Err(e) => error!("Key bindings: {}", e),
+        }
         self.state = AppState::initialized()
     }
 
     pub fn loaded(&mut self) {
         self.is_loading = false;
     }
-
-    pub fn slept(&mut self) {
-        self.state.incr_sleep();
-    }
 }
@@ -1,51 +1,25 @@
-use std::time::Duration;
-
 #[derive(Clone)]
 pub enum AppState {
     Init,
-    Initialized {
-        duration: Duration,
-        counter_sleep: u32,
-        counter_tick: u64,
-    },
+    Initialized { counter_tick: u64 },
 }
 
 impl AppState {
     pub fn initialized() -> Self {
-        let duration = Duration::from_secs(1);
-        let counter_sleep = 0;
         let counter_tick = 0;
-        Self::Initialized {
-            duration,
-            counter_sleep,
-            counter_tick,
-        }
+        Self::Initialized { counter_tick }
     }
 
     pub fn is_initialized(&self) -> bool {
         matches!(self, &Self::Initialized { .. })
     }
 
-    pub fn incr_sleep(&mut self) {
-        if let Self::Initialized { counter_sleep, .. } = self {
-            *counter_sleep += 1;
-        }
-    }
-
     pub fn incr_tick(&mut self) {
         if let Self::Initialized { counter_tick, .. } = self {
             *counter_tick += 1;
         }
     }
 
-    pub fn count_sleep(&self) -> Option<u32> {
-        if let Self::Initialized { counter_sleep, .. } = self {
-            Some(*counter_sleep)
-        } else {
-            None
-        }
-    }
-
     pub fn count_tick(&self) -> Option<u64> {
         if let Self::Initialized { counter_tick, .. } = self {
             Some(*counter_tick)
@@ -53,30 +27,6 @@ impl AppState {
             None
         }
     }
-
-    pub fn duration(&self) -> Option<&Duration> {
-        if let Self::Initialized { duration, .. } = self {
-            Some(duration)
-        } else {
-            None
-        }
-    }
-
-    pub fn increment_delay(&mut self) {
-        if let Self::Initialized { duration, .. } = self {
-            // Set the duration, note that the duration is in 1s..10s
-            let secs = (duration.as_secs() + 1).clamp(1, 10);
-            *duration = Duration::from_secs(secs);
-        }
-    }
-
-    pub fn decrement_delay(&mut self) {
-        if let Self::Initialized { duration, .. } = self {
-            // Set the duration, note that the duration is in 1s..10s
-            let secs = (duration.as_secs() - 1).clamp(1, 10);
-            *duration = Duration::from_secs(secs);
-        }
-    }
 }
 
 impl Default for AppState {
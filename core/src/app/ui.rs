@@ -1,26 +1,46 @@
-use std::time::Duration;
+use std::convert::TryFrom;
 
-use symbols::line;
 use tui::backend::Backend;
 use tui::layout::{Alignment, Constraint, Direction, Layout, Rect};
 use tui::style::{Color, Modifier, Style};
+use tui::symbols::Marker;
 use tui::text::{Span, Spans};
-use tui::widgets::{Block, BorderType, Borders, Cell, LineGauge, Paragraph, Row, Table};
-use tui::{symbols, Frame};
+use tui::widgets::{
+    Axis, Block, BorderType, Borders, Cell, Chart, Dataset, GraphType, Paragraph, Row, Sparkline, Table, Wrap,
+};
+use tui::Frame;
 use tui_logger::TuiLoggerWidget;
 
 use super::actions::Actions;
 use super::state::AppState;
-use crate::app::App;
-use crate::pushr::push::state::PushState;
-use crate::pushr::push::item::Item;
+use crate::app::{App, Prompt};
+use crate::node::history::Sample;
+use crate::node::listing::Listing;
+use crate::node::runtime::RunTarget;
+use crate::node::snapshot::{ColumnView, Inspection, ModelView, Snapshot};
+
+/// Inputs per row of the input SDR
+const INPUT_ROW_LENGTH: usize = 32;
+
+/// Smallest terminal the layout is drawn in
+const MIN_WIDTH: u16 = 52;
+const MIN_HEIGHT: u16 = 28;
+
+/// Areas of the panes, the mouse is located with them as well
+pub struct Panes {
+    pub title: Rect,
+    pub help: Rect,
+    pub source: Rect,
+    pub stacks: Rect,
+    pub body: Rect,
+    pub inspector: Rect,
+    pub model: Rect,
+    pub logs: Rect,
+}
 
-pub fn draw<B>(rect: &mut Frame<B>, app: &App)
-where
-    B: Backend,
-{
-    let size = rect.size();
-    check_size(&size);
+/// Lays the panes out, fails if the terminal is too small
+pub fn layout(size: Rect) -> Result<Panes, String> {
+    check_size(&size)?;
 
     // Vertical layout
     let chunks = Layout::default()
@@ -28,63 +48,112 @@ where
         .constraints(
             [
                 Constraint::Length(3),
-                Constraint::Length(5),
-                Constraint::Min(10),
-                Constraint::Length(3),
+                Constraint::Min(20),
                 Constraint::Length(12),
             ]
             .as_ref(),
         )
         .split(size);
 
-    // Title
-    let title = draw_title();
-    rect.render_widget(title, chunks[0]);
-
-    // Stacks 
-    let stack_chunks = Layout::default()
+    // Main & Help
+    let main_chunks = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Length(10), Constraint::Length(10), Constraint::Length(10), Constraint::Length(10)].as_ref())
+        .constraints([Constraint::Min(40), Constraint::Length(32)].as_ref())
         .split(chunks[1]);
 
-    let body = draw_bool_stack(app.is_loading(), app.app_state(), app.push_state());
-    rect.render_widget(body, stack_chunks[0]);
-
-    let body = draw_int_stack(app.is_loading(), app.app_state(), app.push_state());
-    rect.render_widget(body, stack_chunks[1]);
-
-    let body = draw_float_stack(app.is_loading(), app.app_state(), app.push_state());
-    rect.render_widget(body, stack_chunks[2]);
+    // Source & Stacks above the Body
+    let stack_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(14), Constraint::Min(8)].as_ref())
+        .split(main_chunks[0]);
 
-    let body = draw_exec_stack(app.is_loading(), app.app_state(), app.push_state());
-    rect.render_widget(body, stack_chunks[3]);
+    let source_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(55), Constraint::Percentage(45)].as_ref())
+        .split(stack_chunks[0]);
 
-    // Body & Help
+    // Body & Model
     let body_chunks = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Min(20), Constraint::Length(32)].as_ref())
-        .split(chunks[2]);
+        .constraints([Constraint::Length(44), Constraint::Min(20)].as_ref())
+        .split(stack_chunks[1]);
 
-    let body = draw_body(app.is_loading(), app.app_state(), app.push_state());
-    rect.render_widget(body, body_chunks[0]);
+    // Body & Inspector
+    let inspector_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(8), Constraint::Min(4)].as_ref())
+        .split(body_chunks[0]);
+
+    Ok(Panes {
+        title: chunks[0],
+        help: main_chunks[1],
+        source: source_chunks[0],
+        stacks: source_chunks[1],
+        body: inspector_chunks[0],
+        inspector: inspector_chunks[1],
+        model: body_chunks[1],
+        logs: chunks[2],
+    })
+}
 
+pub fn draw<B>(rect: &mut Frame<B>, app: &App)
+where
+    B: Backend,
+{
+    let size = rect.size();
+    let panes = match layout(size) {
+        Ok(panes) => panes,
+        Err(e) => {
+            rect.render_widget(draw_too_small(e), size);
+            return;
+        }
+    };
 
-    let help = draw_help(app.actions());
-    rect.render_widget(help, body_chunks[1]);
+    let title = draw_title(app.snapshot());
+    rect.render_widget(title, panes.title);
 
-    // Duration LineGauge
-    if let Some(duration) = app.app_state().duration() {
-        let duration_block = draw_duration(duration);
-        rect.render_widget(duration_block, chunks[3]);
+    let help = draw_help(app.actions());
+    rect.render_widget(help, panes.help);
+
+    let source = draw_source(app.listing(), app.snapshot(), app.cursor(), inner_height(panes.source));
+    rect.render_widget(source, panes.source);
+
+    let stacks = draw_stacks(
+        app.snapshot(),
+        app.stack_tab(),
+        app.stack_scroll(),
+        app.stack_item(),
+        app.binding_filter(),
+        inner_height(panes.stacks).saturating_sub(1),
+        panes.stacks.width.saturating_sub(2) as usize,
+    );
+    rect.render_widget(stacks, panes.stacks);
+
+    let body = draw_body(app.is_loading(), app.app_state(), app.snapshot(), app.prompt());
+    rect.render_widget(body, panes.body);
+
+    let inspector = draw_inspector(app.snapshot());
+    rect.render_widget(inspector, panes.inspector);
+
+    if app.show_history() {
+        draw_history(rect, panes.model, &app.snapshot().history);
+    } else {
+        let model = draw_model(app.snapshot().model.as_ref(), app.selection(), app.model_pan());
+        rect.render_widget(model, panes.model);
     }
 
-    // Logs
-    let logs = draw_logs();
-    rect.render_widget(logs, chunks[4]);
+    let mut logs = draw_logs();
+    logs.state(app.log_state());
+    rect.render_widget(logs, panes.logs);
+}
+
+/// Lines inside the borders
+fn inner_height(area: Rect) -> usize {
+    area.height.saturating_sub(2) as usize
 }
 
-fn draw_title<'a>() -> Paragraph<'a> {
-    Paragraph::new("Plop with TUI")
+fn draw_title<'a>(snapshot: &Snapshot) -> Paragraph<'a> {
+    Paragraph::new(format!("PHTM node {} ({:?})", snapshot.node_id, snapshot.node_type))
         .style(Style::default().fg(Color::LightCyan))
         .alignment(Alignment::Center)
         .block(
@@ -95,38 +164,60 @@ fn draw_title<'a>() -> Paragraph<'a> {
         )
 }
 
-fn check_size(rect: &Rect) {
-    if rect.width < 52 {
-        panic!("Require width >= 52, (got {})", rect.width);
+fn check_size(rect: &Rect) -> Result<(), String> {
+    if rect.width < MIN_WIDTH {
+        return Err(format!("Require width >= {} (got {})", MIN_WIDTH, rect.width));
     }
-    if rect.height < 28 {
-        panic!("Require height >= 28, (got {})", rect.height);
+    if rect.height < MIN_HEIGHT {
+        return Err(format!("Require height >= {} (got {})", MIN_HEIGHT, rect.height));
     }
+    Ok(())
 }
 
-fn draw_body<'a>(loading: bool, app_state: &AppState, push_state: &PushState) -> Paragraph<'a> {
+/// Shown instead of the layout until the terminal is resized, keys keep
+/// working
+fn draw_too_small<'a>(reason: String) -> Paragraph<'a> {
+    let lines = vec![
+        Spans::from(Span::styled("Terminal too small", Style::default().fg(Color::LightRed))),
+        Spans::from(Span::raw(reason)),
+    ];
+    Paragraph::new(lines)
+        .style(Style::default().fg(Color::White))
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true })
+}
+
+fn draw_body<'a>(loading: bool, app_state: &AppState, snapshot: &Snapshot, prompt: Option<&Prompt>) -> Paragraph<'a> {
     let initialized_text = if app_state.is_initialized() {
         "Initialized"
     } else {
         "Not Initialized !"
     };
     let loading_text = if loading { "Loading..." } else { "" };
-    let sleep_text = if let Some(sleeps) = app_state.count_sleep() {
-        format!("Sleep count: {}", sleeps)
-    } else {
-        String::default()
-    };
     let tick_text = if let Some(ticks) = app_state.count_tick() {
         format!("Tick count: {}", ticks)
     } else {
         String::default()
     };
+    let node_text = format!(
+        "Node {}: {}{}",
+        snapshot.node_id,
+        snapshot.state,
+        if snapshot.paused { " (paused)" } else { "" }
+    );
+    let steps_text = format!("Steps: {} (budget {})", snapshot.steps, snapshot.step_budget);
+    let prompt_text = match prompt {
+        Some(prompt) => format!("{}: {}_", prompt.label(), prompt.text()),
+        None => String::default(),
+    };
 
     Paragraph::new(vec![
         Spans::from(Span::raw(initialized_text)),
         Spans::from(Span::raw(loading_text)),
-        Spans::from(Span::raw(sleep_text)),
         Spans::from(Span::raw(tick_text)),
+        Spans::from(Span::raw(node_text)),
+        Spans::from(Span::raw(steps_text)),
+        Spans::from(Span::raw(prompt_text)),
     ])
     .style(Style::default().fg(Color::LightCyan))
     .alignment(Alignment::Left)
@@ -139,180 +230,492 @@ fn draw_body<'a>(loading: bool, app_state: &AppState, push_state: &PushState) ->
     )
 
 }
-fn draw_item(item: &Item) -> String {
-    match item {
-        Item::List { items } =>return "( ... )".to_string(),
-        _ => return item.to_string(),
-    }
+/// First line shown, the cursor is kept in the middle
+fn source_start(lines: usize, cursor: usize, height: usize) -> usize {
+    cursor.saturating_sub(height / 2).min(lines.saturating_sub(height))
 }
 
-fn draw_exec_stack<'a>(loading: bool, app_state: &AppState, push_state: &PushState) -> Paragraph<'a>{
-
+/// Line of the source at a row of the terminal
+pub fn source_line_at(listing: &Listing, cursor: usize, area: Rect, row: u16) -> Option<usize> {
+    let offset = row.checked_sub(area.y + 1).map(usize::from).filter(|o| *o < inner_height(area))?;
+    let line = source_start(listing.lines().len(), cursor, inner_height(area)) + offset;
+    (line < listing.lines().len()).then_some(line)
+}
 
-    let exec_pos1_text = if let Some(item) = push_state.exec_stack.get(0) {
-        format!("1: {}", draw_item(item))
-    } else {
-        format!("1: ")
-    };
-    let exec_pos2_text = if let Some(item) = push_state.exec_stack.get(1) {
-        format!("2: {}", draw_item(item))
-    } else {
-        format!("2: ")
-    };
-    let exec_pos3_text = if let Some(item) = push_state.exec_stack.get(2) {
-        format!("3: {}", draw_item(item))
-    } else {
-        format!("3: ")
+/// Program source around the cursor. The next instruction is
+/// highlighted, breakpoints of the front end are marked with * (lines
+/// with a breakpoint instruction as well) and the breakpoint
+/// instructions of the program (BP0, BP1, ...) coloured.
+fn draw_source<'a>(listing: &Listing, snapshot: &Snapshot, cursor: usize, height: usize) -> Paragraph<'a> {
+    let lines = listing.lines();
+    let start = source_start(lines.len(), cursor, height);
+    let mut text = vec![];
+    for (i, line) in lines.iter().enumerate().skip(start).take(height) {
+        let code = listing.code(i);
+        let mut style = if code.is_empty() {
+            Style::default().fg(Color::DarkGray)
+        } else if code.iter().any(|token| RunTarget::Breakpoint.reached(token)) {
+            Style::default().fg(Color::Magenta)
+        } else {
+            Style::default().fg(Color::White)
+        };
+        if snapshot.line == Some(i) {
+            style = style.fg(Color::Black).bg(Color::Yellow);
+        }
+        if i == cursor {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        let breakpoint = snapshot.breakpoints.contains(&i)
+            || code.iter().any(|token| snapshot.instruction_breakpoints.iter().any(|name| name == token));
+        let marker = if breakpoint { "*" } else { " " };
+        let next = if snapshot.line == Some(i) { ">" } else { " " };
+        text.push(Spans::from(vec![
+            Span::styled(format!("{}{}{:>4} ", marker, next, i + 1), Style::default().fg(Color::Red)),
+            Span::styled(line.replace('\t', "    "), style),
+        ]));
+    }
+    if lines.is_empty() {
+        text.push(Spans::from(Span::raw("Source of attached nodes is not available")));
+    }
+    let title = match snapshot.line {
+        Some(line) => format!(
+            "Source line {}{}",
+            line + 1,
+            listing.routine(line).map_or(String::default(), |r| format!(" in {}", r.name))
+        ),
+        None => "Source".to_string(),
     };
-
-    Paragraph::new(vec![
-        Spans::from(Span::raw(exec_pos1_text)),
-        Spans::from(Span::raw(exec_pos2_text)),
-        Spans::from(Span::raw(exec_pos3_text)),
-    ])
-    .style(Style::default().fg(Color::LightCyan))
-    .alignment(Alignment::Left)
-    .block(
+    Paragraph::new(text).block(
         Block::default()
-             .title("EXEC")
+            .title(title)
             .borders(Borders::ALL)
             .style(Style::default().fg(Color::White))
             .border_type(BorderType::Plain),
     )
 }
 
+/// Names of the tabs, the stacks and the bindings
+fn stack_tabs(snapshot: &Snapshot) -> Vec<&str> {
+    snapshot
+        .stacks
+        .iter()
+        .map(|s| s.name.as_str())
+        .chain(std::iter::once("BINDINGS"))
+        .collect()
+}
 
-fn draw_bool_stack<'a>(loading: bool, app_state: &AppState, push_state: &PushState) -> Paragraph<'a>{
+/// First tab shown, tabs before the selected one are left out if the
+/// line is too short
+fn first_tab(names: &[&str], tab: usize, width: usize) -> usize {
+    let mut first = 0;
+    while first < tab && names[first..=tab.min(names.len() - 1)].iter().map(|n| n.len() + 1).sum::<usize>() > width {
+        first += 1;
+    }
+    first
+}
 
-    let bool_pos1_text = if let Some(item) = push_state.bool_stack.get(0) {
-        format!("1: {}", item)
-    } else {
-        format!("1: ")
-    };
-    let bool_pos2_text = if let Some(item) = push_state.bool_stack.get(1) {
-        format!("2: {}", item)
-    } else {
-        format!("2: ")
-    };
-    let bool_pos3_text = if let Some(item) = push_state.bool_stack.get(2) {
-        format!("3: {}", item)
-    } else {
-        format!("3: ")
-    };
+/// Title and items of the selected tab
+fn stack_items(snapshot: &Snapshot, tab: usize, filter: &str) -> (String, Vec<String>) {
+    match snapshot.stacks.get(tab) {
+        Some(stack) => (
+            format!("{} ({} items)", stack.name, stack.size),
+            stack.items.iter().enumerate().map(|(i, item)| format!("{:>3}: {}", i + 1, item)).collect(),
+        ),
+        None => {
+            let bindings: Vec<&(String, String)> = snapshot
+                .bindings
+                .iter()
+                .filter(|(name, _)| name.to_lowercase().contains(&filter.to_lowercase()))
+                .collect();
+            let width = bindings.iter().map(|(name, _)| name.len()).max().unwrap_or_default();
+            (
+                format!("BINDINGS ({} of {}, filter '{}')", bindings.len(), snapshot.bindings.len(), filter),
+                bindings
+                    .iter()
+                    .map(|(name, label)| format!("{:<width$} {}", name, label, width = width))
+                    .collect(),
+            )
+        }
+    }
+}
 
-    Paragraph::new(vec![
-        Spans::from(Span::raw(bool_pos1_text)),
-        Spans::from(Span::raw(bool_pos2_text)),
-        Spans::from(Span::raw(bool_pos3_text)),
-    ])
-    .style(Style::default().fg(Color::LightCyan))
-    .alignment(Alignment::Left)
-    .block(
+/// Tab or item of the stack viewer at a position of the terminal
+pub enum StackHit {
+    Tab(usize),
+    Item(usize),
+}
+
+pub fn stack_hit_at(
+    snapshot: &Snapshot,
+    tab: usize,
+    scroll: usize,
+    filter: &str,
+    area: Rect,
+    (column, row): (u16, u16),
+) -> Option<StackHit> {
+    let x = column.checked_sub(area.x + 1).map(usize::from)?;
+    let y = row.checked_sub(area.y + 1).map(usize::from).filter(|y| *y < inner_height(area))?;
+    if y == 0 {
+        let names = stack_tabs(snapshot);
+        let mut end = 0;
+        for (i, name) in names.iter().enumerate().skip(first_tab(&names, tab, area.width.saturating_sub(2) as usize)) {
+            end += name.len() + 1;
+            if x < end {
+                return Some(StackHit::Tab(i));
+            }
+        }
+        return None;
+    }
+    let items = stack_items(snapshot, tab, filter).1.len();
+    let item = scroll.min(items.saturating_sub(1)) + y - 1;
+    (item < items).then_some(StackHit::Item(item))
+}
+
+/// Tabs of all stacks and the name bindings above the items of the
+/// selected tab, starting at the scroll position. The clicked item is
+/// reversed.
+fn draw_stacks<'a>(
+    snapshot: &Snapshot,
+    tab: usize,
+    scroll: usize,
+    item: Option<usize>,
+    filter: &str,
+    height: usize,
+    width: usize,
+) -> Paragraph<'a> {
+    let mut tabs: Vec<Span> = vec![];
+    let names = stack_tabs(snapshot);
+    let first = first_tab(&names, tab, width);
+    for (i, name) in names.into_iter().enumerate().skip(first) {
+        let style = if i == tab {
+            Style::default().fg(Color::LightCyan).add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default().fg(Color::Gray)
+        };
+        tabs.push(Span::styled(name.to_string(), style));
+        tabs.push(Span::raw(" "));
+    }
+    let (title, items) = stack_items(snapshot, tab, filter);
+    let scroll = scroll.min(items.len().saturating_sub(1));
+    let mut lines = vec![Spans::from(tabs)];
+    lines.extend(items.into_iter().enumerate().skip(scroll).take(height).map(|(i, text)| {
+        let style = if item == Some(i) {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        };
+        Spans::from(Span::styled(text, style))
+    }));
+    Paragraph::new(lines).block(
         Block::default()
-            .title("BOOL")
+            .title(title)
             .borders(Borders::ALL)
             .style(Style::default().fg(Color::White))
             .border_type(BorderType::Plain),
     )
 }
 
-fn draw_float_stack<'a>(loading: bool, app_state: &AppState, push_state: &PushState) -> Paragraph<'a>{
+fn state_color(name: Option<&str>) -> Color {
+    match name {
+        Some("INPUT_ON") => Color::Yellow,
+        Some("COLUMN_ACTIVE") | Some("CELL_ACTIVE") => Color::Green,
+        Some("CELL_ACTIVE_WINNER") => Color::LightGreen,
+        Some("CELL_WINNER") => Color::Cyan,
+        Some("CELL_PREDICTIVE") => Color::Magenta,
+        _ => Color::DarkGray,
+    }
+}
 
-    let float_pos1_text = if let Some(item) = push_state.float_stack.get(0) {
-        format!("1: {}", item)
-    } else {
-        String::default()
-    };
-    let float_pos2_text = if let Some(item) = push_state.float_stack.get(1) {
-        format!("2: {}", item)
-    } else {
-        String::default()
-    };
-    let float_pos3_text = if let Some(item) = push_state.float_stack.get(2) {
-        format!("3: {}", item)
+/// Rows of the input SDR and the space below it
+fn input_rows(model: &ModelView) -> usize {
+    match model.inputs.len() {
+        0 => 0,
+        n => n.div_ceil(INPUT_ROW_LENGTH) + 1,
+    }
+}
+
+/// Column and cell at a position of the terminal, the model is panned
+/// by the offset. Follows the layout of `draw_model`.
+pub fn model_at(
+    model: &ModelView,
+    pan: (u16, u16),
+    area: Rect,
+    (column, row): (u16, u16),
+) -> Option<(usize, Option<usize>)> {
+    let x = usize::from(column.checked_sub(area.x + 1)?.saturating_add(pan.0));
+    let y = usize::from(row.checked_sub(area.y + 1)?.saturating_add(pan.1));
+    let edge_length = model.edge_length.max(1);
+    let r = y.checked_sub(input_rows(model))?;
+    let mut start = 0;
+    for (c, view) in model.columns.iter().enumerate().skip(r * edge_length).take(edge_length) {
+        // Space between two columns
+        if x < start {
+            return None;
+        }
+        let cells = view.cells.len();
+        let width = column_width(view);
+        if x < start + width {
+            let cell = (cells > 0 && x > start && x <= start + cells).then(|| x - start - 1);
+            return Some((c, cell));
+        }
+        start += width + 1;
+    }
+    None
+}
+
+/// Characters taken by a column in its row
+fn column_width(column: &ColumnView) -> usize {
+    match column.cells.len() {
+        0 => 2,
+        cells => cells + 2,
+    }
+}
+
+/// States of the columns, cells and inputs, shown in the legend
+fn model_states(model: &ModelView) -> Vec<i32> {
+    let mut states: Vec<i32> = model
+        .columns
+        .iter()
+        .flat_map(|c| std::iter::once(c.state).chain(c.cells.iter().map(|(_, s)| *s)))
+        .chain(model.inputs.iter().map(|(_, s)| *s))
+        .collect();
+    states.sort_unstable();
+    states.dedup();
+    states
+}
+
+/// Width and height of the lines of `draw_model`, the view is panned
+/// within them
+pub fn model_size(model: &ModelView) -> (u16, u16) {
+    let edge_length = model.edge_length.max(1);
+    let width = model
+        .columns
+        .chunks(edge_length)
+        .map(|row| row.iter().map(|c| column_width(c) + 1).sum())
+        .chain(std::iter::once(model.inputs.len().min(INPUT_ROW_LENGTH)))
+        .max()
+        .unwrap_or_default();
+    let height = input_rows(model) + model.columns.len().div_ceil(edge_length) + 1 + model_states(model).len();
+    let clamp = |size: usize| u16::try_from(size).unwrap_or(u16::MAX);
+    (clamp(width), clamp(height))
+}
+
+/// Input SDR above the grid of columns, each column is drawn as a
+/// block or as its cells, coloured by state. The selected column or
+/// cell is reversed. The view is panned by the horizontal and vertical
+/// offset.
+fn draw_model<'a>(
+    model: Option<&ModelView>,
+    selection: Option<(usize, Option<usize>)>,
+    pan: (u16, u16),
+) -> Paragraph<'a> {
+    let mut lines = vec![];
+    if let Some(model) = model {
+        let styled = |symbol: &'a str, state: i32| {
+            Span::styled(symbol, Style::default().fg(state_color(model.state_name(state))))
+        };
+        let selected = |mut span: Span<'a>, column: usize, cell: Option<usize>| {
+            if selection == Some((column, cell)) {
+                span.style = span.style.add_modifier(Modifier::REVERSED);
+            }
+            span
+        };
+        for row in model.inputs.chunks(INPUT_ROW_LENGTH) {
+            lines.push(Spans::from(row.iter().map(|(_, state)| styled("■", *state)).collect::<Vec<_>>()));
+        }
+        if !model.inputs.is_empty() {
+            lines.push(Spans::default());
+        }
+        for (r, row) in model.columns.chunks(model.edge_length.max(1)).enumerate() {
+            let mut spans = vec![];
+            for (c, column) in row.iter().enumerate() {
+                let i = r * model.edge_length.max(1) + c;
+                if column.cells.is_empty() {
+                    spans.push(selected(styled("██", column.state), i, None));
+                } else {
+                    spans.push(selected(styled("[", column.state), i, None));
+                    for (j, (_, state)) in column.cells.iter().enumerate() {
+                        spans.push(selected(styled("●", *state), i, Some(j)));
+                    }
+                    spans.push(selected(styled("]", column.state), i, None));
+                }
+                spans.push(Span::raw(" "));
+            }
+            lines.push(Spans::from(spans));
+        }
+        // Legend of the states in the view
+        lines.push(Spans::default());
+        for state in model_states(model) {
+            let name = model.state_name(state).map(|n| n.to_string()).unwrap_or_else(|| state.to_string());
+            lines.push(Spans::from(vec![styled("■ ", state), Span::raw(name)]));
+        }
     } else {
-        String::default()
-    };
+        lines.push(Spans::from(Span::raw("No columns")));
+    }
+    Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title("Model")
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .border_type(BorderType::Plain),
+        )
+        .scroll((pan.1, pan.0))
+}
 
-    Paragraph::new(vec![
-        Spans::from(Span::raw(float_pos1_text)),
-        Spans::from(Span::raw(float_pos2_text)),
-        Spans::from(Span::raw(float_pos3_text)),
+/// Label and value of a sparkline of the history
+type Series = (&'static str, fn(&Sample) -> u64);
+
+/// Learning statistics of the recent inputs: anomaly and mean
+/// permanence as chart, the counts as sparklines after their last value
+fn draw_history<B>(rect: &mut Frame<B>, area: Rect, history: &[Sample])
+where
+    B: Backend,
+{
+    let block = Block::default()
+        .title(format!("History ({} inputs)", history.len()))
+        .borders(Borders::ALL)
+        .style(Style::default().fg(Color::White))
+        .border_type(BorderType::Plain);
+    let inner = block.inner(area);
+    rect.render_widget(block, area);
+
+    let series: [Series; 5] = [
+        ("Input bits", |s| s.input as u64),
+        ("Active columns", |s| s.active_columns as u64),
+        ("Bursting columns", |s| s.bursting_columns as u64),
+        ("Predictive cells", |s| s.predictive_cells as u64),
+        ("Steps per input", |s| s.steps),
+    ];
+    let mut constraints = vec![Constraint::Min(3)];
+    constraints.extend(series.iter().map(|_| Constraint::Length(1)));
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(constraints)
+        .split(inner);
+
+    let points = |value: fn(&Sample) -> f32| -> Vec<(f64, f64)> {
+        history.iter().enumerate().map(|(i, s)| (i as f64, value(s) as f64)).collect()
+    };
+    let (anomaly, permanence) = (points(|s| s.anomaly), points(|s| s.mean_permanence));
+    let last = history.last().cloned().unwrap_or_default();
+    let chart = Chart::new(vec![
+        Dataset::default()
+            .name(format!("anomaly {:.2}", last.anomaly))
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Red))
+            .data(&anomaly),
+        Dataset::default()
+            .name(format!("permanence {:.3}", last.mean_permanence))
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Green))
+            .data(&permanence),
     ])
-    .style(Style::default().fg(Color::LightCyan))
-    .alignment(Alignment::Left)
-    .block(
-        Block::default()
-            .title("FLOAT")
-            .borders(Borders::ALL)
-            .style(Style::default().fg(Color::White))
-            .border_type(BorderType::Plain),
+    .x_axis(Axis::default().bounds([0.0, history.len().saturating_sub(1).max(1) as f64]))
+    .y_axis(
+        Axis::default()
+            .bounds([0.0, 1.0])
+            .labels(vec![Span::raw("0"), Span::raw("1")])
+            .style(Style::default().fg(Color::DarkGray)),
     )
+    .hidden_legend_constraints((Constraint::Percentage(100), Constraint::Percentage(100)));
+    rect.render_widget(chart, rows[0]);
+
+    for ((label, value), row) in series.iter().zip(rows.iter().skip(1)) {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(24), Constraint::Min(1)].as_ref())
+            .split(*row);
+        let text = format!("{:<16} {:>7}", label, value(&last));
+        rect.render_widget(Paragraph::new(text).style(Style::default().fg(Color::Gray)), columns[0]);
+        // The sparkline starts with the oldest value that fits
+        let data: Vec<u64> = history.iter().map(value).collect();
+        let data = &data[data.len().saturating_sub(columns[1].width as usize)..];
+        rect.render_widget(Sparkline::default().data(data).style(Style::default().fg(Color::Cyan)), columns[1]);
+    }
 }
 
-fn draw_int_stack<'a>(loading: bool, app_state: &AppState, push_state: &PushState) -> Paragraph<'a>{
+fn state_label(model: Option<&ModelView>, state: i32) -> String {
+    model
+        .and_then(|m| m.state_name(state))
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| state.to_string())
+}
 
-    let int_pos1_text = if let Some(item) = push_state.int_stack.get(0) {
-        format!("1: {}", item)
-    } else {
-        format!("1: ")
-    };
-    let int_pos2_text = if let Some(item) = push_state.int_stack.get(1) {
-        format!("2: {}", item)
-    } else {
-        format!("2: ")
-    };
-    let int_pos3_text = if let Some(item) = push_state.int_stack.get(2) {
-        format!("3: {}", item)
-    } else {
-        format!("3: ")
+/// Synapses of the selected column or cell, connected permanences are
+/// highlighted
+fn draw_inspector<'a>(snapshot: &Snapshot) -> Paragraph<'a> {
+    let model = snapshot.model.as_ref();
+    let mut lines = vec![];
+    let synapse = |id: usize, permanence: f32, threshold: Option<f32>| {
+        let connected = threshold.is_some_and(|t| permanence >= t);
+        let style = if connected {
+            Style::default().fg(Color::Green)
+        } else {
+            Style::default().fg(Color::Gray)
+        };
+        Spans::from(Span::styled(format!("  {:>6} {:.3}{}", id, permanence, if connected { " *" } else { "" }), style))
     };
-
-    Paragraph::new(vec![
-        Spans::from(Span::raw(int_pos1_text)),
-        Spans::from(Span::raw(int_pos2_text)),
-        Spans::from(Span::raw(int_pos3_text)),
-    ])
-    .style(Style::default().fg(Color::LightCyan))
-    .alignment(Alignment::Left)
-    .block(
+    match &snapshot.inspection {
+        None => lines.push(Spans::from(Span::raw("Select a column with the arrow keys"))),
+        Some(inspection) => {
+            let Inspection {
+                id,
+                state,
+                boost,
+                proximal,
+                segments,
+                proximal_threshold,
+                distal_threshold,
+            } = inspection;
+            lines.push(Spans::from(Span::raw(format!("Node {}: {}", id, state_label(model, *state)))));
+            if let Some(boost) = boost {
+                lines.push(Spans::from(Span::raw(format!(
+                    "Boost cell {}: factor {:.3}, overlap {:.3}",
+                    boost.id, boost.factor, boost.overlap
+                ))));
+            }
+            if !proximal.is_empty() {
+                lines.push(Spans::from(Span::raw(format!(
+                    "Proximal: {}/{} connected{}",
+                    inspection.connected().map_or("?".to_string(), |c| c.to_string()),
+                    proximal.len(),
+                    proximal_threshold.map_or(String::default(), |t| format!(" (>= {})", t)),
+                ))));
+                lines.extend(proximal.iter().map(|(id, p)| synapse(*id, *p, *proximal_threshold)));
+            }
+            if !segments.is_empty() {
+                lines.push(Spans::from(Span::raw(format!("Distal segments: {}", segments.len()))));
+            }
+            for segment in segments.iter() {
+                lines.push(Spans::from(Span::raw(format!(
+                    " Segment {}: {}",
+                    segment.id,
+                    state_label(model, segment.state)
+                ))));
+                lines.extend(segment.synapses.iter().map(|(id, p)| synapse(*id, *p, *distal_threshold)));
+            }
+        }
+    }
+    Paragraph::new(lines).block(
         Block::default()
-            .title("INT")
+            .title("Inspector")
             .borders(Borders::ALL)
             .style(Style::default().fg(Color::White))
             .border_type(BorderType::Plain),
     )
 }
 
-fn draw_duration(duration: &Duration) -> LineGauge {
-    let sec = duration.as_secs();
-    let label = format!("{}s", sec);
-    let ratio = sec as f64 / 10.0;
-    LineGauge::default()
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Sleep duration"),
-        )
-        .gauge_style(
-            Style::default()
-                .fg(Color::Cyan)
-                .bg(Color::Black)
-                .add_modifier(Modifier::BOLD),
-        )
-        .line_set(line::THICK)
-        .label(label)
-        .ratio(ratio)
-}
-
-fn draw_help(actions: &Actions) -> Table {
+fn draw_help(actions: &Actions) -> Table<'_> {
     let key_style = Style::default().fg(Color::LightCyan);
     let help_style = Style::default().fg(Color::Gray);
 
     let mut rows = vec![];
     for action in actions.actions().iter() {
         let mut first = true;
-        for key in action.keys() {
+        for key in actions.keys(*action) {
             let help = if first {
                 first = false;
                 action.to_string()
@@ -353,3 +756,37 @@ fn draw_logs<'a>() -> TuiLoggerWidget<'a> {
         )
         .style(Style::default().fg(Color::White).bg(Color::Black))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_and_cells_are_located() {
+        // 40 inputs in 2 rows and an empty row, then [●●] ██ per row
+        let column = |id, cells: usize| ColumnView {
+            id,
+            state: 0,
+            cells: (0..cells).map(|i| (100 + i, 0)).collect(),
+        };
+        let model = ModelView {
+            edge_length: 2,
+            state_names: vec![],
+            columns: vec![column(0, 2), column(1, 0), column(2, 2), column(3, 0)],
+            inputs: (0..40).map(|i| (i, 0)).collect(),
+        };
+        let area = Rect::new(10, 5, 40, 20);
+        let at = |pan, x: u16, y: u16| model_at(&model, pan, area, (area.x + 1 + x, area.y + 1 + y));
+        assert_eq!(at((0, 0), 0, 1), None);
+        assert_eq!(at((0, 0), 0, 3), Some((0, None)));
+        assert_eq!(at((0, 0), 2, 3), Some((0, Some(1))));
+        assert_eq!(at((0, 0), 4, 3), None);
+        assert_eq!(at((0, 0), 6, 3), Some((1, None)));
+        assert_eq!(at((0, 0), 1, 4), Some((2, Some(0))));
+        assert_eq!(at((5, 1), 1, 2), Some((1, None)));
+        assert_eq!(at((0, 0), 0, 5), None);
+        assert_eq!(at((u16::MAX, u16::MAX), 1, 1), None);
+        // Widest row [●●] ██, 3 input rows, 2 column rows and the legend
+        assert_eq!(model_size(&model), (32, 7));
+    }
+}
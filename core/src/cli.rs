@@ -0,0 +1,104 @@
+// Cli Module
+// Logger and argument parsing shared by the command line binaries
+
+use std::env;
+use std::fmt;
+use std::process;
+use std::str::FromStr;
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Prints the log to stdout, e.g. to be prefixed by the supervisor
+struct StdoutLogger;
+
+impl Log for StdoutLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            println!("{}", record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StdoutLogger = StdoutLogger;
+
+/// Logs info messages to stdout
+pub fn init_logger() {
+    log::set_logger(&LOGGER)
+        .map(|()| log::set_max_level(LevelFilter::Info))
+        .expect("Failed to set logger");
+}
+
+/// Prints the usage lines and exits with status 2
+pub fn usage(lines: &[&str]) -> ! {
+    for line in lines {
+        println!("{}", line);
+    }
+    process::exit(2);
+}
+
+/// Prints the message and exits with status 1
+pub fn fail(message: impl fmt::Display) -> ! {
+    println!("{}", message);
+    process::exit(1);
+}
+
+/// Command line arguments, invalid arguments print the usage
+pub struct Args {
+    args: std::vec::IntoIter<String>,
+    usage: &'static [&'static str],
+}
+
+impl Args {
+    /// Arguments of the process without the binary
+    pub fn new(usage: &'static [&'static str]) -> Self {
+        Self::from(env::args().skip(1).collect(), usage)
+    }
+
+    pub fn from(args: Vec<String>, usage: &'static [&'static str]) -> Self {
+        Self {
+            args: args.into_iter(),
+            usage,
+        }
+    }
+
+    /// Parses the next argument, e.g. the value of an option
+    pub fn value<T: FromStr>(&mut self) -> T {
+        match self.args.next().and_then(|v| v.parse().ok()) {
+            Some(v) => v,
+            None => self.usage(),
+        }
+    }
+
+    pub fn usage(&self) -> ! {
+        usage(self.usage)
+    }
+}
+
+impl Iterator for Args {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.args.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args_parse_values() {
+        let args = vec!["--rows".to_string(), "12".to_string(), "program.push".to_string()];
+        let mut args = Args::from(args, &["Usage: test [--rows N] <program.push>"]);
+        assert_eq!(args.next(), Some("--rows".to_string()));
+        assert_eq!(args.value::<usize>(), 12);
+        assert_eq!(args.value::<String>(), "program.push");
+        assert_eq!(args.next(), None);
+    }
+}
@@ -0,0 +1,80 @@
+use std::env;
+
+use phtm::cli;
+use phtm::node::channel::{self, ChannelConfig, ChannelEvent, ControlChannel};
+use phtm::node::network::{self, NodeInfo};
+use phtm::shared::msg::{MessageCommand, PAYLOAD_OFFSET};
+
+const USAGE: &[&str] = &[
+    "Usage: phtm_ctl nodes",
+    "       phtm_ctl set <node_id> <NAME> <VALUE>",
+    "       phtm_ctl replicate <node_id> [--with-state]",
+    "       phtm_ctl pause|resume|reset <node_id>",
+    "       phtm_ctl shutdown <node_id> [checkpoint]",
+];
+
+fn parse_node(arg: &str) -> String {
+    match arg.parse::<u16>() {
+        Ok(id) => channel::node_identity(id),
+        Err(_) => cli::usage(USAGE),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let (target, msg) = match (args.get(1).map(|a| a.as_str()), args.len()) {
+        (Some("nodes"), 2) => (channel::BROKER_IDENTITY.to_string(), network::request(MessageCommand::READ)),
+        (Some("set"), 5) => (parse_node(&args[2]), channel::parameter_write(&args[3], &args[4])),
+        (Some("replicate"), 3) => (parse_node(&args[2]), network::request(MessageCommand::REPLICATE)),
+        (Some("replicate"), 4) if args[3] == "--with-state" => {
+            let mut m = network::request(MessageCommand::REPLICATE);
+            m.set_payload(&mut 1u16.to_be_bytes().to_vec());
+            (parse_node(&args[2]), m)
+        }
+        (Some("pause"), 3) => (parse_node(&args[2]), network::request(MessageCommand::PAUSE)),
+        (Some("resume"), 3) => (parse_node(&args[2]), network::request(MessageCommand::RESUME)),
+        (Some("reset"), 3) => (parse_node(&args[2]), network::request(MessageCommand::RESET)),
+        (Some("shutdown"), 3) => (parse_node(&args[2]), network::request(MessageCommand::SHUTDOWN)),
+        (Some("shutdown"), 4) => {
+            let mut m = network::request(MessageCommand::SHUTDOWN);
+            m.set_payload(&mut args[3].clone().into_bytes());
+            (parse_node(&args[2]), m)
+        }
+        _ => cli::usage(USAGE),
+    };
+
+    let context = zmq::Context::new();
+    let mut control = ControlChannel::connect(&context, channel::CONTROL_ENDPOINT, None, ChannelConfig::default())
+        .expect("Failed to connect control channel");
+    let msg_cmd = msg.get_cmd();
+    let id = control.request(&target, msg).expect("Failed to send request");
+
+    loop {
+        match control.poll(100) {
+            Ok(Some(ChannelEvent::Acked(ack, reply))) if ack == id => {
+                if target == channel::BROKER_IDENTITY {
+                    for node in NodeInfo::list_from_payload(&reply.data[PAYLOAD_OFFSET..]) {
+                        println!("{}", node);
+                    }
+                } else if msg_cmd == Some(MessageCommand::REPLICATE) && reply.data.len() >= PAYLOAD_OFFSET + 2 {
+                    println!("{}: ok (replica {})", target, channel::node_identity(reply.get_prop(&PAYLOAD_OFFSET)));
+                } else {
+                    println!("{}: ok", target);
+                }
+                return;
+            }
+            Ok(Some(ChannelEvent::Nacked(nack, reason))) if nack == id => {
+                cli::fail(format!("{}: rejected ({})", target, reason));
+            }
+            Ok(_) => (),
+            Err(e) => cli::fail(format!("Control channel error: {}", e)),
+        }
+        match control.retry() {
+            Ok(failed) if failed.contains(&id) => {
+                cli::fail(format!("{}: no acknowledge received", target));
+            }
+            Ok(_) => (),
+            Err(e) => cli::fail(format!("Control channel error: {}", e)),
+        }
+    }
+}
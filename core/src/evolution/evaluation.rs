@@ -0,0 +1,415 @@
+// Evaluation Module
+// Runs a temporal memory program headless on a dataset and scores
+// its predictions. Spatial pooler programs are scored by the quality
+// metrics of their output.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::evolution::mutation::{parameters, parse_program};
+use crate::node::encoder::ScalarEncoder;
+use crate::node::execution::PushExecutor;
+use crate::node::metrics::{self, MetricsTracker, SpMetrics};
+use crate::node::random;
+use crate::node::sdr;
+
+/// Number of header lines of NuPIC style CSV files (names, types, flags)
+const CSV_HEADER_LINES: usize = 3;
+
+#[derive(Clone, Debug)]
+pub struct EvaluationConfig {
+    /// Number of rows of the dataset that are fed to the program
+    pub rows: usize,
+    /// Rows at the start that are not scored
+    pub warmup: usize,
+    /// Interpreter steps to build the memory graph
+    pub setup_steps: usize,
+    /// Maximum interpreter steps per input, an input that does not
+    /// reach the end of the input cycle fails the evaluation
+    pub steps_per_input: usize,
+    /// Fraction of active bits of the encoded input
+    pub sparsity: f32,
+    /// Seed of the random instructions
+    pub seed: u64,
+}
+
+impl Default for EvaluationConfig {
+    fn default() -> Self {
+        Self {
+            rows: 200,
+            warmup: 20,
+            setup_steps: 1_000_000,
+            steps_per_input: 5_000,
+            sparsity: 0.125,
+            seed: 42,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SpEvaluationConfig {
+    /// Interpreter steps to build the pooler graph
+    pub setup_steps: usize,
+    /// Maximum interpreter steps per input
+    pub steps_per_input: usize,
+    /// Fraction of active input bits moved for the noisy inputs
+    pub noise: f32,
+    /// Seed of the random instructions and the input noise
+    pub seed: u64,
+}
+
+impl Default for SpEvaluationConfig {
+    fn default() -> Self {
+        Self {
+            setup_steps: 1_000_000,
+            steps_per_input: 100_000,
+            noise: 0.1,
+            seed: 42,
+        }
+    }
+}
+
+/// Result of an evaluation. Lower scores are better.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fitness {
+    /// Mean anomaly score of a temporal memory, i.e. the fraction of
+    /// active columns that were not predicted, or quality loss of a
+    /// spatial pooler
+    Score(f32),
+    /// Program did not run up to the end of the dataset
+    Invalid(String),
+}
+
+impl Fitness {
+    /// Score used for ranking, invalid programs rank last
+    pub fn score(&self) -> f32 {
+        match self {
+            Fitness::Score(s) => *s,
+            Fitness::Invalid(_) => f32::INFINITY,
+        }
+    }
+}
+
+impl fmt::Display for Fitness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fitness::Score(s) => write!(f, "{:.4}", s),
+            Fitness::Invalid(reason) => write!(f, "invalid ({})", reason),
+        }
+    }
+}
+
+/// Measurements of a complete evaluation run
+#[derive(Clone, Debug, PartialEq)]
+pub struct Measurement {
+    /// Mean fraction of active columns that were not predicted
+    pub anomaly: f32,
+    /// Mean fraction of columns that were predicted
+    pub predicted: f32,
+    pub duration: Duration,
+}
+
+/// Scalar time series
+#[derive(Clone, Debug)]
+pub struct Dataset {
+    pub values: Vec<f32>,
+}
+
+impl Dataset {
+    /// Reads the second column of a CSV file with NuPIC headers, e.g.
+    /// data/rec-center-hourly.csv (timestamp, consumption)
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.as_ref().display(), e))?;
+        Dataset::parse(&content)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut values = vec![];
+        for (i, line) in content.lines().enumerate().skip(CSV_HEADER_LINES) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let value = line
+                .split(',')
+                .nth(1)
+                .and_then(|v| v.trim().parse::<f32>().ok())
+                .ok_or_else(|| format!("Invalid value in line {}: {}", i + 1, line))?;
+            values.push(value);
+        }
+        if values.is_empty() {
+            return Err("Dataset is empty".to_string());
+        }
+        Ok(Self { values })
+    }
+}
+
+fn int_binding(executor: &PushExecutor, name: &str) -> Result<i32, String> {
+    sdr::int_binding(&executor.push_state, name).ok_or_else(|| format!("Missing integer {}", name))
+}
+
+fn int_vector_binding(executor: &PushExecutor, name: &str) -> Result<Vec<i32>, String> {
+    sdr::int_vector_binding(&executor.push_state, name)
+        .map(|v| v.to_vec())
+        .ok_or_else(|| format!("Missing integer vector {}", name))
+}
+
+/// Runs the program until the next instruction is the marker. Returns
+/// false if the marker was not reached within the step limit.
+fn run_until(executor: &mut PushExecutor, marker: &str, max_steps: usize) -> bool {
+    for _ in 0..max_steps {
+        if executor.step() {
+            return false;
+        }
+        if let Some(next) = executor.push_state.exec_stack.get(0) {
+            if next.to_string() == marker {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Flags the columns that contain at least one predictive cell. Cells
+/// are the origins of the incoming edges of a column node.
+fn predicted_columns(executor: &PushExecutor, column_ids: &[i32], state_predictive: i32) -> Vec<bool> {
+    let graph = match executor.push_state.graph_stack.get(0) {
+        Some(graph) => graph,
+        None => return vec![false; column_ids.len()],
+    };
+    column_ids
+        .iter()
+        .map(|id| match graph.edges.get(&(*id as usize)) {
+            Some(edges) => edges
+                .iter()
+                .any(|e| graph.get_state(&e.get_origin_id()) == Some(state_predictive)),
+            None => false,
+        })
+        .collect()
+}
+
+/// Spatial pooler programs define the size of their input, temporal
+/// memory programs take the columns of a pooler
+pub fn is_spatial_pooler(code: &str) -> bool {
+    parameters(&parse_program(code)).iter().any(|p| p.name == "NUM_INPUTS")
+}
+
+/// Feeds the encoded dataset to the program. Temporal memory programs
+/// score the mean anomaly score after the warmup, spatial pooler
+/// programs the quality loss of their output.
+pub fn evaluate(code: &str, dataset: &Dataset, config: &EvaluationConfig) -> Fitness {
+    let score = if is_spatial_pooler(code) {
+        score_spatial_pooler(code, dataset, config)
+    } else {
+        measure(code, dataset, config).map(|measurement| measurement.anomaly)
+    };
+    match score {
+        Ok(score) => Fitness::Score(score),
+        Err(reason) => Fitness::Invalid(reason),
+    }
+}
+
+/// Runs the program on the dataset and measures its predictions
+pub fn measure(code: &str, dataset: &Dataset, config: &EvaluationConfig) -> Result<Measurement, String> {
+    let start = Instant::now();
+    let mut executor = PushExecutor::new();
+    executor.initialize();
+    random::seed_instructions(&mut executor.instruction_set, config.seed);
+    executor.load(code.to_string());
+    if !run_until(&mut executor, sdr::TM_GRAPH_CREATED, config.setup_steps) {
+        return Err(format!("{} not reached", sdr::TM_GRAPH_CREATED));
+    }
+    let num_columns = int_binding(&executor, "NUM_COLUMNS")?;
+    let column_ids = int_vector_binding(&executor, "COLUMN_IDS")?;
+    let state_predictive = int_binding(&executor, "STATE_CELL_PREDICTIVE")?;
+    if num_columns <= 0 || column_ids.len() != num_columns as usize {
+        return Err(format!("{} column ids for {} columns", column_ids.len(), num_columns));
+    }
+    let size = num_columns as usize;
+    let active = ((size as f32 * config.sparsity).round() as usize).max(1);
+    let rows = &dataset.values[..config.rows.min(dataset.values.len())];
+    let encoder = ScalarEncoder::for_values(rows, size, active)?;
+
+    let mut anomaly_sum = 0.0;
+    let mut predicted_sum = 0.0;
+    let mut scored = 0;
+    for (t, value) in rows.iter().enumerate() {
+        let input = encoder.encode(*value);
+        if t >= config.warmup {
+            let predicted = predicted_columns(&executor, &column_ids, state_predictive);
+            let hits = input.iter().zip(predicted.iter()).filter(|(i, p)| **i && **p).count();
+            anomaly_sum += 1.0 - hits as f32 / active as f32;
+            predicted_sum += predicted.iter().filter(|p| **p).count() as f32 / size as f32;
+            scored += 1;
+        }
+        executor.inject(input);
+        if !run_until(&mut executor, sdr::TM_CYCLE_END, config.steps_per_input) {
+            return Err(format!("{} not reached for row {}", sdr::TM_CYCLE_END, t));
+        }
+    }
+    if scored == 0 {
+        return Err("No rows after warmup".to_string());
+    }
+    Ok(Measurement {
+        anomaly: anomaly_sum / scored as f32,
+        predicted: predicted_sum / scored as f32,
+        duration: start.elapsed(),
+    })
+}
+
+/// Feeds the encoded dataset to the spatial pooler program and returns
+/// the mean of the missing entropy, the missing stability and the
+/// noise degradation
+pub fn score_spatial_pooler(code: &str, dataset: &Dataset, config: &EvaluationConfig) -> Result<f32, String> {
+    let metrics = measure_spatial_pooler_dataset(code, dataset, config)?;
+    let score = (2.0 - metrics.entropy - metrics.stability + metrics.noise_degradation) / 3.0;
+    if score.is_nan() {
+        return Err("Spatial pooler metrics not measured".to_string());
+    }
+    Ok(score)
+}
+
+/// Feeds the encoded dataset to the spatial pooler program and returns
+/// its quality metrics
+pub fn measure_spatial_pooler_dataset(code: &str, dataset: &Dataset, config: &EvaluationConfig) -> Result<SpMetrics, String> {
+    let mut sp_config = SpEvaluationConfig {
+        setup_steps: config.setup_steps,
+        seed: config.seed,
+        ..SpEvaluationConfig::default()
+    };
+    let mut executor = spatial_pooler(code, &sp_config)?;
+    let size = int_binding(&executor, "NUM_INPUTS")?.max(0) as usize;
+    let active = ((size as f32 * config.sparsity).round() as usize).max(1);
+    // The noisy versions move at least one bit
+    sp_config.noise = sp_config.noise.max(1.0 / active as f32);
+    let rows = &dataset.values[..config.rows.min(dataset.values.len())];
+    let encoder = ScalarEncoder::for_values(rows, size, active)?;
+    let inputs: Vec<Vec<bool>> = rows.iter().map(|value| encoder.encode(*value)).collect();
+    feed_spatial_pooler(&mut executor, &inputs, &sp_config)
+}
+
+/// Feeds each input to the spatial pooler program followed by a
+/// repetition and a noisy version and returns the quality metrics.
+pub fn measure_spatial_pooler(code: &str, inputs: &[Vec<bool>], config: &SpEvaluationConfig) -> Result<SpMetrics, String> {
+    let mut executor = spatial_pooler(code, config)?;
+    feed_spatial_pooler(&mut executor, inputs, config)
+}
+
+/// Runs the spatial pooler program until its graph is created
+fn spatial_pooler(code: &str, config: &SpEvaluationConfig) -> Result<PushExecutor, String> {
+    let mut executor = PushExecutor::new();
+    executor.initialize();
+    random::seed_instructions(&mut executor.instruction_set, config.seed);
+    executor.load(code.to_string());
+    if !run_until(&mut executor, sdr::SP_GRAPH_CREATED, config.setup_steps) {
+        return Err(format!("{} not reached", sdr::SP_GRAPH_CREATED));
+    }
+    Ok(executor)
+}
+
+fn feed_spatial_pooler(executor: &mut PushExecutor, inputs: &[Vec<bool>], config: &SpEvaluationConfig) -> Result<SpMetrics, String> {
+    let num_inputs = int_binding(executor, "NUM_INPUTS")? as usize;
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut tracker = MetricsTracker::new(metrics::active_columns(&executor.push_state)?.len());
+    for (t, input) in inputs.iter().enumerate() {
+        if input.len() != num_inputs {
+            return Err(format!("Input {} has {} bits, expected {}", t, input.len(), num_inputs));
+        }
+        let noisy = metrics::add_noise(input, config.noise, &mut rng);
+        for sdr in [input, input, &noisy].iter() {
+            executor.inject(sdr.to_vec());
+            if !run_until(executor, sdr::SP_CYCLE_END, config.steps_per_input) {
+                return Err(format!("{} not reached for input {}", sdr::SP_CYCLE_END, t));
+            }
+            let output = metrics::active_columns(&executor.push_state)?;
+            tracker.update(sdr, &output);
+        }
+    }
+    Ok(tracker.metrics())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::source::Source;
+
+    #[test]
+    fn dataset_skips_header() {
+        let dataset = Dataset::parse("timestamp,consumption\ndatetime,float\nT,\n7/2/10 0:00,21.2\n7/2/10 1:00,16.4\n").unwrap();
+        assert_eq!(dataset.values, vec![21.2, 16.4]);
+        assert!(Dataset::parse("a,b\nc,d\ne,\n7/2/10 0:00,x\n").is_err());
+    }
+
+    #[test]
+    fn broken_program_is_invalid() {
+        let fitness = evaluate("( 1 2 INTEGER.+ )", &Dataset { values: vec![1.0, 2.0] }, &EvaluationConfig::default());
+        assert_eq!(fitness, Fitness::Invalid("BP1 not reached".to_string()));
+        assert_eq!(fitness.score(), f32::INFINITY);
+    }
+
+    #[test]
+    fn spatial_pooler_metrics_are_measured() {
+        let code = Source::read_debug_code(include_str!("../core/spatial_pooler.push").to_string());
+        let inputs: Vec<Vec<bool>> = (0..2).map(|t| (0..32).map(|i| (i + 7 * t) % 5 == 0).collect()).collect();
+        let metrics = measure_spatial_pooler(&code, &inputs, &SpEvaluationConfig::default()).unwrap();
+        assert!((0.0..=1.0).contains(&metrics.sparsity));
+        assert!((0.0..=1.0).contains(&metrics.entropy));
+        assert!((0.0..=1.0).contains(&metrics.stability));
+        assert!(measure_spatial_pooler(&code, &[vec![true; 3]], &SpEvaluationConfig::default()).is_err());
+    }
+
+    #[test]
+    fn spatial_pooler_is_scored() {
+        let code = Source::read_debug_code(include_str!("../core/spatial_pooler.push").to_string());
+        assert!(is_spatial_pooler(&code));
+        let dataset = Dataset {
+            values: (0..4).map(|i| (i % 2) as f32).collect(),
+        };
+        let config = EvaluationConfig {
+            rows: 4,
+            ..EvaluationConfig::default()
+        };
+        match evaluate(&code, &dataset, &config) {
+            Fitness::Score(score) => assert!(score.is_finite()),
+            f => panic!("Unexpected fitness {}", f),
+        }
+    }
+
+    #[test]
+    fn temporal_memory_is_scored() {
+        let code = Source::read_debug_code(include_str!("../core/temporal_memory.push").to_string());
+        let dataset = Dataset {
+            values: (0..12).map(|i| (i % 4) as f32).collect(),
+        };
+        let config = EvaluationConfig {
+            rows: 12,
+            warmup: 4,
+            ..EvaluationConfig::default()
+        };
+        match evaluate(&code, &dataset, &config) {
+            Fitness::Score(score) => assert!((0.0..=1.0).contains(&score)),
+            f => panic!("Unexpected fitness {}", f),
+        }
+    }
+
+    #[test]
+    fn measurement_is_reproducible() {
+        let code = Source::read_debug_code(include_str!("../core/temporal_memory.push").to_string());
+        let dataset = Dataset {
+            values: (0..8).map(|i| (i % 4) as f32).collect(),
+        };
+        let config = EvaluationConfig {
+            rows: 8,
+            warmup: 2,
+            seed: 7,
+            ..EvaluationConfig::default()
+        };
+        let (a, b) = (measure(&code, &dataset, &config).unwrap(), measure(&code, &dataset, &config).unwrap());
+        assert_eq!((a.anomaly, a.predicted), (b.anomaly, b.predicted));
+    }
+}
@@ -0,0 +1,262 @@
+// Evolution Module
+// Evolves memory programs: seeds are mutated, evaluated headless on a
+// dataset and selected by their anomaly score. Each individual is
+// written to a lineage log. Spatial pooler and temporal memory scores
+// are not comparable, a population holds one kind of program.
+
+pub mod evaluation;
+pub mod mutation;
+pub mod sweep;
+
+use std::io::Write;
+
+use log::info;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::evolution::evaluation::{evaluate, is_spatial_pooler, Dataset, EvaluationConfig, Fitness};
+use crate::evolution::mutation::{MutationConfig, Mutator};
+
+/// Header of the tab separated lineage log
+pub const LINEAGE_HEADER: &str = "generation\tid\tparent\tfitness\tmutation";
+
+#[derive(Clone, Debug)]
+pub struct EvolutionConfig {
+    /// Number of individuals that survive each generation
+    pub population_size: usize,
+    /// Number of mutated children per generation
+    pub offspring: usize,
+    pub generations: usize,
+    /// Number of individuals competing for parenthood
+    pub tournament_size: usize,
+    /// Seed of the random generator for selection and mutation
+    pub seed: u64,
+    /// Number of parallel evaluations
+    pub threads: usize,
+    pub mutation: MutationConfig,
+    pub evaluation: EvaluationConfig,
+}
+
+impl Default for EvolutionConfig {
+    fn default() -> Self {
+        Self {
+            population_size: 8,
+            offspring: 8,
+            generations: 10,
+            tournament_size: 3,
+            seed: 42,
+            threads: 4,
+            mutation: MutationConfig::default(),
+            evaluation: EvaluationConfig::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Individual {
+    pub id: usize,
+    pub parent: Option<usize>,
+    pub generation: usize,
+    pub code: String,
+    /// Mutation that created the individual from its parent
+    pub mutation: String,
+    pub fitness: Fitness,
+}
+
+impl Individual {
+    /// Line of the lineage log
+    pub fn lineage(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}",
+            self.generation,
+            self.id,
+            self.parent.map_or("-".to_string(), |p| p.to_string()),
+            self.fitness,
+            self.mutation
+        )
+    }
+}
+
+pub struct Evolution {
+    config: EvolutionConfig,
+    dataset: Dataset,
+    mutator: Mutator,
+    /// Programs of generation 0
+    seeds: Vec<String>,
+    /// Kind of the programs of the population
+    spatial_pooler: bool,
+    rng: StdRng,
+    next_id: usize,
+    pub generation: usize,
+    /// Population sorted by fitness, best first
+    pub population: Vec<Individual>,
+}
+
+impl Evolution {
+    pub fn new(config: EvolutionConfig, dataset: Dataset, seeds: &[String]) -> Result<Self, String> {
+        if seeds.is_empty() {
+            return Err("No seed programs".to_string());
+        }
+        if config.population_size == 0 || config.tournament_size == 0 {
+            return Err("Population and tournament size must be positive".to_string());
+        }
+        let spatial_pooler = is_spatial_pooler(&seeds[0]);
+        if seeds.iter().any(|code| is_spatial_pooler(code) != spatial_pooler) {
+            return Err("Seeds mix spatial pooler and temporal memory programs".to_string());
+        }
+        Ok(Self {
+            mutator: Mutator::new(config.mutation.clone(), seeds),
+            seeds: seeds.to_vec(),
+            spatial_pooler,
+            rng: StdRng::seed_from_u64(config.seed),
+            config,
+            dataset,
+            next_id: 0,
+            generation: 0,
+            population: vec![],
+        })
+    }
+
+    /// Evaluates the seed programs as generation 0
+    pub fn initialize(&mut self, log: &mut dyn Write) -> Result<(), String> {
+        let seeds = self.seeds.clone();
+        let children: Vec<Individual> = seeds
+            .into_iter()
+            .map(|code| self.individual(None, code, "seed".to_string()))
+            .collect();
+        self.add(children, log)
+    }
+
+    /// Breeds, evaluates and selects one generation
+    pub fn step(&mut self, log: &mut dyn Write) -> Result<(), String> {
+        self.generation += 1;
+        let mut children = vec![];
+        for _ in 0..self.config.offspring {
+            let parent = self.select().clone();
+            let (code, mutation) = self.mutator.mutate(&parent.code, &mut self.rng);
+            children.push(self.individual(Some(parent.id), code, mutation));
+        }
+        self.add(children, log)
+    }
+
+    /// Runs all generations and returns the best individual
+    pub fn run(&mut self, log: &mut dyn Write) -> Result<Individual, String> {
+        self.initialize(log)?;
+        for _ in 0..self.config.generations {
+            self.step(log)?;
+            info!("Generation {}: best {}", self.generation, self.best().fitness);
+        }
+        Ok(self.best().clone())
+    }
+
+    pub fn best(&self) -> &Individual {
+        &self.population[0]
+    }
+
+    fn individual(&mut self, parent: Option<usize>, code: String, mutation: String) -> Individual {
+        self.next_id += 1;
+        Individual {
+            id: self.next_id,
+            parent,
+            generation: self.generation,
+            code,
+            mutation,
+            fitness: Fitness::Invalid("Not evaluated".to_string()),
+        }
+    }
+
+    /// Tournament selection
+    fn select(&mut self) -> &Individual {
+        let n = self.population.len();
+        let best = (0..self.config.tournament_size)
+            .map(|_| self.rng.gen_range(0..n))
+            .min()
+            .unwrap_or(0);
+        &self.population[best]
+    }
+
+    /// Evaluates the children, logs them and keeps the best of parents
+    /// and children. Children of another program kind are invalid.
+    fn add(&mut self, mut children: Vec<Individual>, log: &mut dyn Write) -> Result<(), String> {
+        evaluate_all(&mut children, &self.dataset, &self.config.evaluation, self.config.threads);
+        for child in children.iter_mut() {
+            if is_spatial_pooler(&child.code) != self.spatial_pooler {
+                child.fitness = Fitness::Invalid("Program kind changed".to_string());
+            }
+        }
+        for child in children.iter() {
+            writeln!(log, "{}", child.lineage()).map_err(|e| e.to_string())?;
+        }
+        log.flush().map_err(|e| e.to_string())?;
+        self.population.extend(children);
+        // Stable sort keeps older individuals first on equal fitness
+        self.population
+            .sort_by(|a, b| a.fitness.score().total_cmp(&b.fitness.score()));
+        self.population.truncate(self.config.population_size);
+        Ok(())
+    }
+}
+
+/// Evaluates the individuals on up to the given number of threads
+pub fn evaluate_all(individuals: &mut [Individual], dataset: &Dataset, config: &EvaluationConfig, threads: usize) {
+    for_each_parallel(individuals, threads, |individual| {
+        individual.fitness = evaluate(&individual.code, dataset, config);
+    });
+}
+
+/// Calls f for each item, the items are split evenly between the threads
+pub(crate) fn for_each_parallel<T, F>(items: &mut [T], threads: usize, f: F)
+where
+    T: Send,
+    F: Fn(&mut T) + Sync,
+{
+    let chunk = items.len().div_ceil(threads.max(1)).max(1);
+    let f = &f;
+    std::thread::scope(|scope| {
+        for chunk in items.chunks_mut(chunk) {
+            scope.spawn(move || chunk.iter_mut().for_each(f));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evolution_logs_lineage_and_keeps_best() {
+        let config = EvolutionConfig {
+            population_size: 2,
+            offspring: 3,
+            generations: 2,
+            ..EvolutionConfig::default()
+        };
+        let dataset = Dataset { values: vec![1.0, 2.0] };
+        // Programs without memory graph are invalid but still evolve
+        let seeds = vec!["( 1 A INTEGER.DEFINE 0.5 B FLOAT.DEFINE )".to_string()];
+        let mut evolution = Evolution::new(config, dataset, &seeds).unwrap();
+        let mut log = vec![];
+        let best = evolution.run(&mut log).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 1 + 2 * 3);
+        assert!(lines[0].starts_with("0\t1\t-\tinvalid"));
+        assert!(lines[1].starts_with("1\t2\t1\t"));
+        assert_eq!(evolution.population.len(), 2);
+        assert_eq!(best.id, 1, "Seed stays best on equal fitness");
+    }
+
+    #[test]
+    fn evolution_rejects_mixed_seeds() {
+        let dataset = Dataset { values: vec![1.0, 2.0] };
+        let seeds = vec![
+            "( 1 NUM_INPUTS INTEGER.DEFINE )".to_string(),
+            "( 1 NUM_COLUMNS INTEGER.DEFINE )".to_string(),
+        ];
+        let result = Evolution::new(EvolutionConfig::default(), dataset, &seeds);
+        assert_eq!(
+            result.err(),
+            Some("Seeds mix spatial pooler and temporal memory programs".to_string())
+        );
+    }
+}
@@ -0,0 +1,313 @@
+// Mutation Module
+// Varies the parameters and the code of a program. Random code is
+// generated by the CodeGenerator of pushr.
+
+use rand::Rng;
+
+use crate::node::execution::PushExecutor;
+use crate::node::source::Source;
+use crate::pushr::push::item::{Item, PushType};
+use crate::pushr::push::random::CodeGenerator;
+use crate::pushr::push::state::PushState;
+
+/// Prefix of constants that encode node states and must not be varied
+const STATE_PREFIX: &str = "STATE_";
+
+#[derive(Clone, Debug)]
+pub struct MutationConfig {
+    /// Probability that a mutation changes code instead of a parameter
+    pub code_mutation_rate: f32,
+    /// Relative change of parameter values
+    pub strength: f32,
+    /// Maximum number of points of generated code (at least 2)
+    pub max_points: usize,
+}
+
+impl Default for MutationConfig {
+    fn default() -> Self {
+        Self {
+            code_mutation_rate: 0.1,
+            strength: 0.2,
+            max_points: 5,
+        }
+    }
+}
+
+/// Constant defined as `<literal> <NAME> INTEGER.DEFINE|FLOAT.DEFINE`
+/// in the top level list of the program
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub value: PushType,
+}
+
+/// Parses the program and returns the items of the code stack
+pub fn parse_program(code: &str) -> Vec<Item> {
+    let mut executor = PushExecutor::new();
+    executor.initialize();
+    executor.load(code.to_string());
+    (0..executor.push_state.code_stack.size())
+        .filter_map(|i| executor.push_state.code_stack.copy(i))
+        .collect()
+}
+
+/// Calls f with the list of items at the top level and with the
+/// contents of each top level list
+fn for_each_top_level(items: &mut [Item], f: &mut dyn FnMut(&mut [Item])) {
+    f(items);
+    for item in items.iter_mut() {
+        if let Item::List { items: list } = item {
+            let mut contents: Vec<Item> = (0..list.size()).filter_map(|i| list.copy(i)).collect();
+            f(&mut contents);
+            // Lists store their first item on top
+            contents.reverse();
+            *item = Item::list(contents);
+        }
+    }
+}
+
+fn define_index(items: &[Item]) -> Vec<(usize, Parameter)> {
+    let mut found = vec![];
+    for i in 2..items.len() {
+        let is_define = matches!(&items[i], Item::InstructionMeta { name } if name == "INTEGER.DEFINE" || name == "FLOAT.DEFINE");
+        if !is_define {
+            continue;
+        }
+        if let (Item::Literal { push_type }, Item::Identifier { name }) = (&items[i - 2], &items[i - 1]) {
+            let matching_type = match push_type {
+                PushType::Int { .. } => matches!(&items[i], Item::InstructionMeta { name } if name == "INTEGER.DEFINE"),
+                PushType::Float { .. } => matches!(&items[i], Item::InstructionMeta { name } if name == "FLOAT.DEFINE"),
+                _ => false,
+            };
+            if matching_type && !name.starts_with(STATE_PREFIX) {
+                found.push((
+                    i - 2,
+                    Parameter {
+                        name: name.clone(),
+                        value: push_type.clone(),
+                    },
+                ));
+            }
+        }
+    }
+    found
+}
+
+/// Returns the parameters of the program in order of definition
+pub fn parameters(items: &[Item]) -> Vec<Parameter> {
+    let mut items = items.to_vec();
+    let mut found = vec![];
+    for_each_top_level(&mut items, &mut |list| {
+        found.extend(define_index(list).into_iter().map(|(_, p)| p));
+    });
+    found
+}
+
+/// Overwrites the value of a parameter. Returns false if the
+/// program does not define the parameter.
+pub fn set_parameter(items: &mut [Item], parameter: &Parameter) -> bool {
+    let mut replaced = false;
+    for_each_top_level(items, &mut |list| {
+        for (i, p) in define_index(list) {
+            if p.name == parameter.name {
+                list[i] = Item::Literal {
+                    push_type: parameter.value.clone(),
+                };
+                replaced = true;
+            }
+        }
+    });
+    replaced
+}
+
+/// Returns the point with the given index in depth first order,
+/// index 0 is the item itself
+fn point_mut(item: &mut Item, mut index: usize) -> Option<&mut Item> {
+    if index == 0 {
+        return Some(item);
+    }
+    index -= 1;
+    if let Item::List { items } = item {
+        for i in 0..items.size() {
+            let size = Item::size(items.get(i)?);
+            if index < size {
+                return point_mut(items.get_mut(i)?, index);
+            }
+            index -= size;
+        }
+    }
+    None
+}
+
+/// Creates program variants
+pub struct Mutator {
+    config: MutationConfig,
+    executor: PushExecutor,
+}
+
+impl Mutator {
+    /// Names of the seed programs are used by generated code
+    pub fn new(config: MutationConfig, seeds: &[String]) -> Self {
+        let mut executor = PushExecutor::new();
+        executor.initialize();
+        let mut push_state = PushState::new();
+        for seed in seeds.iter() {
+            for name in Source::read_debug_code(seed.to_string()).split_whitespace() {
+                let is_name = name.chars().next().is_some_and(|c| c.is_ascii_uppercase())
+                    && !executor.instruction_set.is_instruction(name)
+                    && !name.contains('[')
+                    && name != "TRUE"
+                    && name != "FALSE";
+                if is_name {
+                    push_state.name_bindings.insert(name.to_string(), Item::noop());
+                }
+            }
+        }
+        executor.push_state = push_state;
+        Self { config, executor }
+    }
+
+    /// Applies one random mutation. Returns the new program code and a
+    /// description of the mutation.
+    pub fn mutate<R: Rng>(&self, code: &str, rng: &mut R) -> (String, String) {
+        let mut items = parse_program(code);
+        let description = if rng.gen::<f32>() < self.config.code_mutation_rate {
+            self.mutate_code(&mut items, rng)
+        } else {
+            self.mutate_parameter(&mut items, rng)
+        };
+        match description {
+            Some(description) => (Source::from_items(&items), description),
+            None => (code.to_string(), "none".to_string()),
+        }
+    }
+
+    fn mutate_parameter<R: Rng>(&self, items: &mut [Item], rng: &mut R) -> Option<String> {
+        let params = parameters(items);
+        if params.is_empty() {
+            return None;
+        }
+        let mut param = params[rng.gen_range(0..params.len())].clone();
+        let strength = self.config.strength;
+        let old = param.value.clone();
+        param.value = match old {
+            PushType::Float { val } => PushType::Float {
+                val: val * (1.0 + rng.gen_range(-strength..=strength)),
+            },
+            PushType::Int { val } => {
+                let delta = ((val.abs() as f32 * strength).round() as i32).max(1);
+                let new = if rng.gen::<bool>() { val + delta } else { val - delta };
+                PushType::Int {
+                    val: if new < 1 { val + delta } else { new },
+                }
+            }
+            _ => return None,
+        };
+        set_parameter(items, &param);
+        Some(format!(
+            "{} {} -> {}",
+            param.name,
+            Source::from_items(&[Item::Literal { push_type: old }]),
+            Source::from_items(&[Item::Literal {
+                push_type: param.value.clone()
+            }])
+        ))
+    }
+
+    /// Replaces, wraps or deletes a random point of the program. The
+    /// top level items are never replaced.
+    fn mutate_code<R: Rng>(&self, items: &mut [Item], rng: &mut R) -> Option<String> {
+        let sizes: Vec<usize> = items.iter().map(Item::size).collect();
+        let total: usize = sizes.iter().sum();
+        if total <= items.len() {
+            return None;
+        }
+        // Pick a point below one of the top level items
+        let mut index = rng.gen_range(0..total - items.len());
+        let mut target = None;
+        for (i, size) in sizes.iter().enumerate() {
+            if index < size - 1 {
+                target = Some((i, index + 1));
+                break;
+            }
+            index -= size - 1;
+        }
+        let (root, index) = target?;
+        let point = point_mut(&mut items[root], index)?;
+        let code = CodeGenerator::random_code(
+            &self.executor.push_state,
+            &self.executor.instruction_cache,
+            self.config.max_points.max(2),
+        )?;
+        let old = Source::from_items(std::slice::from_ref(point));
+        let new = Source::from_items(std::slice::from_ref(&code));
+        Some(match rng.gen_range(0..3) {
+            0 => {
+                *point = code;
+                format!("replace {} with {}", old, new)
+            }
+            1 => {
+                *point = Item::list(vec![point.clone(), code]);
+                format!("insert {} before {}", new, old)
+            }
+            _ => {
+                *point = Item::noop();
+                format!("delete {}", old)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const PROGRAM: &str = "( 1 STATE_ON INTEGER.DEFINE 16 NUM_COLUMNS INTEGER.DEFINE 0.5 PERM FLOAT.DEFINE ( NUM_COLUMNS INTEGER.DUP ) )";
+
+    #[test]
+    fn parameters_skip_state_constants() {
+        let params = parameters(&parse_program(PROGRAM));
+        let names: Vec<&str> = params.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["NUM_COLUMNS", "PERM"]);
+    }
+
+    #[test]
+    fn set_parameter_replaces_literal() {
+        let mut items = parse_program(PROGRAM);
+        let param = Parameter {
+            name: "PERM".to_string(),
+            value: PushType::Float { val: 0.25 },
+        };
+        assert!(set_parameter(&mut items, &param));
+        assert!(Source::from_items(&items).contains("0.25 PERM FLOAT.DEFINE"));
+    }
+
+    #[test]
+    fn mutations_keep_program_parseable() {
+        let mutator = Mutator::new(
+            MutationConfig {
+                code_mutation_rate: 0.5,
+                ..MutationConfig::default()
+            },
+            &[PROGRAM.to_string()],
+        );
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut code = PROGRAM.to_string();
+        for _ in 0..20 {
+            let (mutated, description) = mutator.mutate(&code, &mut rng);
+            assert!(!description.is_empty());
+            assert_eq!(Source::from_items(&parse_program(&mutated)), mutated);
+            code = mutated;
+        }
+    }
+
+    #[test]
+    fn temporal_memory_parameters_are_found() {
+        let tm = Source::read_debug_code(include_str!("../core/temporal_memory.push").to_string());
+        let params = parameters(&parse_program(&tm));
+        assert!(params.iter().any(|p| p.name == "PERM_INCREMENT"));
+        assert!(params.iter().all(|p| !p.name.starts_with(STATE_PREFIX)));
+    }
+}
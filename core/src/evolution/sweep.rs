@@ -0,0 +1,403 @@
+// Sweep Module
+// Grid and random search over the DEFINE constants of a program. Each
+// configuration is evaluated headless and the runs are ranked by a
+// metric.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::evolution::evaluation::{is_spatial_pooler, measure, measure_spatial_pooler_dataset, Dataset, EvaluationConfig, Measurement};
+use crate::evolution::for_each_parallel;
+use crate::evolution::mutation::{parameters, parse_program, set_parameter, Parameter};
+use crate::node::metrics::SpMetrics;
+use crate::node::sdr::typed_value;
+use crate::node::source::Source;
+use crate::pushr::push::item::PushType;
+
+/// Values of a swept parameter
+#[derive(Clone, Debug, PartialEq)]
+pub enum Values {
+    /// Explicit values, e.g. `NUM_CELLS=1,2,4`
+    List(Vec<f32>),
+    /// Uniform interval for random search, e.g. `PERM_INCREMENT=0.01:0.1`
+    Interval(f32, f32),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParameterRange {
+    pub name: String,
+    pub values: Values,
+}
+
+impl FromStr for ParameterRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("Invalid parameter range {}, expected NAME=v1,v2,.. or NAME=min:max", s);
+        let (name, values) = s.split_once('=').ok_or_else(err)?;
+        let number = |v: &str| v.trim().parse::<f32>().map_err(|_| err());
+        let values = match values.split_once(':') {
+            Some((min, max)) => {
+                let (min, max) = (number(min)?, number(max)?);
+                if min > max {
+                    return Err(err());
+                }
+                Values::Interval(min, max)
+            }
+            None => Values::List(values.split(',').map(number).collect::<Result<_, _>>()?),
+        };
+        Ok(Self {
+            name: name.trim().to_string(),
+            values,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// All combinations of the listed values
+    Grid,
+    /// Given number of configurations sampled from lists and intervals
+    Random(usize),
+}
+
+/// Ranking metric of the results table
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metric {
+    /// Mean anomaly score, lower is better
+    Anomaly,
+    /// Mean fraction of predicted columns, higher is better
+    Predicted,
+    /// Run time, lower is better
+    Duration,
+    /// Normalized duty cycle entropy of a spatial pooler, higher is better
+    Entropy,
+    /// Output overlap of repeated inputs, higher is better
+    Stability,
+    /// Output overlap loss of noisy inputs, lower is better
+    NoiseRobustness,
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "anomaly" => Ok(Metric::Anomaly),
+            "predicted" => Ok(Metric::Predicted),
+            "duration" => Ok(Metric::Duration),
+            "entropy" => Ok(Metric::Entropy),
+            "stability" => Ok(Metric::Stability),
+            "noise" => Ok(Metric::NoiseRobustness),
+            _ => Err(format!(
+                "Unknown metric {}, expected anomaly, predicted, entropy, stability, noise or duration",
+                s
+            )),
+        }
+    }
+}
+
+impl Metric {
+    /// Value used for ranking, lower is better and failed runs rank last
+    fn rank_value(&self, result: &Result<Outcome, String>) -> f32 {
+        match (self, result) {
+            (Metric::Anomaly, Ok(Outcome::TemporalMemory(m))) => m.anomaly,
+            (Metric::Predicted, Ok(Outcome::TemporalMemory(m))) => -m.predicted,
+            (Metric::Entropy, Ok(Outcome::SpatialPooler(m, _))) => -m.entropy,
+            (Metric::Stability, Ok(Outcome::SpatialPooler(m, _))) => -m.stability,
+            (Metric::NoiseRobustness, Ok(Outcome::SpatialPooler(m, _))) => m.noise_degradation,
+            (Metric::Duration, Ok(outcome)) => outcome.duration().as_secs_f32(),
+            _ => f32::INFINITY,
+        }
+    }
+
+    /// Temporal memory and spatial pooler programs are ranked by
+    /// different metrics
+    fn applies_to(&self, spatial_pooler: bool) -> bool {
+        match self {
+            Metric::Anomaly | Metric::Predicted => !spatial_pooler,
+            Metric::Entropy | Metric::Stability | Metric::NoiseRobustness => spatial_pooler,
+            Metric::Duration => true,
+        }
+    }
+}
+
+/// Measurements of a run, depending on the kind of program
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    TemporalMemory(Measurement),
+    SpatialPooler(SpMetrics, Duration),
+}
+
+impl Outcome {
+    pub fn duration(&self) -> Duration {
+        match self {
+            Outcome::TemporalMemory(m) => m.duration,
+            Outcome::SpatialPooler(_, duration) => *duration,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SweepConfig {
+    pub mode: Mode,
+    /// Base seed, run i uses seed + i
+    pub seed: u64,
+    pub threads: usize,
+    pub metric: Metric,
+    pub evaluation: EvaluationConfig,
+}
+
+impl Default for SweepConfig {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Grid,
+            seed: 42,
+            threads: 4,
+            metric: Metric::Anomaly,
+            evaluation: EvaluationConfig::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Run {
+    pub index: usize,
+    pub seed: u64,
+    pub parameters: Vec<Parameter>,
+    pub result: Result<Outcome, String>,
+}
+
+/// Creates the parameter values of all runs. In random mode the values
+/// of run i only depend on the seed of run i.
+pub fn configurations(
+    defaults: &[Parameter],
+    ranges: &[ParameterRange],
+    mode: Mode,
+    seed: u64,
+) -> Result<Vec<Vec<Parameter>>, String> {
+    let mut types = vec![];
+    for range in ranges.iter() {
+        match defaults.iter().find(|p| p.name == range.name) {
+            Some(p) => types.push(p.value.clone()),
+            None => return Err(format!("Program does not define {}", range.name)),
+        }
+    }
+    let parameter = |i: usize, value: f32| Parameter {
+        name: ranges[i].name.clone(),
+        value: typed_value(&types[i], value),
+    };
+    match mode {
+        Mode::Grid => {
+            let mut configs = vec![vec![]];
+            for (i, range) in ranges.iter().enumerate() {
+                let values = match &range.values {
+                    Values::List(values) => values,
+                    Values::Interval(..) => return Err(format!("Grid search needs a list of values for {}", range.name)),
+                };
+                configs = configs
+                    .into_iter()
+                    .flat_map(|config: Vec<Parameter>| {
+                        values.iter().map(move |v| {
+                            let mut config = config.clone();
+                            config.push(parameter(i, *v));
+                            config
+                        })
+                    })
+                    .collect();
+            }
+            Ok(configs)
+        }
+        Mode::Random(samples) => Ok((0..samples)
+            .map(|run| {
+                let mut rng = StdRng::seed_from_u64(seed + run as u64);
+                ranges
+                    .iter()
+                    .enumerate()
+                    .map(|(i, range)| match &range.values {
+                        Values::List(values) => parameter(i, values[rng.gen_range(0..values.len())]),
+                        Values::Interval(min, max) => parameter(i, rng.gen_range(*min..=*max)),
+                    })
+                    .collect()
+            })
+            .collect()),
+    }
+}
+
+/// Evaluates all configurations of the program in parallel and returns
+/// the runs ranked by the metric, best first
+pub fn sweep(code: &str, dataset: &Dataset, ranges: &[ParameterRange], config: &SweepConfig) -> Result<Vec<Run>, String> {
+    let spatial_pooler = is_spatial_pooler(code);
+    if !config.metric.applies_to(spatial_pooler) {
+        return Err(if spatial_pooler {
+            "Spatial pooler programs are ranked by entropy, stability, noise or duration".to_string()
+        } else {
+            "Temporal memory programs are ranked by anomaly, predicted or duration".to_string()
+        });
+    }
+    let items = parse_program(code);
+    let configs = configurations(&parameters(&items), ranges, config.mode, config.seed)?;
+    let mut runs: Vec<(Run, String)> = configs
+        .into_iter()
+        .enumerate()
+        .map(|(index, parameters)| {
+            let mut items = items.clone();
+            for p in parameters.iter() {
+                set_parameter(&mut items, p);
+            }
+            let run = Run {
+                index,
+                seed: config.seed + index as u64,
+                parameters,
+                result: Err("Not evaluated".to_string()),
+            };
+            (run, Source::from_items(&items))
+        })
+        .collect();
+    for_each_parallel(&mut runs, config.threads, |(run, code)| {
+        let evaluation = EvaluationConfig {
+            seed: run.seed,
+            ..config.evaluation.clone()
+        };
+        run.result = if spatial_pooler {
+            let start = Instant::now();
+            measure_spatial_pooler_dataset(code, dataset, &evaluation).map(|m| Outcome::SpatialPooler(m, start.elapsed()))
+        } else {
+            measure(code, dataset, &evaluation).map(Outcome::TemporalMemory)
+        };
+    });
+    let mut runs: Vec<Run> = runs.into_iter().map(|(run, _)| run).collect();
+    runs.sort_by(|a, b| {
+        config
+            .metric
+            .rank_value(&a.result)
+            .total_cmp(&config.metric.rank_value(&b.result))
+    });
+    Ok(runs)
+}
+
+/// Tab separated results table, one line per run in the given order
+pub struct ResultsTable<'a>(pub &'a [Run]);
+
+impl fmt::Display for ResultsTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rank\trun\tseed")?;
+        if let Some(run) = self.0.first() {
+            for p in run.parameters.iter() {
+                write!(f, "\t{}", p.name)?;
+            }
+        }
+        let spatial_pooler = self.0.iter().any(|run| matches!(run.result, Ok(Outcome::SpatialPooler(..))));
+        if spatial_pooler {
+            writeln!(f, "\tsparsity\tentropy\tstability\tnoise_degradation\tseconds")?;
+        } else {
+            writeln!(f, "\tanomaly\tpredicted\tseconds")?;
+        }
+        for (rank, run) in self.0.iter().enumerate() {
+            write!(f, "{}\t{}\t{}", rank + 1, run.index, run.seed)?;
+            for p in run.parameters.iter() {
+                match p.value {
+                    PushType::Int { val } => write!(f, "\t{}", val)?,
+                    PushType::Float { val } => write!(f, "\t{}", val)?,
+                    _ => write!(f, "\t-")?,
+                }
+            }
+            match &run.result {
+                Ok(Outcome::TemporalMemory(m)) => {
+                    writeln!(f, "\t{:.4}\t{:.4}\t{:.3}", m.anomaly, m.predicted, m.duration.as_secs_f32())?
+                }
+                Ok(Outcome::SpatialPooler(m, duration)) => writeln!(
+                    f,
+                    "\t{:.4}\t{:.4}\t{:.4}\t{:.4}\t{:.3}",
+                    m.sparsity,
+                    m.entropy,
+                    m.stability,
+                    m.noise_degradation,
+                    duration.as_secs_f32()
+                )?,
+                Err(reason) if spatial_pooler => writeln!(f, "\tinvalid ({})\t-\t-\t-\t-", reason)?,
+                Err(reason) => writeln!(f, "\tinvalid ({})\t-\t-", reason)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> Vec<Parameter> {
+        parameters(&parse_program("( 4 NUM_CELLS INTEGER.DEFINE 0.5 PERM FLOAT.DEFINE )"))
+    }
+
+    #[test]
+    fn parameter_ranges_are_parsed() {
+        assert_eq!(
+            "NUM_CELLS=1,2,4".parse(),
+            Ok(ParameterRange {
+                name: "NUM_CELLS".to_string(),
+                values: Values::List(vec![1.0, 2.0, 4.0])
+            })
+        );
+        assert_eq!("PERM=0.1:0.3".parse::<ParameterRange>().unwrap().values, Values::Interval(0.1, 0.3));
+        assert!("PERM".parse::<ParameterRange>().is_err());
+        assert!("PERM=0.3:0.1".parse::<ParameterRange>().is_err());
+    }
+
+    #[test]
+    fn grid_creates_all_combinations() {
+        let ranges = vec!["NUM_CELLS=1,2".parse().unwrap(), "PERM=0.1,0.2,0.3".parse().unwrap()];
+        let configs = configurations(&defaults(), &ranges, Mode::Grid, 0).unwrap();
+        assert_eq!(configs.len(), 6);
+        assert_eq!(configs[5][0].value, PushType::Int { val: 2 });
+        assert_eq!(configs[5][1].value, PushType::Float { val: 0.3 });
+        let unknown = vec!["FOO=1".parse().unwrap()];
+        assert!(configurations(&defaults(), &unknown, Mode::Grid, 0).is_err());
+    }
+
+    #[test]
+    fn random_search_is_reproducible() {
+        let ranges = vec!["NUM_CELLS=1:8".parse().unwrap(), "PERM=0.1:0.3".parse().unwrap()];
+        let a = configurations(&defaults(), &ranges, Mode::Random(5), 7).unwrap();
+        let b = configurations(&defaults(), &ranges, Mode::Random(5), 7).unwrap();
+        assert_eq!(a, b);
+        assert!(a.iter().all(|c| matches!(c[0].value, PushType::Int { val } if (1..=8).contains(&val))));
+    }
+
+    #[test]
+    fn runs_are_ranked_and_tabulated() {
+        let dataset = Dataset { values: vec![1.0, 2.0] };
+        let ranges = vec!["NUM_CELLS=1,2".parse().unwrap()];
+        let code = "( 4 NUM_CELLS INTEGER.DEFINE )";
+        let runs = sweep(code, &dataset, &ranges, &SweepConfig::default()).unwrap();
+        assert_eq!(runs.len(), 2);
+        let table = ResultsTable(&runs).to_string();
+        assert!(table.starts_with("rank\trun\tseed\tNUM_CELLS\tanomaly"));
+        assert_eq!(table.lines().count(), 3);
+    }
+
+    #[test]
+    fn spatial_pooler_runs_are_ranked_by_its_metrics() {
+        let dataset = Dataset {
+            values: (0..4).map(|i| (i % 2) as f32).collect(),
+        };
+        let ranges = vec!["SYN_PERM_ACTIVE_INC=0.03,0.1".parse().unwrap()];
+        let code = Source::read_debug_code(include_str!("../core/spatial_pooler.push").to_string());
+        let config = SweepConfig {
+            metric: Metric::Stability,
+            ..SweepConfig::default()
+        };
+        let runs = sweep(&code, &dataset, &ranges, &config).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert!(runs.iter().all(|run| matches!(run.result, Ok(Outcome::SpatialPooler(..)))));
+        assert!(config.metric.rank_value(&runs[0].result) <= config.metric.rank_value(&runs[1].result));
+        let table = ResultsTable(&runs).to_string();
+        assert!(table.starts_with("rank\trun\tseed\tSYN_PERM_ACTIVE_INC\tsparsity\tentropy"));
+        assert!(sweep(&code, &dataset, &ranges, &SweepConfig::default()).is_err());
+    }
+}
@@ -0,0 +1,91 @@
+use std::fs::{self, File};
+use std::io::Write;
+
+use std::path::Path;
+
+use phtm::cli::{self, Args};
+use phtm::evolution::evaluation::{is_spatial_pooler, Dataset};
+use phtm::evolution::{Evolution, EvolutionConfig, LINEAGE_HEADER};
+use phtm::node::source::Source;
+
+const DEFAULT_DATASET: &str = "../data/rec-center-hourly.csv";
+const DEFAULT_SEED_PROGRAMS: [&str; 2] = ["src/core/spatial_pooler.push", "src/core/temporal_memory.push"];
+
+const USAGE: &[&str] = &[
+    "Usage: phtm_evolve [--data FILE] [--generations N] [--population N] [--offspring N]",
+    "                   [--seed N] [--threads N] [--rows N] [--lineage FILE] [--out FILE] [program.push]...",
+];
+
+fn main() {
+    let mut config = EvolutionConfig::default();
+    let mut data = DEFAULT_DATASET.to_string();
+    let mut lineage = "lineage.tsv".to_string();
+    let mut out = "best.push".to_string();
+    let mut programs = vec![];
+    let mut args = Args::new(USAGE);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--data" => data = args.value(),
+            "--generations" => config.generations = args.value(),
+            "--population" => config.population_size = args.value(),
+            "--offspring" => config.offspring = args.value(),
+            "--seed" => config.seed = args.value(),
+            "--threads" => config.threads = args.value(),
+            "--rows" => config.evaluation.rows = args.value(),
+            "--lineage" => lineage = args.value(),
+            "--out" => out = args.value(),
+            a if a.starts_with("--") => args.usage(),
+            a => programs.push(a.to_string()),
+        }
+    }
+    if programs.is_empty() {
+        programs.extend(DEFAULT_SEED_PROGRAMS.iter().map(|p| p.to_string()));
+    }
+
+    let seeds: Vec<String> = programs
+        .iter()
+        .map(|p| match fs::read_to_string(p) {
+            Ok(code) => Source::read_debug_code(code),
+            Err(e) => cli::fail(format!("Failed to read {}: {}", p, e)),
+        })
+        .collect();
+    let dataset = Dataset::load(&data).unwrap_or_else(|e| cli::fail(e));
+    cli::init_logger();
+
+    // Each program kind evolves in its own population, the files get
+    // the kind as suffix if both kinds are seeded
+    let (sp_seeds, tm_seeds): (Vec<String>, Vec<String>) = seeds.into_iter().partition(|code| is_spatial_pooler(code));
+    let both = !sp_seeds.is_empty() && !tm_seeds.is_empty();
+    for (kind, seeds) in [("sp", sp_seeds), ("tm", tm_seeds)] {
+        if seeds.is_empty() {
+            continue;
+        }
+        let (lineage, out) = if both {
+            (kind_path(&lineage, kind), kind_path(&out, kind))
+        } else {
+            (lineage.clone(), out.clone())
+        };
+        evolve(config.clone(), dataset.clone(), &seeds, &lineage, &out);
+    }
+}
+
+fn evolve(config: EvolutionConfig, dataset: Dataset, seeds: &[String], lineage: &str, out: &str) {
+    let mut log = File::create(lineage).unwrap_or_else(|e| cli::fail(format!("Failed to create {}: {}", lineage, e)));
+    writeln!(log, "{}", LINEAGE_HEADER).expect("Failed to write lineage");
+    let best = Evolution::new(config, dataset, seeds)
+        .and_then(|mut evolution| evolution.run(&mut log))
+        .unwrap_or_else(|e| cli::fail(format!("Evolution failed: {}", e)));
+    fs::write(out, &best.code).expect("Failed to write best program");
+    println!("Best individual {} ({}) written to {}, lineage in {}", best.id, best.fitness, out, lineage);
+}
+
+/// Inserts the program kind before the extension, best.push becomes best.sp.push
+fn kind_path(path: &str, kind: &str) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().map_or(String::new(), |s| s.to_string_lossy().to_string());
+    let name = match path.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, kind, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, kind),
+    };
+    path.with_file_name(name).to_string_lossy().to_string()
+}
@@ -1,10 +1,12 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::thread;
 use std::time::Duration;
 
 use log::error;
 
 use super::key::Key;
+use super::mouse::Mouse;
 use super::InputEvent;
 
 /// A small event handler that wrap crossterm input and tick event. Each event
@@ -25,18 +27,26 @@ impl Events {
 
         let event_tx = tx.clone();
         let event_stop_capture = stop_capture.clone();
-        tokio::spawn(async move {
+        // Polling blocks, a task would hold a worker of the async runtime
+        thread::spawn(move || {
             loop {
                 // poll for tick rate duration, if no event, sent tick event.
                 if crossterm::event::poll(tick_rate).unwrap() {
-                    if let crossterm::event::Event::Key(key) = crossterm::event::read().unwrap() {
-                        let key = Key::from(key);
-                        if let Err(err) = event_tx.send(InputEvent::Input(key)).await {
+                    let event = match crossterm::event::read().unwrap() {
+                        crossterm::event::Event::Key(key) => Some(InputEvent::Input(Key::from(key))),
+                        crossterm::event::Event::Mouse(mouse) => match Mouse::from(mouse) {
+                            Mouse::Unknown => None,
+                            mouse => Some(InputEvent::Mouse(mouse)),
+                        },
+                        crossterm::event::Event::Resize(width, height) => Some(InputEvent::Resize(width, height)),
+                    };
+                    if let Some(event) = event {
+                        if let Err(err) = event_tx.blocking_send(event) {
                             error!("Oops!, {}", err);
                         }
                     }
                 }
-                if let Err(err) = event_tx.send(InputEvent::Tick).await {
+                if let Err(err) = event_tx.blocking_send(InputEvent::Tick) {
                     error!("Oops!, {}", err);
                 }
                 if event_stop_capture.load(Ordering::Relaxed) {
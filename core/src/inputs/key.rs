@@ -1,4 +1,5 @@
 use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
 
 use crossterm::event;
 
@@ -115,6 +116,57 @@ impl Display for Key {
     }
 }
 
+/// Parses the names shown by `Display`, with or without the angle
+/// brackets, e.g. `<Ctrl+c>`, `PageUp`, `F5`, `Space` or `q`
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = match s.strip_prefix('<').and_then(|s| s.strip_suffix('>')) {
+            Some(name) if !name.is_empty() => name,
+            _ => s,
+        };
+        let char = |c: &str| match c {
+            "Space" => Some(' '),
+            _ => {
+                let mut chars = c.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(c),
+                    _ => None,
+                }
+            }
+        };
+        let key = match name {
+            "Enter" => Some(Key::Enter),
+            "Tab" => Some(Key::Tab),
+            "Backspace" => Some(Key::Backspace),
+            "Esc" => Some(Key::Esc),
+            "Left" => Some(Key::Left),
+            "Right" => Some(Key::Right),
+            "Up" => Some(Key::Up),
+            "Down" => Some(Key::Down),
+            "Ins" => Some(Key::Ins),
+            "Delete" => Some(Key::Delete),
+            "Home" => Some(Key::Home),
+            "End" => Some(Key::End),
+            "PageUp" => Some(Key::PageUp),
+            "PageDown" => Some(Key::PageDown),
+            _ => {
+                if let Some(c) = name.strip_prefix("Ctrl+") {
+                    char(c).map(Key::Ctrl)
+                } else if let Some(c) = name.strip_prefix("Alt+") {
+                    char(c).map(Key::Alt)
+                } else if let Some(n) = name.strip_prefix('F').and_then(|n| n.parse::<u8>().ok()) {
+                    (n <= 12).then(|| Key::from_f(n))
+                } else {
+                    char(name).map(Key::Char)
+                }
+            }
+        };
+        key.ok_or_else(|| format!("unknown key {}", s))
+    }
+}
+
 impl From<event::KeyEvent> for Key {
     fn from(key_event: event::KeyEvent) -> Self {
         match key_event {
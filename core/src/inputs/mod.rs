@@ -1,11 +1,17 @@
 use self::key::Key;
+use self::mouse::Mouse;
 
 pub mod events;
 pub mod key;
+pub mod mouse;
 
 pub enum InputEvent {
     /// An input event occurred.
     Input(Key),
+    /// A mouse event occurred.
+    Mouse(Mouse),
     /// An tick event occurred.
     Tick,
+    /// The terminal was resized to the width and height.
+    Resize(u16, u16),
 }
@@ -0,0 +1,46 @@
+use crossterm::event::{self, MouseButton, MouseEventKind};
+
+/// Represents a mouse event of the left button or the wheel at a column
+/// and row of the terminal.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub enum Mouse {
+    /// Left button pressed
+    Down(u16, u16),
+    /// Moved with the left button pressed
+    Drag(u16, u16),
+    /// Left button released
+    Up(u16, u16),
+    /// Wheel scrolled up
+    ScrollUp(u16, u16),
+    /// Wheel scrolled down
+    ScrollDown(u16, u16),
+    Unknown,
+}
+
+impl Mouse {
+    /// Column and row of the event
+    pub fn position(&self) -> Option<(u16, u16)> {
+        match *self {
+            Mouse::Down(column, row)
+            | Mouse::Drag(column, row)
+            | Mouse::Up(column, row)
+            | Mouse::ScrollUp(column, row)
+            | Mouse::ScrollDown(column, row) => Some((column, row)),
+            Mouse::Unknown => None,
+        }
+    }
+}
+
+impl From<event::MouseEvent> for Mouse {
+    fn from(mouse_event: event::MouseEvent) -> Self {
+        let (column, row) = (mouse_event.column, mouse_event.row);
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) => Mouse::Down(column, row),
+            MouseEventKind::Drag(MouseButton::Left) => Mouse::Drag(column, row),
+            MouseEventKind::Up(MouseButton::Left) => Mouse::Up(column, row),
+            MouseEventKind::ScrollUp => Mouse::ScrollUp(column, row),
+            MouseEventKind::ScrollDown => Mouse::ScrollDown(column, row),
+            _ => Mouse::Unknown,
+        }
+    }
+}
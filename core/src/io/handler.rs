@@ -1,57 +1,228 @@
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use eyre::Result;
-use log::{error, info};
+use eyre::{eyre, Result};
+use log::{error, info, warn};
+use tokio::sync::{mpsc, oneshot};
 
 use super::IoEvent;
 use crate::app::App;
+use crate::node::checkpoint::Checkpoint;
+use crate::node::runtime::RuntimeCommand;
+use crate::node::sdr;
+
+/// Time without a queued input after which the remaining inputs are
+/// rejected, e.g. while the node is paused
+const INJECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Wait before an input is offered again to the full input queue
+const INJECT_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Injects the inputs one after another, waiting while the input queue
+/// of the node is full. Returns the number of rejected inputs.
+pub async fn inject(commands: &mpsc::Sender<RuntimeCommand>, inputs: Vec<Vec<bool>>) -> Result<usize> {
+    let mut rejected = 0;
+    let mut last_queued = Instant::now();
+    for input in inputs {
+        loop {
+            let (tx, rx) = oneshot::channel();
+            commands
+                .send(RuntimeCommand::InjectAcked(input.clone(), tx))
+                .await
+                .map_err(|e| eyre!("Node not reachable: {}", e))?;
+            let queued = rx
+                .await
+                .map_err(|_| eyre!("Inputs are not supported by attached nodes"))?;
+            if queued {
+                last_queued = Instant::now();
+                break;
+            }
+            if last_queued.elapsed() >= INJECT_TIMEOUT {
+                rejected += 1;
+                break;
+            }
+            tokio::time::sleep(INJECT_RETRY_INTERVAL).await;
+        }
+    }
+    Ok(rejected)
+}
 
 /// In the IO thread, we handle IO event without blocking the UI thread
 pub struct IoAsyncHandler {
     app: Arc<tokio::sync::Mutex<App>>,
+    /// Commands of the node the app is attached to
+    commands: mpsc::Sender<RuntimeCommand>,
 }
 
 impl IoAsyncHandler {
-    pub fn new(app: Arc<tokio::sync::Mutex<App>>) -> Self {
-        Self { app }
+    pub fn new(app: Arc<tokio::sync::Mutex<App>>, commands: mpsc::Sender<RuntimeCommand>) -> Self {
+        Self { app, commands }
     }
 
-    /// We could be async here
+    /// Runs the event, errors are written to the log
     pub async fn handle_io_event(&mut self, io_event: IoEvent) {
         let result = match io_event {
-            IoEvent::Initialize => self.do_initialize().await,
-            IoEvent::Sleep(duration) => self.do_sleep(duration).await,
+            IoEvent::LoadProgram(path) => self.do_load_program(&path).await,
+            IoEvent::LoadCheckpoint(path) => self.do_load_checkpoint(&path).await,
+            IoEvent::SaveCheckpoint(path) => self.do_save_checkpoint(&path).await,
+            IoEvent::InjectSdrFile(path) => self.do_inject_sdr_file(&path).await,
+            IoEvent::InjectSequence(path, times) => self.do_inject_sequence(&path, times).await,
+            IoEvent::ConnectBus(broker) => self.do_connect_bus(&broker).await,
+            IoEvent::ExportGraph(path) => self.do_export_graph(&path).await,
+            IoEvent::SaveSession(path) => self.do_save_session(&path).await,
         };
 
         if let Err(err) = result {
-            error!("Oops, something wrong happen: {:?}", err);
+            error!("{}", err);
         }
 
         let mut app = self.app.lock().await;
         app.loaded();
     }
 
-    /// We use dummy implementation here, just wait 1s
-    async fn do_initialize(&mut self) -> Result<()> {
-        info!("🚀 Initialize the application");
-        let mut app = self.app.lock().await;
-        tokio::time::sleep(Duration::from_secs(1)).await;
-        app.initialized(); // we could update the app state
-        info!("👍 Application initialized");
+    async fn command(&self, command: RuntimeCommand) -> Result<()> {
+        self.commands
+            .send(command)
+            .await
+            .map_err(|e| eyre!("Node not reachable: {}", e))
+    }
+
+    /// Requests a checkpoint of the current state of the node
+    async fn checkpoint(&self) -> Result<Checkpoint> {
+        let (tx, rx) = oneshot::channel();
+        self.command(RuntimeCommand::Checkpoint(tx)).await?;
+        rx.await
+            .map_err(|_| eyre!("Checkpoints are not supported by attached nodes"))
+    }
 
+    async fn do_load_program(&mut self, path: &str) -> Result<()> {
+        info!("Loading program {}", path);
+        let code = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| eyre!("Failed to read {}: {}", path, e))?;
+        self.command(RuntimeCommand::LoadProgram(code.clone())).await?;
+        self.app.lock().await.load_source(&code);
+        info!("Program {} loaded", path);
         Ok(())
     }
 
-    /// Just take a little break
-    async fn do_sleep(&mut self, duration: Duration) -> Result<()> {
-        info!("😴 Go sleeping for {:?}...", duration);
-        tokio::time::sleep(duration).await;
-        info!("⏰ Wake up !");
-        // Notify the app for having slept
-        let mut app = self.app.lock().await;
-        app.slept();
+    async fn do_load_checkpoint(&mut self, path: &str) -> Result<()> {
+        info!("Loading checkpoint {}", path);
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| eyre!("Failed to read {}: {}", path, e))?;
+        let checkpoint = Checkpoint::parse(&content).map_err(|e| eyre!("{}", e))?;
+        self.command(RuntimeCommand::LoadCheckpoint(checkpoint)).await
+    }
+
+    async fn do_save_checkpoint(&mut self, path: &str) -> Result<()> {
+        let checkpoint = self.checkpoint().await?;
+        tokio::fs::write(path, checkpoint.to_json())
+            .await
+            .map_err(|e| eyre!("Failed to write {}: {}", path, e))?;
+        info!("Checkpoint saved to {}", path);
+        Ok(())
+    }
+
+    async fn do_inject_sdr_file(&mut self, path: &str) -> Result<()> {
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| eyre!("Failed to read {}: {}", path, e))?;
+        let inputs = sdr::parse_file(&content).map_err(|e| eyre!("{}: {}", path, e))?;
+        let (size, node_type) = {
+            let app = self.app.lock().await;
+            (app.snapshot().input_size(), app.snapshot().node_type)
+        };
+        for (i, input) in inputs.iter().enumerate() {
+            sdr::check_input_size(input, size, node_type).map_err(|e| eyre!("{}: SDR {}: {}", path, i + 1, e))?;
+        }
+        let count = inputs.len();
+        let rejected = inject(&self.commands, inputs).await?;
+        if rejected > 0 {
+            warn!("Injected {} of {} inputs of {}, {} rejected", count - rejected, count, path, rejected);
+        } else {
+            info!("Injected {} inputs of {}", count, path);
+        }
+        Ok(())
+    }
+
+    async fn do_inject_sequence(&mut self, path: &str, times: usize) -> Result<()> {
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| eyre!("Failed to read {}: {}", path, e))?;
+        let encoder = self.app.lock().await.input_encoder().map_err(|e| eyre!(e))?;
+        let inputs = encoder.encode_file(&content).map_err(|e| eyre!("{}: {}", path, e))?;
+        let count = inputs.len() * times;
+        let sequence = (0..times).flat_map(|_| inputs.iter().cloned()).collect();
+        let rejected = inject(&self.commands, sequence).await?;
+        if rejected > 0 {
+            warn!("Injected {} of {} inputs of {} {} times, {} rejected", count - rejected, count, path, times, rejected);
+        } else {
+            info!("Injected {} inputs of {} {} times", inputs.len(), path, times);
+        }
+        Ok(())
+    }
+
+    async fn do_connect_bus(&mut self, broker: &str) -> Result<()> {
+        info!("Connecting to {}", broker);
+        self.command(RuntimeCommand::ConnectBus(broker.to_string())).await
+    }
+
+    async fn do_export_graph(&mut self, path: &str) -> Result<()> {
+        let checkpoint = self.checkpoint().await?;
+        if checkpoint.nodes.is_empty() {
+            return Err(eyre!("No memory graph to export"));
+        }
+        tokio::fs::write(path, checkpoint.to_dot())
+            .await
+            .map_err(|e| eyre!("Failed to write {}: {}", path, e))?;
+        info!("Graph with {} nodes exported to {}", checkpoint.nodes.len(), path);
+        Ok(())
+    }
 
+    async fn do_save_session(&mut self, path: &str) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.command(RuntimeCommand::Session(tx)).await?;
+        let session = rx
+            .await
+            .map_err(|_| eyre!("Sessions are not recorded by attached nodes"))?;
+        tokio::fs::write(path, session.to_json())
+            .await
+            .map_err(|e| eyre!("Failed to write {}: {}", path, e))?;
+        info!("Session of {} changes in {} steps saved to {}", session.entries.len(), session.steps, path);
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::args::NodeArgs;
+    use crate::node::runtime::{NodeRuntime, RuntimeConfig};
+
+    fn spawn_node(program: &str, start_paused: bool) -> mpsc::Sender<RuntimeCommand> {
+        let args = vec!["phtm".to_string(), program.to_string()];
+        let config = RuntimeConfig {
+            connect: false,
+            start_paused,
+            ..RuntimeConfig::default()
+        };
+        let (handle, _task) = NodeRuntime::spawn(NodeArgs::parse(&args).unwrap(), config);
+        handle.commands
+    }
+
+    #[tokio::test]
+    async fn inject_waits_for_the_input_queue() {
+        let commands = spawn_node("( BP1 EXEC.Y ( INPUT.NEXT ) )", false);
+        let inputs = (0..200).map(|i| vec![i % 2 == 0; 8]).collect();
+        assert_eq!(inject(&commands, inputs).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn inject_reports_inputs_rejected_by_a_paused_node() {
+        // Inputs wait in the queue of 64 inputs until the graph exists
+        let commands = spawn_node("( BP1 EXEC.Y ( INPUT.NEXT ) )", true);
+        let inputs = (0..100).map(|_| vec![true; 8]).collect();
+        assert_eq!(inject(&commands, inputs).await.unwrap(), 36);
+    }
+}
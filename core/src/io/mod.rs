@@ -1,9 +1,22 @@
-use std::time::Duration;
-
 pub mod handler;
-// For this dummy application we only need two IO event
+
+/// IO operations of the terminal interface, run outside the UI thread
 #[derive(Debug, Clone)]
 pub enum IoEvent {
-    Initialize,      // Launch to initialize the application
-    Sleep(Duration), // Just take a little break
+    /// Replaces the program of the node with the file
+    LoadProgram(String),
+    /// Restores the graph and bindings of a checkpoint file
+    LoadCheckpoint(String),
+    /// Saves a checkpoint of the node to the file
+    SaveCheckpoint(String),
+    /// Injects the SDRs of the file, one per line
+    InjectSdrFile(String),
+    /// Injects the encoded inputs of the file the number of times
+    InjectSequence(String, usize),
+    /// Connects the node to the broker at the host
+    ConnectBus(String),
+    /// Writes the memory graph as DOT file
+    ExportGraph(String),
+    /// Writes the recorded session as JSON file
+    SaveSession(String),
 }
@@ -1,35 +1,76 @@
 use std::io::stdout;
+use std::panic::{self, PanicHookInfo};
 use std::sync::Arc;
+use std::thread;
 use std::time::Duration;
 
 use app::{App, AppReturn};
+use crossterm::cursor::{MoveTo, Show};
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::execute;
+use crossterm::terminal::{Clear, ClearType};
 use eyre::Result;
+use log::debug;
 use inputs::events::Events;
 use inputs::InputEvent;
-use io::IoEvent;
 use tui::backend::CrosstermBackend;
 use tui::Terminal;
 
 use crate::app::ui;
 
 pub mod app;
+pub mod cli;
+pub mod evolution;
 pub mod inputs;
 pub mod io;
 pub mod node;
 extern crate pushr;
 
 // For ZMQ messaging
-#[macro_use]
-extern crate enum_primitive_derive;
 extern crate num_traits;
-#[path = "../../msg/rs/msg.rs"]
-pub mod shared;
+pub use phtm_msg::shared;
 
 
+type PanicHook = dyn Fn(&PanicHookInfo<'_>) + Sync + Send + 'static;
+
+/// Restores the terminal and the previous panic hook when the interface
+/// ends, whether it quits, fails or panics
+struct TerminalGuard {
+    previous_hook: Arc<PanicHook>,
+}
+
+impl TerminalGuard {
+    /// A panic must not leave the terminal in raw mode, the message of
+    /// the previous hook is printed once it is restored
+    fn install() -> Self {
+        let previous_hook: Arc<PanicHook> = Arc::from(panic::take_hook());
+        let hook = Arc::clone(&previous_hook);
+        panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            hook(info);
+        }));
+        Self { previous_hook }
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+        // Hooks cannot be replaced while unwinding
+        if !thread::panicking() {
+            let previous_hook = Arc::clone(&self.previous_hook);
+            panic::set_hook(Box::new(move |info| previous_hook(info)));
+        }
+    }
+}
+
 pub async fn start_ui(app: &Arc<tokio::sync::Mutex<App>>) -> Result<()> {
+    let _guard = TerminalGuard::install();
+
     // Configure Crossterm backend for tui
-    let stdout = stdout();
+    let mut stdout = stdout();
     crossterm::terminal::enable_raw_mode()?;
+    execute!(stdout, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
     terminal.clear()?;
@@ -40,11 +81,7 @@ pub async fn start_ui(app: &Arc<tokio::sync::Mutex<App>>) -> Result<()> {
     let mut events = Events::new(tick_rate);
 
     // Trigger state change from Init to Initialized
-    {
-        let mut app = app.lock().await;
-        // Here we assume the the first load is a long task
-        app.dispatch(IoEvent::Initialize).await;
-    }
+    app.lock().await.initialized();
 
     loop {
         let mut app = app.lock().await;
@@ -55,7 +92,14 @@ pub async fn start_ui(app: &Arc<tokio::sync::Mutex<App>>) -> Result<()> {
         // Handle inputs
         let result = match events.next().await {
             InputEvent::Input(key) => app.do_action(key).await,
+            InputEvent::Mouse(mouse) => app.do_mouse(mouse, terminal.size()?).await,
             InputEvent::Tick => app.update_on_tick().await,
+            // The next draw lays the panes out for the new size
+            InputEvent::Resize(width, height) => {
+                debug!("Terminal resized to {}x{}", width, height);
+                terminal.autoresize()?;
+                AppReturn::Continue
+            }
         };
         // Check if we should exit
         if result == AppReturn::Exit {
@@ -64,10 +108,12 @@ pub async fn start_ui(app: &Arc<tokio::sync::Mutex<App>>) -> Result<()> {
         }
     }
 
-    // Restore the terminal and close application
-    terminal.clear()?;
-    terminal.show_cursor()?;
-    crossterm::terminal::disable_raw_mode()?;
-
+    // The guard restores the terminal
     Ok(())
 }
+
+/// Leaves raw mode and clears the interface like the normal exit
+fn restore_terminal() {
+    let _ = crossterm::terminal::disable_raw_mode();
+    let _ = execute!(stdout(), DisableMouseCapture, Clear(ClearType::All), MoveTo(0, 0), Show);
+}
@@ -1,41 +1,123 @@
 use std::sync::Arc;
-use std::env;
+use std::{env, fs};
 
 use eyre::Result;
 use log::LevelFilter;
-use phtm::app::App;
+use phtm::app::actions::KeyBindings;
+use phtm::app::{App, KEYS_FILE};
 use phtm::io::handler::IoAsyncHandler;
-use pushr::push::instructions::{InstructionCache, InstructionSet};
 use phtm::io::IoEvent;
+use phtm::node::args::{self, NodeArgs};
+use phtm::node::debug;
+use phtm::node::runtime::{self, NodeRuntime, RuntimeConfig};
+use phtm::node::session::Session;
 use phtm::start_ui;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let mut args: Vec<String> = env::args().collect();
+    // Sessions are replayed with the program they were recorded with
+    let replay = match args.iter().position(|a| a == "--replay") {
+        Some(i) if i + 1 < args.len() => {
+            let path = args.drain(i..i + 2).nth(1).unwrap_or_default();
+            match fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|c| Session::parse(&c))
+            {
+                Ok(session) => Some(session),
+                Err(e) => {
+                    println!("{}: {}", path, e);
+                    return Ok(());
+                }
+            }
+        }
+        _ => None,
+    };
+    // Attach to a running node or start one
+    let endpoint = match args.get(1).map(|a| a.as_str()) {
+        Some("--attach") if args.len() == 3 => Some(args[2].clone()),
+        _ => None,
+    };
+    let node_args = match endpoint {
+        Some(_) => None,
+        None => match NodeArgs::parse(&args) {
+            Ok(node_args) => Some(node_args),
+            Err(e) => {
+                println!("{}", e);
+                println!("Usage: {} {}", args[0], args::USAGE);
+                println!("       {} <program> [...] --replay SESSION", args[0]);
+                println!("       {} --attach ENDPOINT", args[0]);
+                return Ok(());
+            }
+        },
+    };
 
-    let args: Vec<String> = env::args().collect(); 
-    if args.len() < 2 {
-        return Ok(());
-    }
+    // The key bindings file is optional
+    let bindings = match fs::read_to_string(KEYS_FILE) {
+        Ok(content) => match KeyBindings::parse(&content) {
+            Ok(bindings) => bindings,
+            Err(e) => {
+                println!("{}: {}", KEYS_FILE, e);
+                return Ok(());
+            }
+        },
+        Err(_) => KeyBindings::default(),
+    };
 
-    let bin = args[0].clone();
-    let input = args[1].clone();
+    // Configure log
+    tui_logger::init_logger(LevelFilter::Debug).unwrap();
+    tui_logger::set_default_level(log::LevelFilter::Debug);
 
-    println!("bin = {}", bin);
-    println!("input = {}", input);
+    // Attached nodes do not send their source
+    let program = node_args
+        .as_ref()
+        .map(|node_args| node_args.program.clone());
+    let (runtime, node) = match (node_args, &endpoint) {
+        (Some(node_args), _) => {
+            if let Some(session) = &replay {
+                if let Err(e) = session
+                    .check_program(runtime::program_hash(&runtime::load_executor(&node_args)))
+                {
+                    println!("{}", e);
+                    return Ok(());
+                }
+            }
+            // The node runs on the bus, the UI is attached to it. Replays
+            // run offline, inputs of the bus would change the state.
+            let config = RuntimeConfig {
+                start_paused: true,
+                record: true,
+                connect: replay.is_none(),
+                replay,
+                ..RuntimeConfig::default()
+            };
+            NodeRuntime::spawn(node_args, config)
+        }
+        (None, Some(endpoint)) => match debug::attach(endpoint) {
+            Ok(attached) => attached,
+            Err(e) => {
+                println!("{}", e);
+                return Ok(());
+            }
+        },
+        (None, None) => return Ok(()),
+    };
 
     let (sync_io_tx, mut sync_io_rx) = tokio::sync::mpsc::channel::<IoEvent>(100);
 
+    // The IO thread sends its commands to the node directly
+    let commands = runtime.commands.clone();
     // We need to share the App between thread
-    let app = Arc::new(tokio::sync::Mutex::new(App::new(sync_io_tx.clone(), bin, input)));
+    let mut app = App::new(sync_io_tx.clone(), runtime, bindings);
+    if let Some(program) = program {
+        app.load_source(&program);
+    }
+    let app = Arc::new(tokio::sync::Mutex::new(app));
     let app_ui = Arc::clone(&app);
 
-    // Configure log
-    tui_logger::init_logger(LevelFilter::Debug).unwrap();
-    tui_logger::set_default_level(log::LevelFilter::Debug);
-
     // Handle IO in a specifc thread
     tokio::spawn(async move {
-        let mut handler = IoAsyncHandler::new(app);
+        let mut handler = IoAsyncHandler::new(app, commands);
         while let Some(io_event) = sync_io_rx.recv().await {
             handler.handle_io_event(io_event).await;
         }
@@ -43,5 +125,12 @@ async fn main() -> Result<()> {
 
     start_ui(&app_ui).await?;
 
+    // Quit shuts a started node down, an attached node keeps running
+    let state = node.await?;
+    match endpoint {
+        Some(endpoint) => println!("Detached from {} ({})", endpoint, state),
+        None => println!("Node stopped ({})", state),
+    }
+
     Ok(())
 }
@@ -0,0 +1,199 @@
+// Args Module
+// Command line arguments of a node process
+
+use crate::shared::msg::{MessageKey, NodeType};
+
+#[derive(Clone, Debug)]
+pub struct NodeArgs {
+    /// Path of the node binary
+    pub bin: String,
+    /// Push program code
+    pub program: String,
+    pub node_id: u16,
+    pub node_type: NodeType,
+    /// Message keys the node consumes
+    pub keys: Vec<u16>,
+    /// Message key of the output SDR, no output without key
+    pub output_key: Option<u16>,
+    /// Checkpoint file written on shutdown
+    pub checkpoint: Option<String>,
+    /// Checkpoint file restored after the graph creation
+    pub restore: Option<String>,
+    /// Deletes the restored checkpoint, e.g. the temporary checkpoint of
+    /// a replica
+    pub remove_restored: bool,
+    /// Endpoint of the debug protocol, e.g. tcp://127.0.0.1:7005
+    pub debug: Option<String>,
+    /// Seed of the random instructions, unseeded without
+    pub seed: Option<u64>,
+}
+
+pub const USAGE: &str = "<program> [--id N] [--type sp|tm|encoder] [--key K]... [--output-key K] [--checkpoint FILE] [--restore FILE] [--restore-once FILE] [--debug ENDPOINT] [--seed N]";
+
+/// Node types by command line name
+pub const NODE_TYPES: [(&str, NodeType); 3] = [
+    ("sp", NodeType::SPATIAL_POOLER),
+    ("tm", NodeType::TEMPORAL_MEMORY),
+    ("encoder", NodeType::SCALAR_ENCODER),
+];
+
+impl NodeArgs {
+    /// Parses `<bin>` followed by the arguments described in `USAGE`.
+    /// Without id the node id is derived from the process id, without
+    /// keys the default input key of the node type is used. The spatial
+    /// pooler outputs with D_SPOOL by default.
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        if args.len() < 2 {
+            return Err("No input provided".to_string());
+        }
+        let mut node_args = Self {
+            bin: args[0].clone(),
+            program: args[1].clone(),
+            node_id: std::process::id() as u16,
+            node_type: NodeType::UNDEFINED,
+            keys: vec![],
+            output_key: None,
+            checkpoint: None,
+            restore: None,
+            remove_restored: false,
+            debug: None,
+            seed: None,
+        };
+        let mut it = args[2..].iter();
+        while let Some(arg) = it.next() {
+            let mut value = || it.next().ok_or(format!("Missing value for {}", arg));
+            match arg.as_str() {
+                "--id" => {
+                    node_args.node_id = value()?
+                        .parse()
+                        .map_err(|_| "Node id must be an unsigned 16 bit integer".to_string())?
+                }
+                "--type" => {
+                    let t = value()?;
+                    node_args.node_type = match NODE_TYPES.iter().find(|(name, _)| name == t) {
+                        Some((_, node_type)) => *node_type,
+                        None => return Err(format!("Unknown node type {}", t)),
+                    }
+                }
+                "--key" => node_args.keys.push(
+                    value()?
+                        .parse()
+                        .map_err(|_| "Key must be an unsigned 16 bit integer".to_string())?,
+                ),
+                "--output-key" => {
+                    node_args.output_key = Some(
+                        value()?
+                            .parse()
+                            .map_err(|_| "Key must be an unsigned 16 bit integer".to_string())?,
+                    )
+                }
+                "--checkpoint" => node_args.checkpoint = Some(value()?.clone()),
+                "--restore" => node_args.restore = Some(value()?.clone()),
+                "--restore-once" => {
+                    node_args.restore = Some(value()?.clone());
+                    node_args.remove_restored = true;
+                }
+                "--debug" => node_args.debug = Some(value()?.clone()),
+                "--seed" => {
+                    node_args.seed = Some(
+                        value()?
+                            .parse()
+                            .map_err(|_| "Seed must be an unsigned 64 bit integer".to_string())?,
+                    )
+                }
+                a => return Err(format!("Unknown argument {}", a)),
+            }
+        }
+        if node_args.keys.is_empty() {
+            match node_args.node_type {
+                NodeType::SPATIAL_POOLER => node_args.keys.push(MessageKey::D_INPUT as u16),
+                NodeType::TEMPORAL_MEMORY => node_args.keys.push(MessageKey::D_SPOOL as u16),
+                _ => (),
+            }
+        }
+        if node_args.output_key.is_none() && node_args.node_type == NodeType::SPATIAL_POOLER {
+            node_args.output_key = Some(MessageKey::D_SPOOL as u16);
+        }
+        Ok(node_args)
+    }
+
+    /// Arguments (without binary) of a replica of this node with the
+    /// given id and program. Replicas do not restore the checkpoint of
+    /// this node.
+    pub fn replica_args(&self, node_id: u16, program: String) -> Vec<String> {
+        let mut args = vec![program, "--id".to_string(), node_id.to_string()];
+        if let Some((name, _)) = NODE_TYPES.iter().find(|(_, t)| *t == self.node_type) {
+            args.push("--type".to_string());
+            args.push(name.to_string());
+        }
+        for k in self.keys.iter() {
+            args.push("--key".to_string());
+            args.push(k.to_string());
+        }
+        if let Some(k) = self.output_key {
+            args.push("--output-key".to_string());
+            args.push(k.to_string());
+        }
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_options() {
+        let args = NodeArgs::parse(&to_args(&["bin", "( )", "--id", "7", "--type", "tm", "--key", "1003"])).unwrap();
+        assert_eq!(args.node_id, 7);
+        assert_eq!(args.node_type, NodeType::TEMPORAL_MEMORY);
+        assert_eq!(args.keys, vec![1003]);
+        assert_eq!(args.checkpoint, None);
+        let args = NodeArgs::parse(&to_args(&["bin", "( )", "--checkpoint", "node.chk"])).unwrap();
+        assert_eq!(args.checkpoint, Some("node.chk".to_string()));
+        let args = NodeArgs::parse(&to_args(&["bin", "( )", "--restore", "node.chk"])).unwrap();
+        assert_eq!(args.restore, Some("node.chk".to_string()));
+        assert!(!args.remove_restored);
+        let args = NodeArgs::parse(&to_args(&["bin", "( )", "--restore-once", "node.chk"])).unwrap();
+        assert_eq!(args.restore, Some("node.chk".to_string()));
+        assert!(args.remove_restored);
+        let args = NodeArgs::parse(&to_args(&["bin", "( )", "--debug", "tcp://127.0.0.1:7005"])).unwrap();
+        assert_eq!(args.debug, Some("tcp://127.0.0.1:7005".to_string()));
+        let args = NodeArgs::parse(&to_args(&["bin", "( )", "--seed", "42"])).unwrap();
+        assert_eq!(args.seed, Some(42));
+    }
+
+    #[test]
+    fn parse_uses_default_keys_of_node_type() {
+        let args = NodeArgs::parse(&to_args(&["bin", "( )", "--type", "sp"])).unwrap();
+        assert_eq!(args.keys, vec![MessageKey::D_INPUT as u16]);
+        assert_eq!(args.output_key, Some(MessageKey::D_SPOOL as u16));
+        let args = NodeArgs::parse(&to_args(&["bin", "( )", "--type", "tm"])).unwrap();
+        assert_eq!(args.output_key, None);
+    }
+
+    #[test]
+    fn replica_args_parse_to_replica() {
+        let args = NodeArgs::parse(&to_args(&["bin", "( )", "--id", "7", "--type", "sp", "--key", "1003", "--output-key", "1004", "--restore", "7.chk"])).unwrap();
+        let mut replica_args = vec!["bin".to_string()];
+        replica_args.extend(args.replica_args(8, "( A )".to_string()));
+        let replica = NodeArgs::parse(&replica_args).unwrap();
+        assert_eq!(replica.program, "( A )");
+        assert_eq!(replica.node_id, 8);
+        assert_eq!(replica.node_type, NodeType::SPATIAL_POOLER);
+        assert_eq!(replica.keys, vec![1003]);
+        assert_eq!(replica.output_key, Some(1004));
+        assert_eq!(replica.restore, None);
+    }
+
+    #[test]
+    fn parse_rejects_unknown_arguments() {
+        assert!(NodeArgs::parse(&to_args(&["bin"])).is_err());
+        assert!(NodeArgs::parse(&to_args(&["bin", "( )", "--id"])).is_err());
+        assert!(NodeArgs::parse(&to_args(&["bin", "( )", "--foo", "1"])).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+// Bus Module
+// Connection of a node to the message bus: the publisher, the control
+// channel to the broker and the inbound queue filled by the subscriber
+// thread. Requests of the control channel are converted to commands
+// of the runtime.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc as std_mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+use crate::node::channel::{self, ChannelConfig, ChannelEvent, ControlChannel, Request};
+use crate::node::control::State;
+use crate::node::execution::PushExecutor;
+use crate::node::message::Message;
+use crate::node::network::{self, NodeInfo};
+use crate::node::queue::QueueConfig;
+use crate::node::runtime::RuntimeCommand;
+use crate::node::session::{self, Recorder};
+use crate::shared::msg::{MessageCommand, MessageKey, MessageType};
+
+pub struct Bus {
+    publisher: zmq::Socket,
+    control: ControlChannel,
+    /// Messages received by the subscriber thread
+    pub inbound: std_mpsc::Receiver<Vec<u8>>,
+    stop_subscriber: Arc<AtomicBool>,
+    subscriber_thread: thread::JoinHandle<()>,
+    last_heartbeat: Instant,
+}
+
+impl Bus {
+    pub fn connect(broker: &str, node_id: u16, config: &QueueConfig) -> Result<Self, String> {
+        let (tx, rx) = std_mpsc::sync_channel(config.inbound_capacity);
+        let context = zmq::Context::new();
+        // Initialize publisher
+        let publisher = context.socket(zmq::PUB).map_err(|e| e.to_string())?;
+        publisher.set_linger(1000).map_err(|e| e.to_string())?;
+        publisher
+            .connect(&format!("{}:6000", broker))
+            .map_err(|e| format!("Failed to connect publisher: {}", e))?;
+        // Initialize control channel
+        let identity = channel::node_identity(node_id);
+        let control = ControlChannel::connect(&context, &format!("{}:5556", broker), Some(&identity), ChannelConfig::default())
+            .map_err(|e| format!("Failed to connect control channel: {}", e))?;
+        // Initialize subsciber
+        let subscriber = context.socket(zmq::SUB).map_err(|e| e.to_string())?;
+        subscriber
+            .connect(&format!("{}:5555", broker))
+            .map_err(|e| format!("Failed to connect subscriber: {}", e))?;
+        // Wake up regularly to check the stop flag
+        subscriber.set_rcvtimeo(100).map_err(|e| e.to_string())?;
+        subscriber.set_linger(0).map_err(|e| e.to_string())?;
+        for (t, c) in [
+            (MessageType::CONFIGURATION, MessageCommand::INPUT),
+            (MessageType::DATA, MessageCommand::WRITE),
+        ]
+        .iter()
+        {
+            subscriber
+                .set_subscribe(format!("T{:03}.{:03}", *t as u16, *c as u16).as_bytes())
+                .map_err(|e| format!("Failed to subscribe: {}", e))?;
+        }
+        let stop_subscriber = Arc::new(AtomicBool::new(false));
+        let stop = Arc::clone(&stop_subscriber);
+        let subscriber_thread = thread::spawn(move || {
+            // Message waiting for space in the inbound queue. Meanwhile
+            // nothing is received and the socket queues or drops messages.
+            let mut pending: Option<Vec<u8>> = None;
+            while !stop.load(Ordering::Relaxed) {
+                if let Some(data) = pending.take() {
+                    match tx.try_send(data) {
+                        Ok(()) => (),
+                        Err(std_mpsc::TrySendError::Full(data)) => {
+                            pending = Some(data);
+                            thread::sleep(Duration::from_millis(1));
+                        }
+                        Err(std_mpsc::TrySendError::Disconnected(_)) => break,
+                    }
+                    continue;
+                }
+                // Messages are sent as topic and data frame
+                match subscriber.recv_multipart(0) {
+                    Ok(mut frames) => pending = frames.pop(),
+                    Err(zmq::Error::EAGAIN) => (),
+                    Err(e) => {
+                        warn!("Subscriber failed: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+        info!("Connected to message broker {} ({})", broker, identity);
+        Ok(Self {
+            publisher,
+            control,
+            inbound: rx,
+            stop_subscriber,
+            subscriber_thread,
+            last_heartbeat: Instant::now(),
+        })
+    }
+
+    pub fn publish(&self, m: &Message) {
+        if let Err(e) = self
+            .publisher
+            .send(&m.get_topic(), zmq::SNDMORE)
+            .and_then(|_| self.publisher.send(&m.data, 0))
+        {
+            warn!("Failed to publish {}: {}", m.get_topic(), e);
+        }
+    }
+
+    /// Announces the node to the registry and publishes its state
+    pub fn announce(&mut self, node_info: &NodeInfo, state: &State) {
+        if let Err(e) = self
+            .control
+            .request(channel::BROKER_IDENTITY, node_info.to_message(MessageCommand::ANNOUNCE))
+        {
+            warn!("Failed to announce node: {}", e);
+        }
+        self.publish(&state.to_message(node_info.node_id));
+    }
+
+    /// Leaves the network and closes the sockets
+    pub fn leave(mut self, node_info: &NodeInfo) {
+        if let Err(e) = self
+            .control
+            .notify(channel::BROKER_IDENTITY, &node_info.to_message(MessageCommand::LEAVE))
+        {
+            warn!("Failed to leave network: {}", e);
+        }
+        self.stop_subscriber.store(true, Ordering::Relaxed);
+        if self.subscriber_thread.join().is_err() {
+            warn!("Subscriber thread panicked");
+        }
+        info!("Sockets closed");
+    }
+
+    /// Sends a heartbeat to the registry once per interval
+    pub fn heartbeat(&mut self, node_info: &NodeInfo) {
+        if self.last_heartbeat.elapsed() < network::HEARTBEAT_INTERVAL {
+            return;
+        }
+        self.last_heartbeat = Instant::now();
+        if let Err(e) = self
+            .control
+            .notify(channel::BROKER_IDENTITY, &node_info.to_message(MessageCommand::HEARTBEAT))
+        {
+            warn!("Failed to send heartbeat: {}", e);
+        }
+    }
+
+    /// Retries unacknowledged requests. Returns the ids of the requests
+    /// that failed.
+    pub fn retry(&mut self) -> Vec<u16> {
+        match self.control.retry() {
+            Ok(failed) => {
+                if !failed.is_empty() {
+                    warn!("Requests not acknowledged: {:?}", failed);
+                }
+                failed
+            }
+            Err(e) => {
+                warn!("Control channel error: {}", e);
+                vec![]
+            }
+        }
+    }
+
+    /// Receives an event of the control channel without waiting
+    pub fn poll(&mut self) -> Option<ChannelEvent> {
+        self.control.poll(0).unwrap_or_else(|e| {
+            warn!("Control channel error: {}", e);
+            None
+        })
+    }
+
+    /// Sends a request to the broker
+    pub fn request(&mut self, msg: Message) -> Result<u16, String> {
+        self.control.request(channel::BROKER_IDENTITY, msg).map_err(|e| e.to_string())
+    }
+
+    pub fn reply(&mut self, request: &Request, result: Result<Vec<u8>, String>) {
+        if let Err(e) = self.control.reply(request, result) {
+            warn!("Failed to reply: {}", e);
+        }
+    }
+}
+
+/// Converts a request of the control channel to a command. Parameter
+/// writes are applied directly to report errors to the sender and
+/// recorded with the step.
+pub fn handle_request(
+    executor: &mut PushExecutor,
+    recorder: &mut Recorder,
+    step: u64,
+    msg: &Message,
+) -> Result<Option<RuntimeCommand>, String> {
+    match (msg.get_type(), msg.get_cmd(), msg.get_key()) {
+        (Some(MessageType::CONFIGURATION), Some(MessageCommand::WRITE), Some(MessageKey::C_PARAM)) => {
+            match channel::parse_parameter_write(msg) {
+                Some((name, value)) => executor.set_parameter(&name, &value).map(|_| {
+                    recorder.record(step, session::Action::SetParameter { name, value });
+                    None
+                }),
+                None => Err(format!("Malformed parameter write '{}'", msg.get_payload_string())),
+            }
+        }
+        (Some(MessageType::NETWORK), Some(MessageCommand::PAUSE), _) => Ok(Some(RuntimeCommand::Pause)),
+        (Some(MessageType::NETWORK), Some(MessageCommand::RESUME), _) => Ok(Some(RuntimeCommand::Resume)),
+        (Some(MessageType::NETWORK), Some(MessageCommand::RESET), _) => Ok(Some(RuntimeCommand::Reset)),
+        (Some(MessageType::NETWORK), Some(MessageCommand::SHUTDOWN), _) => {
+            let path = msg.get_payload_string();
+            Ok(Some(RuntimeCommand::Shutdown(if path.is_empty() { None } else { Some(path) })))
+        }
+        _ => Err(format!("Unsupported request {}", msg.get_topic())),
+    }
+}
@@ -0,0 +1,361 @@
+// Checkpoint Module
+// Saves and restores the learned state of a node: the top graph of
+// the graph stack and the literal name bindings. Checkpoints are
+// written as JSON with a version.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::pushr::push::graph::Edge;
+use crate::pushr::push::item::{Item, PushType};
+use crate::pushr::push::state::PushState;
+use crate::pushr::push::vector::IntVector;
+
+/// Version of the checkpoint format, checkpoints of other versions
+/// are rejected
+pub const CHECKPOINT_VERSION: u16 = 1;
+
+/// Prefix of the node state constants of the programs
+const STATE_PREFIX: &str = "STATE_";
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Literal name bindings by name
+    #[serde(with = "literals")]
+    pub bindings: Vec<(String, PushType)>,
+    /// Nodes as (id, state)
+    pub nodes: Vec<(usize, i32)>,
+    /// Edges as (destination id, origin id, weight)
+    pub edges: Vec<(usize, usize, f32)>,
+}
+
+impl Checkpoint {
+    /// Captures the literal name bindings and the top graph of the
+    /// graph stack. Older graphs of the graph stack are not saved.
+    pub fn from_state(push_state: &PushState) -> Self {
+        let mut checkpoint = Checkpoint::default();
+        for (name, item) in push_state.name_bindings.iter() {
+            if let Item::Literal { push_type } = item {
+                match push_type {
+                    PushType::Graph { .. } | PushType::Index { .. } => (),
+                    pt => checkpoint.bindings.push((name.clone(), pt.clone())),
+                }
+            }
+        }
+        checkpoint.bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        if let Some(graph) = push_state.graph_stack.get(0) {
+            for (id, node) in graph.nodes.iter() {
+                checkpoint.nodes.push((*id, node.get_state()));
+            }
+            for (destination, edges) in graph.edges.iter() {
+                for e in edges.iter() {
+                    checkpoint.edges.push((*destination, e.get_origin_id(), e.get_weight()));
+                }
+            }
+            checkpoint.nodes.sort_unstable();
+            checkpoint.edges.sort_by_key(|e| (e.0, e.1));
+        }
+        checkpoint
+    }
+
+    /// Overwrites the bindings and the top graph of the given state
+    /// with the checkpoint. Nodes cannot be created with a given id, so
+    /// nodes missing from the graph get new ids. Edges and int vector
+    /// bindings of node ids, e.g. COLUMN_IDS, are remapped to the new ids.
+    pub fn apply(&self, push_state: &mut PushState) -> Result<(), String> {
+        let graph = match push_state.graph_stack.get_mut(0) {
+            Some(graph) => graph,
+            None => return Err("No graph to restore".to_string()),
+        };
+        let restored: HashSet<usize> = self.nodes.iter().map(|(id, _)| *id).collect();
+        graph.nodes.retain(|id, _| restored.contains(id));
+        let mut remapped = HashMap::new();
+        for (id, state) in self.nodes.iter() {
+            if graph.nodes.contains_key(id) {
+                graph.set_state(id, *state);
+            } else {
+                remapped.insert(*id, graph.add_node(*state));
+            }
+        }
+        let remap = |id: &usize| *remapped.get(id).unwrap_or(id);
+        graph.edges.clear();
+        for (destination, origin, weight) in self.edges.iter() {
+            graph
+                .edges
+                .entry(remap(destination))
+                .or_insert_with(Vec::new)
+                .push(Edge::new(remap(origin), *weight));
+        }
+        for (name, push_type) in self.bindings.iter() {
+            let push_type = match push_type {
+                PushType::IntVector { val } if is_node_ids(&val.values, &restored) && !remapped.is_empty() => {
+                    PushType::IntVector {
+                        val: IntVector::new(val.values.iter().map(|id| remap(&(*id as usize)) as i32).collect()),
+                    }
+                }
+                pt => pt.clone(),
+            };
+            push_state.name_bindings.insert(name.clone(), Item::Literal { push_type });
+        }
+        Ok(())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        fs::write(path, self.to_json())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Checkpoint::parse(&content)
+    }
+
+    /// Encodes the checkpoint as JSON with the format version
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&Payload {
+            version: CHECKPOINT_VERSION,
+            checkpoint: self,
+        })
+        .unwrap_or_default()
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let PayloadVersion { version } =
+            serde_json::from_str(content).map_err(|e| format!("Invalid checkpoint: {}", e))?;
+        if version != CHECKPOINT_VERSION {
+            return Err(format!("Unsupported checkpoint version {}, expected {}", version, CHECKPOINT_VERSION));
+        }
+        let payload: Payload<Self> =
+            serde_json::from_str(content).map_err(|e| format!("Invalid checkpoint: {}", e))?;
+        Ok(payload.checkpoint)
+    }
+
+    /// Describes the graph in the DOT language of Graphviz. Nodes are
+    /// labeled with their state, named after the `STATE_*` bindings of
+    /// the program, edges with their weight.
+    pub fn to_dot(&self) -> String {
+        let state_name = |state: i32| {
+            self.bindings
+                .iter()
+                .find(|(name, push_type)| {
+                    name.starts_with(STATE_PREFIX) && *push_type == PushType::Int { val: state }
+                })
+                .map(|(name, _)| name.trim_start_matches(STATE_PREFIX).to_string())
+                .unwrap_or_else(|| state.to_string())
+        };
+        let mut dot = String::from("digraph memory {\n");
+        for (id, state) in self.nodes.iter() {
+            dot.push_str(&format!("  {} [label=\"{}\\n{}\"];\n", id, id, state_name(*state)));
+        }
+        for (destination, origin, weight) in self.edges.iter() {
+            dot.push_str(&format!("  {} -> {} [label=\"{}\"];\n", origin, destination, weight));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Restores the checkpoint file, failures are logged. Replicas remove
+/// their temporary checkpoint once it is read.
+pub fn restore(path: &str, remove: bool, push_state: &mut PushState) {
+    match Checkpoint::load(path).and_then(|c| c.apply(push_state)) {
+        Ok(()) => info!("Restored checkpoint {}", path),
+        Err(e) => warn!("Failed to restore checkpoint {} ({})", path, e),
+    }
+    if remove {
+        if let Err(e) = fs::remove_file(path) {
+            warn!("Failed to remove checkpoint {} ({})", path, e);
+        }
+    }
+}
+
+/// Saves a checkpoint of the state, failures are logged
+pub fn save(path: &str, push_state: &PushState) {
+    match Checkpoint::from_state(push_state).save(path) {
+        Ok(()) => info!("Saved checkpoint to {}", path),
+        Err(e) => warn!("Failed to save checkpoint to {} ({})", path, e),
+    }
+}
+
+/// Int vectors of node ids of the checkpoint are bindings of nodes
+fn is_node_ids(values: &[i32], ids: &HashSet<usize>) -> bool {
+    !values.is_empty() && values.iter().all(|v| *v > 0 && ids.contains(&(*v as usize)))
+}
+
+/// Checkpoint file
+#[derive(Serialize, Deserialize)]
+struct Payload<T> {
+    version: u16,
+    checkpoint: T,
+}
+
+/// Version of a checkpoint file, read before the checkpoint
+#[derive(Deserialize)]
+struct PayloadVersion {
+    version: u16,
+}
+
+/// Serializes the literal bindings, other push types are skipped
+mod literals {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::pushr::push::item::PushType;
+    use crate::pushr::push::vector::{BoolVector, FloatVector, IntVector};
+
+    #[derive(Serialize, Deserialize)]
+    enum Literal {
+        Bool(bool),
+        Int(i32),
+        Float(f32),
+        BoolVector(Vec<bool>),
+        IntVector(Vec<i32>),
+        FloatVector(Vec<f32>),
+    }
+
+    pub fn serialize<S: Serializer>(bindings: &[(String, PushType)], serializer: S) -> Result<S::Ok, S::Error> {
+        let literals: Vec<(&String, Literal)> = bindings
+            .iter()
+            .filter_map(|(name, push_type)| {
+                let literal = match push_type {
+                    PushType::Bool { val } => Literal::Bool(*val),
+                    PushType::Int { val } => Literal::Int(*val),
+                    PushType::Float { val } => Literal::Float(*val),
+                    PushType::BoolVector { val } => Literal::BoolVector(val.values.clone()),
+                    PushType::IntVector { val } => Literal::IntVector(val.values.clone()),
+                    PushType::FloatVector { val } => Literal::FloatVector(val.values.clone()),
+                    _ => return None,
+                };
+                Some((name, literal))
+            })
+            .collect();
+        literals.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(String, PushType)>, D::Error> {
+        let literals = Vec::<(String, Literal)>::deserialize(deserializer)?;
+        Ok(literals
+            .into_iter()
+            .map(|(name, literal)| {
+                let push_type = match literal {
+                    Literal::Bool(val) => PushType::Bool { val },
+                    Literal::Int(val) => PushType::Int { val },
+                    Literal::Float(val) => PushType::Float { val },
+                    Literal::BoolVector(v) => PushType::BoolVector { val: BoolVector::new(v) },
+                    Literal::IntVector(v) => PushType::IntVector { val: IntVector::new(v) },
+                    Literal::FloatVector(v) => PushType::FloatVector { val: FloatVector::new(v) },
+                };
+                (name, push_type)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pushr::push::graph::Graph;
+
+    fn test_state() -> PushState {
+        let mut push_state = PushState::new();
+        let mut graph = Graph::new();
+        let n1 = graph.add_node(1);
+        let n2 = graph.add_node(5);
+        graph.add_edge(n1, n2, 0.123_456);
+        push_state.graph_stack.push(graph);
+        push_state.name_bindings.insert("COUNTER".to_string(), Item::int(3));
+        push_state.name_bindings.insert("PERM".to_string(), Item::float(0.015));
+        push_state
+            .name_bindings
+            .insert("IDS".to_string(), Item::intvec(IntVector::new(vec![n1 as i32, n2 as i32])));
+        push_state.name_bindings.insert("EMPTY".to_string(), Item::intvec(IntVector::new(vec![])));
+        push_state
+            .name_bindings
+            .insert("SUBROUTINE".to_string(), Item::instruction("INTEGER.DUP".to_string()));
+        push_state
+    }
+
+    #[test]
+    fn checkpoint_json_roundtrip() {
+        let checkpoint = Checkpoint::from_state(&test_state());
+        assert_eq!(checkpoint.bindings.len(), 4, "Only literals are saved");
+        assert_eq!(checkpoint.nodes.len(), 2);
+        assert_eq!(checkpoint.edges.len(), 1);
+        assert_eq!(Checkpoint::parse(&checkpoint.to_json()), Ok(checkpoint));
+    }
+
+    #[test]
+    fn checkpoint_restores_graph_and_bindings() {
+        let mut push_state = test_state();
+        let checkpoint = Checkpoint::from_state(&push_state);
+        let graph = push_state.graph_stack.get_mut(0).unwrap();
+        for (id, _) in checkpoint.nodes.iter() {
+            graph.set_state(id, 0);
+        }
+        graph.edges.clear();
+        push_state.name_bindings.insert("COUNTER".to_string(), Item::int(0));
+        assert!(checkpoint.apply(&mut push_state).is_ok());
+        assert_eq!(Checkpoint::from_state(&push_state), checkpoint);
+    }
+
+    #[test]
+    fn checkpoint_remaps_missing_nodes() {
+        let checkpoint = Checkpoint::from_state(&test_state());
+        let (n1, n2) = (checkpoint.nodes[0].0, checkpoint.nodes[1].0);
+        // The graph of another process has other node ids
+        let mut push_state = PushState::new();
+        let mut graph = Graph::new();
+        let other = graph.add_node(0);
+        graph.add_node(0);
+        push_state.graph_stack.push(graph);
+        assert!(checkpoint.apply(&mut push_state).is_ok());
+
+        let restored = Checkpoint::from_state(&push_state);
+        let (m1, m2) = (restored.nodes[0].0, restored.nodes[1].0);
+        assert!(m1 > other && m2 > other, "Nodes get new ids");
+        assert_eq!(restored.nodes, vec![(m1, 1), (m2, 5)]);
+        assert_eq!(restored.edges, vec![(m2, m1, 0.123_456)]);
+        let binding = |name: &str| restored.bindings.iter().find(|(n, _)| n == name).map(|(_, pt)| pt.clone());
+        assert_eq!(
+            binding("IDS"),
+            Some(PushType::IntVector { val: IntVector::new(vec![m1 as i32, m2 as i32]) })
+        );
+        assert_eq!(binding("COUNTER"), Some(PushType::Int { val: 3 }));
+        assert!(n1 != m1 && n2 != m2);
+    }
+
+    #[test]
+    fn graph_is_exported_as_dot() {
+        let mut push_state = test_state();
+        push_state.name_bindings.insert("STATE_ACTIVE".to_string(), Item::int(5));
+        let checkpoint = Checkpoint::from_state(&push_state);
+        let (n1, n2) = (checkpoint.nodes[0].0, checkpoint.nodes[1].0);
+        let dot = checkpoint.to_dot();
+        assert!(dot.starts_with("digraph memory {"));
+        assert!(dot.contains(&format!("{} [label=\"{}\\n1\"];", n1, n1)));
+        assert!(dot.contains(&format!("{} [label=\"{}\\nACTIVE\"];", n2, n2)));
+        assert!(dot.contains(&format!("{} -> {} [label=\"0.123456\"];", n1, n2)));
+    }
+
+    #[test]
+    fn checkpoint_rejects_malformed_content() {
+        assert!(Checkpoint::parse("NODE 1 5").is_err());
+        assert!(Checkpoint::parse(r#"{"version":1,"checkpoint":{"bindings":[["A",{"Int":"x"}]],"nodes":[],"edges":[]}}"#).is_err());
+        assert!(Checkpoint::parse(r#"{"version":1}"#).is_err());
+    }
+
+    #[test]
+    fn checkpoint_rejects_other_versions() {
+        let content = serde_json::to_string(&Payload {
+            version: CHECKPOINT_VERSION + 1,
+            checkpoint: &Checkpoint::default(),
+        })
+        .unwrap();
+        assert_eq!(
+            Checkpoint::parse(&content),
+            Err(format!("Unsupported checkpoint version {}, expected {}", CHECKPOINT_VERSION + 1, CHECKPOINT_VERSION))
+        );
+    }
+}
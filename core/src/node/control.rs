@@ -2,43 +2,266 @@
 // Control of different stages of the node lifecycle
 //
 
-#[derive(Debug, PartialEq)]
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::node::message::Message;
+use crate::num_traits::{FromPrimitive, ToPrimitive};
+use crate::shared::msg::{MessageCommand, MessageKey, MessageType, NodeState, PAYLOAD_OFFSET};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum State {
-    Waiting { waiting_time: usize },
-    Computing,
-    Replicating,
+    /// Program runs up to the end of the graph creation (BP4 for the
+    /// spatial pooler, BP1 otherwise)
+    Initializing,
+    Ready { learning: bool },
+    Computing { learning: bool },
+    Replicating { learning: bool },
+    /// Outbound messages are flushed before shutdown
+    Draining,
     Done,
     Failure(String),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Event {
     NothingHappened,
-    TemporalMemoryInitialized,
+    /// Memory graph is built, learning as configured by the program
+    GraphInitialized { learning: bool },
+    InputReceived,
+    InputProcessed,
+    LearningChanged(bool),
+    ReplicationRequested,
+    ReplicationFinished,
+    /// Learned state is discarded and the program reloaded
+    Reset,
+    ShutdownRequested,
+    Drained,
+    Failed(String),
 }
 
 impl State {
     pub fn next(self, event: Event) -> State {
         match (self, event) {
-            (State::Waiting { waiting_time }, Event::NothingHappened) => State::Waiting {
-                waiting_time: waiting_time + 1,
-            },
-            (s, e) => State::Failure(
-                format!("Wrong state, event combintation: {:#?} {:#?}", s, e).to_string(),
-            ),
+            (s, Event::NothingHappened) => s,
+            // Terminal states
+            (State::Done, _) => State::Done,
+            (State::Failure(reason), _) => State::Failure(reason),
+            (_, Event::Failed(reason)) => State::Failure(reason),
+            (State::Initializing, Event::GraphInitialized { learning }) => State::Ready { learning },
+            (State::Ready { learning }, Event::InputReceived) => State::Computing { learning },
+            (State::Computing { learning }, Event::InputReceived) => State::Computing { learning },
+            (State::Computing { learning }, Event::InputProcessed) => State::Ready { learning },
+            (State::Ready { .. }, Event::LearningChanged(learning)) => State::Ready { learning },
+            (State::Computing { .. }, Event::LearningChanged(learning)) => State::Computing { learning },
+            (State::Ready { learning }, Event::ReplicationRequested)
+            | (State::Computing { learning }, Event::ReplicationRequested) => State::Replicating { learning },
+            (State::Replicating { learning }, Event::ReplicationFinished) => State::Ready { learning },
+            // Inputs are processed while replicating
+            (State::Replicating { learning }, Event::InputReceived)
+            | (State::Replicating { learning }, Event::InputProcessed) => State::Replicating { learning },
+            (State::Replicating { .. }, Event::LearningChanged(learning)) => State::Replicating { learning },
+            (State::Ready { .. }, Event::Reset)
+            | (State::Computing { .. }, Event::Reset)
+            | (State::Replicating { .. }, Event::Reset) => State::Initializing,
+            (_, Event::ShutdownRequested) => State::Draining,
+            (State::Draining, Event::Drained) => State::Done,
+            // Events without effect in the state. Learning changes while
+            // initializing are read with the created graph.
+            (State::Initializing, Event::LearningChanged(_)) | (State::Initializing, Event::Reset) => State::Initializing,
+            (State::Replicating { learning }, Event::ReplicationRequested) => State::Replicating { learning },
+            (State::Draining, Event::InputReceived)
+            | (State::Draining, Event::InputProcessed)
+            | (State::Draining, Event::LearningChanged(_)) => State::Draining,
+            (s, e) => State::Failure(format!("Wrong state, event combination: {:?} {:?}", s, e)),
         }
     }
 
-    pub fn run(&self) {
-        match *self {
-            State::Waiting { waiting_time } => {
-                println!("We waited for {}", waiting_time);
-            }
-            State::Computing {} => {
-                // Compute update on input
+    pub fn node_state(&self) -> NodeState {
+        match self {
+            State::Initializing => NodeState::INITIALIZING,
+            State::Ready { .. } => NodeState::READY,
+            State::Computing { .. } => NodeState::COMPUTING,
+            State::Replicating { .. } => NodeState::REPLICATING,
+            State::Draining => NodeState::DRAINING,
+            State::Done => NodeState::DONE,
+            State::Failure(_) => NodeState::FAILED,
+        }
+    }
+
+    pub fn is_learning(&self) -> bool {
+        match self {
+            State::Ready { learning } | State::Computing { learning } | State::Replicating { learning } => *learning,
+            _ => false,
+        }
+    }
+
+    /// Creates the state change message published on the bus. Payload
+    /// (big endian): node id (u16), state (u16), learning (u16) and the
+    /// failure reason as string.
+    pub fn to_message(&self, node_id: u16) -> Message {
+        let mut m = Message {
+            data: vec![0; PAYLOAD_OFFSET],
+        };
+        m.create_header(MessageType::NETWORK, MessageCommand::STATE, MessageKey::UNDEFINED);
+        let mut payload = vec![];
+        payload.extend_from_slice(&node_id.to_be_bytes());
+        payload.extend_from_slice(&self.node_state().to_u16().unwrap_or(0).to_be_bytes());
+        payload.extend_from_slice(&(self.is_learning() as u16).to_be_bytes());
+        if let State::Failure(reason) = self {
+            payload.extend_from_slice(reason.as_bytes());
+        }
+        m.set_payload(&mut payload);
+        m
+    }
+
+    /// Reads node id and state from a state change message
+    pub fn from_message(msg: &Message) -> Option<(u16, State)> {
+        let payload = msg.data.get(PAYLOAD_OFFSET..)?;
+        let read_u16 = |pos: usize| -> Option<u16> {
+            payload.get(pos..pos + 2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+        };
+        let node_id = read_u16(0)?;
+        let learning = read_u16(4)? != 0;
+        let state = match NodeState::from_u16(read_u16(2)?)? {
+            NodeState::INITIALIZING => State::Initializing,
+            NodeState::READY => State::Ready { learning },
+            NodeState::COMPUTING => State::Computing { learning },
+            NodeState::REPLICATING => State::Replicating { learning },
+            NodeState::DRAINING => State::Draining,
+            NodeState::DONE => State::Done,
+            NodeState::FAILED => {
+                let reason = &payload[6..];
+                let end = reason.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+                State::Failure(String::from_utf8_lossy(&reason[..end]).to_string())
             }
-            State::Replicating {} => {}
-            State::Done | State::Failure(_) => {}
+        };
+        Some((node_id, state))
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            State::Failure(reason) => write!(f, "{:?} ({})", self.node_state(), reason),
+            State::Ready { learning } | State::Computing { learning } | State::Replicating { learning } => write!(
+                f,
+                "{:?} (learning {})",
+                self.node_state(),
+                if *learning { "on" } else { "off" }
+            ),
+            _ => write!(f, "{:?}", self.node_state()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(events: Vec<Event>) -> State {
+        events.into_iter().fold(State::Initializing, |s, e| s.next(e))
+    }
+
+    #[test]
+    fn lifecycle_runs_to_done() {
+        let mut state = State::Initializing.next(Event::GraphInitialized { learning: true });
+        assert_eq!(state, State::Ready { learning: true });
+        state = state.next(Event::InputReceived);
+        assert_eq!(state, State::Computing { learning: true });
+        state = state.next(Event::LearningChanged(false));
+        assert_eq!(state, State::Computing { learning: false });
+        state = state.next(Event::InputProcessed);
+        assert_eq!(state, State::Ready { learning: false });
+        state = state.next(Event::ReplicationRequested);
+        assert_eq!(state, State::Replicating { learning: false });
+        state = state.next(Event::InputReceived);
+        assert_eq!(state, State::Replicating { learning: false });
+        state = state.next(Event::ReplicationFinished);
+        assert_eq!(state, State::Ready { learning: false });
+        state = state.next(Event::ShutdownRequested);
+        assert_eq!(state, State::Draining);
+        assert_eq!(state.next(Event::Drained), State::Done);
+    }
+
+    #[test]
+    fn reset_reinitializes_graph() {
+        let state = run(vec![
+            Event::GraphInitialized { learning: true },
+            Event::LearningChanged(false),
+            Event::Reset,
+        ]);
+        assert_eq!(state, State::Initializing);
+        assert_eq!(state.next(Event::GraphInitialized { learning: true }), State::Ready { learning: true });
+    }
+
+    #[test]
+    fn wrong_event_fails_and_failure_is_terminal() {
+        for (state, event) in [
+            (State::Initializing, Event::InputReceived),
+            (State::Initializing, Event::ReplicationRequested),
+            (State::Ready { learning: true }, Event::ReplicationFinished),
+            (State::Draining, Event::Reset),
+        ] {
+            assert_eq!(state.next(event).node_state(), NodeState::FAILED);
+        }
+        let state = run(vec![Event::Failed("Broken".to_string()), Event::ShutdownRequested, Event::Drained]);
+        assert_eq!(state, State::Failure("Broken".to_string()));
+        assert_eq!(State::Done.next(Event::GraphInitialized { learning: true }), State::Done);
+    }
+
+    #[test]
+    fn valid_transitions() {
+        let ready = State::Ready { learning: true };
+        let computing = State::Computing { learning: true };
+        let replicating = State::Replicating { learning: true };
+        let transitions = vec![
+            (State::Initializing, Event::GraphInitialized { learning: false }, State::Ready { learning: false }),
+            (State::Initializing, Event::LearningChanged(true), State::Initializing),
+            (State::Initializing, Event::Reset, State::Initializing),
+            (State::Initializing, Event::ShutdownRequested, State::Draining),
+            (ready.clone(), Event::InputReceived, computing.clone()),
+            (ready.clone(), Event::LearningChanged(false), State::Ready { learning: false }),
+            (ready.clone(), Event::ReplicationRequested, replicating.clone()),
+            (ready.clone(), Event::Reset, State::Initializing),
+            (ready.clone(), Event::ShutdownRequested, State::Draining),
+            (computing.clone(), Event::InputReceived, computing.clone()),
+            (computing.clone(), Event::InputProcessed, ready.clone()),
+            (computing.clone(), Event::LearningChanged(false), State::Computing { learning: false }),
+            (computing.clone(), Event::ReplicationRequested, replicating.clone()),
+            (computing.clone(), Event::Reset, State::Initializing),
+            (computing.clone(), Event::ShutdownRequested, State::Draining),
+            (replicating.clone(), Event::InputReceived, replicating.clone()),
+            (replicating.clone(), Event::InputProcessed, replicating.clone()),
+            (replicating.clone(), Event::LearningChanged(false), State::Replicating { learning: false }),
+            (replicating.clone(), Event::ReplicationRequested, replicating.clone()),
+            (replicating.clone(), Event::ReplicationFinished, ready.clone()),
+            (replicating.clone(), Event::Reset, State::Initializing),
+            (replicating.clone(), Event::ShutdownRequested, State::Draining),
+            (State::Draining, Event::InputReceived, State::Draining),
+            (State::Draining, Event::InputProcessed, State::Draining),
+            (State::Draining, Event::LearningChanged(false), State::Draining),
+            (State::Draining, Event::ShutdownRequested, State::Draining),
+            (State::Draining, Event::Drained, State::Done),
+        ];
+        for (state, event, next) in transitions {
+            assert_eq!(state.clone().next(event.clone()), next, "{:?} {:?}", state, event);
+            assert_eq!(state.clone().next(Event::NothingHappened), state);
+        }
+    }
+
+    #[test]
+    fn state_message_roundtrip() {
+        for state in [
+            State::Initializing,
+            State::Computing { learning: true },
+            State::Ready { learning: false },
+            State::Failure("Empty execution stack".to_string()),
+        ].iter() {
+            let m = state.to_message(42);
+            assert_eq!(m.get_cmd(), Some(MessageCommand::STATE));
+            assert_eq!(State::from_message(&m), Some((42, state.clone())));
         }
     }
 }
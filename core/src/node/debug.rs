@@ -0,0 +1,347 @@
+// Debug Module
+// Inspection protocol of a running node. A node started with a debug
+// endpoint answers DEBUG requests on a REP socket with its snapshot
+// and accepts step, pause and continue commands. Front ends attach
+// to the endpoint with a DebugClient. Breakpoints pause the runtime at
+// source lines and instructions.
+
+use std::collections::BTreeSet;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+
+use crate::node::control::State;
+use crate::node::listing::{self, Listing};
+use crate::node::message::Message;
+use crate::node::runtime::{RuntimeCommand, RuntimeHandle};
+use crate::node::snapshot::Snapshot;
+use crate::pushr::push::state::PushState;
+use crate::shared::msg::{MessageCommand, MessageKey, MessageType, CMD_OFFSET, PAYLOAD_OFFSET};
+
+/// Time to wait for the reply of the node
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Interval between two snapshot requests of an attached front end
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Creates a debug request, READ asks for the snapshot
+pub fn request(cmd: MessageCommand) -> Message {
+    let mut m = Message {
+        data: vec![0; PAYLOAD_OFFSET],
+    };
+    m.create_header(MessageType::DEBUG, cmd, MessageKey::UNDEFINED);
+    m
+}
+
+/// Creates a request that executes steps while the node is paused
+pub fn step_request(steps: u32) -> Message {
+    let mut m = request(MessageCommand::STEP);
+    m.set_payload(&mut steps.to_be_bytes().to_vec());
+    m
+}
+
+/// Converts a debug request to the command of the runtime. Snapshot
+/// requests have no command.
+pub fn parse_request(msg: &Message) -> Result<Option<RuntimeCommand>, String> {
+    if msg.get_type() != Some(MessageType::DEBUG) {
+        return Err(format!("Unsupported request {}", msg.get_topic()));
+    }
+    match msg.get_cmd() {
+        Some(MessageCommand::READ) => Ok(None),
+        Some(MessageCommand::PAUSE) => Ok(Some(RuntimeCommand::Pause)),
+        Some(MessageCommand::RESUME) => Ok(Some(RuntimeCommand::Resume)),
+        Some(MessageCommand::STEP) => match msg.data.get(PAYLOAD_OFFSET..PAYLOAD_OFFSET + 4) {
+            Some(b) => Ok(Some(RuntimeCommand::Step(u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize))),
+            None => Err("Step request without number of steps".to_string()),
+        },
+        _ => Err(format!("Unsupported debug request {}", msg.get_topic())),
+    }
+}
+
+/// Node side of the protocol
+pub struct DebugServer {
+    socket: zmq::Socket,
+}
+
+impl DebugServer {
+    pub fn bind(endpoint: &str) -> Result<Self, String> {
+        let socket = zmq::Context::new().socket(zmq::REP).map_err(|e| e.to_string())?;
+        socket.set_linger(0).map_err(|e| e.to_string())?;
+        socket
+            .bind(endpoint)
+            .map_err(|e| format!("Failed to bind debug endpoint {}: {}", endpoint, e))?;
+        info!("Debug endpoint {}", endpoint);
+        Ok(Self { socket })
+    }
+
+    /// Receives a request without waiting. Every request must be
+    /// answered before the next one is received.
+    pub fn poll(&self) -> Option<Message> {
+        match self.socket.recv_bytes(zmq::DONTWAIT) {
+            Ok(data) => Some(Message { data }),
+            Err(zmq::Error::EAGAIN) => None,
+            Err(e) => {
+                warn!("Debug endpoint failed: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Answers a request with the snapshot or the reason of a failure
+    pub fn reply(&self, request: &Message, result: Result<&Snapshot, String>) {
+        let mut m = Message {
+            data: request.data.get(..PAYLOAD_OFFSET).unwrap_or(&[0; PAYLOAD_OFFSET]).to_vec(),
+        };
+        match result {
+            Ok(snapshot) => {
+                m.set_cmd(MessageCommand::ACK);
+                m.set_payload(&mut snapshot.to_payload());
+            }
+            Err(reason) => {
+                m.set_cmd(MessageCommand::NACK);
+                m.set_payload(&mut reason.into_bytes());
+            }
+        }
+        if let Err(e) = self.socket.send(&m.data, 0) {
+            warn!("Failed to reply to debug request: {}", e);
+        }
+    }
+}
+
+/// Source lines and instructions at which the program pauses
+#[derive(Debug)]
+pub struct Breakpoints {
+    /// Source of the program
+    listing: Listing,
+    /// Source position of the next instruction, updated while line
+    /// breakpoints are set
+    position: Option<usize>,
+    lines: BTreeSet<usize>,
+    instructions: BTreeSet<String>,
+}
+
+impl Breakpoints {
+    pub fn new(program: &str) -> Self {
+        Self {
+            listing: Listing::new(program),
+            position: None,
+            lines: BTreeSet::new(),
+            instructions: BTreeSet::new(),
+        }
+    }
+
+    /// Replaces the program, its line breakpoints are cleared
+    pub fn load(&mut self, program: &str) {
+        self.listing = Listing::new(program);
+        self.lines.clear();
+        self.position = None;
+    }
+
+    /// Forgets the source position when the program restarts
+    pub fn restart(&mut self) {
+        self.position = None;
+    }
+
+    pub fn toggle_line(&mut self, line: usize) {
+        if !self.lines.remove(&line) {
+            self.lines.insert(line);
+        }
+    }
+
+    pub fn toggle_instruction(&mut self, name: String) {
+        if self.instructions.remove(&name) {
+            info!("Breakpoint at {} cleared", name);
+        } else {
+            info!("Breakpoint at {} set", name);
+            self.instructions.insert(name);
+        }
+    }
+
+    /// Checks the next instruction for an instruction breakpoint
+    pub fn at_instruction(&self, next: &str) -> bool {
+        self.instructions.contains(next)
+    }
+
+    /// Updates the source position of the next instruction. Returns the
+    /// line if it moved to a line with a breakpoint.
+    pub fn at_line(&mut self, push_state: &PushState) -> Option<usize> {
+        if self.lines.is_empty() {
+            return None;
+        }
+        let previous = self.line();
+        self.position = self.listing.locate(&listing::exec_tokens(push_state), self.position);
+        match self.line() {
+            Some(line) if Some(line) != previous && self.lines.contains(&line) => Some(line),
+            _ => None,
+        }
+    }
+
+    fn line(&self) -> Option<usize> {
+        self.position.and_then(|p| self.listing.line(p))
+    }
+
+    /// Source line of the next instruction
+    pub fn locate(&self, push_state: &PushState) -> Option<usize> {
+        self.listing
+            .locate(&listing::exec_tokens(push_state), self.position)
+            .and_then(|p| self.listing.line(p))
+    }
+
+    pub fn lines(&self) -> Vec<usize> {
+        self.lines.iter().copied().collect()
+    }
+
+    pub fn instructions(&self) -> Vec<String> {
+        self.instructions.iter().cloned().collect()
+    }
+}
+
+/// Front end side of the protocol
+pub struct DebugClient {
+    endpoint: String,
+    context: zmq::Context,
+    socket: zmq::Socket,
+}
+
+impl DebugClient {
+    pub fn connect(endpoint: &str) -> Result<Self, String> {
+        let context = zmq::Context::new();
+        let socket = Self::socket(&context, endpoint)?;
+        Ok(Self {
+            endpoint: endpoint.to_string(),
+            context,
+            socket,
+        })
+    }
+
+    fn socket(context: &zmq::Context, endpoint: &str) -> Result<zmq::Socket, String> {
+        let socket = context.socket(zmq::REQ).map_err(|e| e.to_string())?;
+        socket.set_linger(0).map_err(|e| e.to_string())?;
+        socket
+            .set_rcvtimeo(REPLY_TIMEOUT.as_millis() as i32)
+            .map_err(|e| e.to_string())?;
+        socket
+            .connect(endpoint)
+            .map_err(|e| format!("Failed to connect to {}: {}", endpoint, e))?;
+        Ok(socket)
+    }
+
+    /// Sends a request and returns the snapshot of the reply. Without
+    /// reply the socket is replaced, a REQ socket can not send again
+    /// before it received the reply.
+    pub fn request(&mut self, msg: &Message) -> Result<Snapshot, String> {
+        let reply = self
+            .socket
+            .send(&msg.data, 0)
+            .and_then(|_| self.socket.recv_bytes(0))
+            .map(|data| Message { data });
+        match reply {
+            Ok(reply) if reply.data.len() >= PAYLOAD_OFFSET && reply.get_prop(&CMD_OFFSET) == MessageCommand::ACK as u16 => {
+                Snapshot::from_payload(&reply.data[PAYLOAD_OFFSET..])
+            }
+            Ok(reply) => Err(format!("Request rejected: {}", reply.get_payload_string())),
+            Err(e) => {
+                self.socket = Self::socket(&self.context, &self.endpoint)?;
+                Err(format!("Node at {} not responding ({})", self.endpoint, e))
+            }
+        }
+    }
+
+    pub fn snapshot(&mut self) -> Result<Snapshot, String> {
+        self.request(&request(MessageCommand::READ))
+    }
+}
+
+/// Attaches a front end to the node at the endpoint. The returned
+/// handle works like the handle of a local runtime: step, pause and
+/// continue are forwarded, snapshots are polled. Shutdown detaches
+/// without stopping the node. The task returns the last known state.
+pub fn attach(endpoint: &str) -> Result<(RuntimeHandle, JoinHandle<State>), String> {
+    let mut client = DebugClient::connect(endpoint)?;
+    let snapshot = client.snapshot()?;
+    info!("Attached to node {} at {}", snapshot.node_id, endpoint);
+    let (command_tx, mut command_rx) = mpsc::channel(32);
+    let (snapshot_tx, snapshot_rx) = watch::channel(snapshot);
+    // The client blocks until the node replies
+    let task = tokio::task::spawn_blocking(move || {
+        let mut last_poll = Instant::now();
+        loop {
+            let msg = match command_rx.try_recv() {
+                Ok(RuntimeCommand::Step(n)) => step_request(n.min(u32::MAX as usize) as u32),
+                Ok(RuntimeCommand::Pause) => request(MessageCommand::PAUSE),
+                Ok(RuntimeCommand::Resume) => request(MessageCommand::RESUME),
+                Ok(RuntimeCommand::Shutdown(_)) | Err(mpsc::error::TryRecvError::Disconnected) => break,
+                Ok(command) => {
+                    warn!("{:?} is not supported by attached nodes", command);
+                    continue;
+                }
+                Err(mpsc::error::TryRecvError::Empty) if last_poll.elapsed() >= POLL_INTERVAL => {
+                    request(MessageCommand::READ)
+                }
+                Err(mpsc::error::TryRecvError::Empty) => {
+                    thread::sleep(Duration::from_millis(10));
+                    continue;
+                }
+            };
+            last_poll = Instant::now();
+            match client.request(&msg) {
+                Ok(snapshot) => {
+                    snapshot_tx.send_replace(snapshot);
+                }
+                Err(e) => warn!("{}", e),
+            }
+        }
+        info!("Detached from {}", client.endpoint);
+        let state = snapshot_tx.borrow().state.clone();
+        state
+    });
+    let handle = RuntimeHandle {
+        commands: command_tx,
+        snapshots: snapshot_rx,
+    };
+    Ok((handle, task))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_requests_are_parsed() {
+        assert!(matches!(parse_request(&request(MessageCommand::READ)), Ok(None)));
+        assert!(matches!(parse_request(&step_request(1000)), Ok(Some(RuntimeCommand::Step(1000)))));
+        assert!(matches!(parse_request(&request(MessageCommand::RESUME)), Ok(Some(RuntimeCommand::Resume))));
+        assert!(parse_request(&request(MessageCommand::STEP)).is_err());
+        assert!(parse_request(&request(MessageCommand::SHUTDOWN)).is_err());
+    }
+
+    #[test]
+    fn client_receives_snapshot() {
+        let server = DebugServer::bind("tcp://127.0.0.1:*").unwrap();
+        let endpoint = server.socket.get_last_endpoint().unwrap().unwrap();
+        let snapshot = Snapshot {
+            node_id: 4,
+            paused: true,
+            ..Snapshot::default()
+        };
+        let server_thread = thread::spawn(move || {
+            let start = Instant::now();
+            while start.elapsed() < REPLY_TIMEOUT {
+                if let Some(request) = server.poll() {
+                    let result = parse_request(&request).map(|_| &snapshot);
+                    server.reply(&request, result);
+                    return;
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+        });
+        let mut client = DebugClient::connect(&endpoint).unwrap();
+        assert_eq!(client.request(&step_request(3)).unwrap().node_id, 4);
+        server_thread.join().unwrap();
+        // Without server the request times out and the client recovers
+        assert!(client.snapshot().is_err());
+    }
+}
@@ -0,0 +1,234 @@
+// Encoder Module
+// Encodes raw values as sparse distributed representations (SDR)
+
+use time::Date;
+
+/// Fraction of active bits of the encoded inputs
+pub const SPARSITY: f32 = 0.1;
+
+/// Encodes a scalar as a block of contiguous active bits whose
+/// position is proportional to the value. Values outside of the
+/// range are clipped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScalarEncoder {
+    pub min: f32,
+    pub max: f32,
+    /// Number of bits of the SDR
+    pub size: usize,
+    /// Number of active bits
+    pub active: usize,
+}
+
+impl ScalarEncoder {
+    pub fn new(min: f32, max: f32, size: usize, active: usize) -> Result<Self, String> {
+        if min >= max || min.is_nan() || max.is_nan() {
+            return Err(format!("Invalid range [{}, {}]", min, max));
+        }
+        if active == 0 || active > size {
+            return Err(format!("Invalid number of active bits {} for size {}", active, size));
+        }
+        Ok(Self { min, max, size, active })
+    }
+
+    /// Creates an encoder for the range of the values
+    pub fn for_values(values: &[f32], size: usize, active: usize) -> Result<Self, String> {
+        let min = values.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        if min == max {
+            return ScalarEncoder::new(min, min + 1.0, size, active);
+        }
+        ScalarEncoder::new(min, max, size, active)
+    }
+
+    pub fn encode(&self, value: f32) -> Vec<bool> {
+        let buckets = (self.size - self.active) as f32;
+        let ratio = ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0);
+        let start = (ratio * buckets).round() as usize;
+        (0..self.size)
+            .map(|i| i >= start && i < start + self.active)
+            .collect()
+    }
+}
+
+/// Active bits of a block of the encoded inputs
+fn active_bits(size: usize, min: usize) -> usize {
+    ((size as f32 * SPARSITY).round() as usize).max(min).min(size)
+}
+
+/// Block of contiguous active bits whose position is proportional to
+/// the value, the block wraps around at the end of the period
+fn periodic(value: f32, period: f32, size: usize, active: usize) -> Vec<bool> {
+    let start = ((value / period).rem_euclid(1.0) * size as f32).round() as usize % size;
+    (0..size).map(|i| (i + size - start) % size < active).collect()
+}
+
+/// Encodes the day of week and the time of day of a timestamp as two
+/// periodic blocks, the first half of the bits for the day and the
+/// second half for the time. Monday follows Sunday and midnight
+/// follows 23:59.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DateEncoder {
+    /// Number of bits of the SDR
+    pub size: usize,
+    /// Number of active bits of each half
+    pub active: usize,
+}
+
+impl DateEncoder {
+    pub fn new(size: usize, active: usize) -> Result<Self, String> {
+        if active == 0 || active > size / 2 {
+            return Err(format!("Invalid number of active bits {} for size {}", active, size));
+        }
+        Ok(Self { size, active })
+    }
+
+    pub fn encode(&self, timestamp: &str) -> Result<Vec<bool>, String> {
+        let (weekday, hours) = parse_timestamp(timestamp)?;
+        let half = self.size / 2;
+        let mut sdr = periodic(weekday as f32, 7.0, half, self.active);
+        sdr.extend(periodic(hours, 24.0, self.size - half, self.active));
+        Ok(sdr)
+    }
+}
+
+/// Day of week (0 is Monday) and hours since midnight of a timestamp
+/// like `7/2/10 0:00` (month/day/year as in the NuPIC datasets) or
+/// `2010-07-02 00:00`, the time is optional
+pub fn parse_timestamp(timestamp: &str) -> Result<(u8, f32), String> {
+    let invalid = || format!("Invalid timestamp {}", timestamp);
+    let mut parts = timestamp.split_whitespace();
+    let date = parts.next().ok_or_else(invalid)?;
+    let numbers = |s: &str, separator: char| -> Result<Vec<i32>, String> {
+        s.split(separator).map(|n| n.parse::<i32>().map_err(|_| invalid())).collect()
+    };
+    let (year, month, day) = match (numbers(date, '/'), numbers(date, '-')) {
+        (Ok(mdy), _) if mdy.len() == 3 => (if mdy[2] < 100 { 2000 + mdy[2] } else { mdy[2] }, mdy[0], mdy[1]),
+        (_, Ok(ymd)) if ymd.len() == 3 => (ymd[0], ymd[1], ymd[2]),
+        _ => return Err(invalid()),
+    };
+    let date = Date::try_from_ymd(year, month as u8, day as u8).map_err(|_| invalid())?;
+    let hours = match parts.next() {
+        Some(time) => {
+            let hms = numbers(time, ':')?;
+            match hms.as_slice() {
+                [h, m] | [h, m, _] if (0..24).contains(h) && (0..60).contains(m) => *h as f32 + *m as f32 / 60.0,
+                _ => return Err(invalid()),
+            }
+        }
+        None => 0.0,
+    };
+    Ok((date.weekday().number_days_from_monday(), hours))
+}
+
+/// Encodes the inputs typed in the terminal interface or listed in a
+/// sequence file for a region with the given number of input bits:
+/// - lines of exactly that many 0 and 1 are SDRs,
+/// - `timestamp,value` rows are encoded with the date encoder in the
+///   first half and the scalar encoder in the second half of the bits,
+/// - numbers are encoded with the scalar encoder for the range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputEncoder {
+    pub size: usize,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl InputEncoder {
+    pub fn encode(&self, line: &str) -> Result<Vec<bool>, String> {
+        let line = line.trim();
+        let bits: Vec<bool> = line.chars().filter(|c| *c == '0' || *c == '1').map(|c| c == '1').collect();
+        if bits.len() == self.size && line.chars().all(|c| c == '0' || c == '1' || c == ',' || c.is_whitespace()) {
+            return Ok(bits);
+        }
+        if let Some((timestamp, value)) = line.rsplit_once(',') {
+            let half = self.size / 2;
+            let mut sdr = DateEncoder::new(half, active_bits(half / 2, 1))?.encode(timestamp)?;
+            sdr.extend(self.scalar(self.size - half)?.encode(self.value(value)?));
+            return Ok(sdr);
+        }
+        Ok(self.scalar(self.size)?.encode(self.value(line)?))
+    }
+
+    /// Encodes the lines of a sequence file, lines starting with # are
+    /// comments
+    pub fn encode_file(&self, content: &str) -> Result<Vec<Vec<bool>>, String> {
+        let mut sdrs = vec![];
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            sdrs.push(self.encode(line).map_err(|e| format!("{} in line {}", e, i + 1))?);
+        }
+        Ok(sdrs)
+    }
+
+    fn scalar(&self, size: usize) -> Result<ScalarEncoder, String> {
+        ScalarEncoder::new(self.min, self.max, size, active_bits(size, 1))
+    }
+
+    fn value(&self, value: &str) -> Result<f32, String> {
+        value.trim().parse::<f32>().map_err(|_| {
+            format!(
+                "Invalid input {}, expected {} bits, a number or a timestamp,value row",
+                value.trim(),
+                self.size
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_clips_and_keeps_sparsity() {
+        let encoder = ScalarEncoder::new(0.0, 10.0, 8, 2).unwrap();
+        assert_eq!(encoder.encode(0.0), vec![true, true, false, false, false, false, false, false]);
+        assert_eq!(encoder.encode(10.0), vec![false, false, false, false, false, false, true, true]);
+        assert_eq!(encoder.encode(-5.0), encoder.encode(0.0));
+        assert_eq!(encoder.encode(5.0).iter().filter(|b| **b).count(), 2);
+    }
+
+    #[test]
+    fn encoder_rejects_invalid_configuration() {
+        assert!(ScalarEncoder::new(1.0, 1.0, 8, 2).is_err());
+        assert!(ScalarEncoder::new(0.0, 1.0, 8, 9).is_err());
+        assert!(ScalarEncoder::for_values(&[3.0, 3.0], 8, 2).is_ok());
+    }
+
+    #[test]
+    fn date_encoder_is_periodic() {
+        assert_eq!(parse_timestamp("7/2/10 0:00"), Ok((4, 0.0)));
+        assert_eq!(parse_timestamp("2010-07-04 13:30"), Ok((6, 13.5)));
+        assert!(parse_timestamp("7/32/10 0:00").is_err());
+        assert!(parse_timestamp("7/2/10 24:00").is_err());
+        let encoder = DateEncoder::new(16, 2).unwrap();
+        let sunday = encoder.encode("7/4/10 23:59").unwrap();
+        let monday = encoder.encode("7/5/10 0:00").unwrap();
+        assert_eq!(sunday.iter().filter(|b| **b).count(), 4);
+        // The day wraps around, midnight follows the last minute
+        assert_eq!(&sunday[..8], &[true, false, false, false, false, false, false, true]);
+        assert_eq!(&monday[..8], &[true, true, false, false, false, false, false, false]);
+        assert_eq!(&sunday[8..], &monday[8..]);
+    }
+
+    #[test]
+    fn inputs_are_encoded_for_the_size() {
+        let encoder = InputEncoder {
+            size: 8,
+            min: 0.0,
+            max: 10.0,
+        };
+        assert_eq!(encoder.encode("0110 0001").unwrap(), vec![false, true, true, false, false, false, false, true]);
+        assert_eq!(encoder.encode("10").unwrap(), ScalarEncoder::new(0.0, 10.0, 8, 1).unwrap().encode(10.0));
+        let row = encoder.encode("7/2/10 0:00,5").unwrap();
+        assert_eq!(row.len(), 8);
+        assert_eq!(&row[4..], &[false, false, true, false]);
+        assert!(encoder.encode("0x1").unwrap_err().starts_with("Invalid input 0x1, expected 8 bits"));
+        let sdrs = encoder.encode_file("# Sequence\n1\n\n2\n").unwrap();
+        assert_eq!(sdrs.len(), 2);
+        assert!(encoder.encode_file("1\nx\n").unwrap_err().ends_with("in line 2"));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::pushr::push::instructions::{InstructionCache, InstructionSet};
 use crate::pushr::push::interpreter::{PushInterpreter, PushInterpreterState};
+use crate::pushr::push::item::{Item, PushType};
 use crate::pushr::push::parser::PushParser;
 use crate::pushr::push::state::PushState;
 use crate::pushr::push::io::PushMessage;
@@ -124,6 +125,27 @@ impl PushExecutor {
         let input_vec = BoolVector::new(input);
         self.push_state.input_stack.push(PushMessage::new(IntVector::new(vec![]), input_vec));
     }
+
+    /// Overwrites the name binding of a scalar parameter. The value is
+    /// parsed according to the type of the existing binding.
+    pub fn set_parameter(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let item = match self.push_state.name_bindings.get(name) {
+            Some(Item::Literal { push_type }) => match push_type {
+                PushType::Int { .. } => value.parse::<i32>().map(Item::int).map_err(|e| e.to_string()),
+                PushType::Float { .. } => value.parse::<f32>().map(Item::float).map_err(|e| e.to_string()),
+                PushType::Bool { .. } => match value.to_uppercase().as_str() {
+                    "TRUE" => Ok(Item::bool(true)),
+                    "FALSE" => Ok(Item::bool(false)),
+                    _ => Err(format!("Invalid boolean {}", value)),
+                },
+                _ => Err(format!("Parameter {} is not a scalar", name)),
+            },
+            Some(_) => Err(format!("Parameter {} is not a literal", name)),
+            None => Err(format!("Unknown parameter {}", name)),
+        }?;
+        self.push_state.name_bindings.insert(name.to_string(), item);
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -132,4 +154,19 @@ mod tests {
     pub fn icache() -> InstructionCache {
         InstructionCache::new(vec![])
     }
+
+    #[test]
+    fn set_parameter_keeps_binding_type() {
+        let mut executor = PushExecutor::new();
+        executor.initialize();
+        executor.load("( 0.5 PERM_INCREMENT FLOAT.DEFINE 3 NUM_CELLS INTEGER.DEFINE )".to_string());
+        executor.run();
+        assert!(executor.set_parameter("PERM_INCREMENT", "0.02").is_ok());
+        assert!(executor.set_parameter("NUM_CELLS", "0.02").is_err());
+        assert!(executor.set_parameter("UNKNOWN", "1").is_err());
+        assert_eq!(
+            executor.push_state.name_bindings.get("PERM_INCREMENT").unwrap().to_string(),
+            Item::float(0.02).to_string()
+        );
+    }
 }
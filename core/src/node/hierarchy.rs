@@ -0,0 +1,333 @@
+// Hierarchy Module
+// Network description of connected regions, e.g. SP -> TM -> SP, and
+// the wiring of the regions: node ids, message keys and input widths.
+
+use std::fs;
+use std::path::Path;
+
+use yaml_rust::{Yaml, YamlLoader};
+
+use crate::evolution::mutation::{parameters, parse_program, set_parameter, Parameter};
+use crate::node::args::{NodeArgs, NODE_TYPES};
+use crate::node::sdr::{typed_value, MAX_SDR_SIZE};
+use crate::node::source::Source;
+use crate::pushr::push::item::{Item, PushType};
+use crate::shared::msg::{MessageKey, NodeType};
+
+/// Input name of the external input of the network (D_INPUT)
+pub const NETWORK_INPUT: &str = "input";
+
+/// Region as described in the network file
+#[derive(Clone, Debug, PartialEq)]
+pub struct RegionSpec {
+    pub name: String,
+    pub node_type: NodeType,
+    /// Path of the Push program
+    pub program: String,
+    /// Names of the regions whose output is the input of this region
+    pub inputs: Vec<String>,
+    /// Overrides of DEFINE constants of the program
+    pub parameters: Vec<(String, f32)>,
+    /// Node id, regions without id are numbered from 1
+    pub id: Option<u16>,
+    /// Endpoint of the debug protocol of the node
+    pub debug: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkSpec {
+    pub regions: Vec<RegionSpec>,
+}
+
+fn field<'a>(region: &'a Yaml, key: &str, name: &str) -> Result<&'a str, String> {
+    region[key]
+        .as_str()
+        .ok_or_else(|| format!("Region {} has no {}", name, key))
+}
+
+fn number(value: &Yaml) -> Option<f32> {
+    match value {
+        Yaml::Integer(i) => Some(*i as f32),
+        Yaml::Real(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+impl NetworkSpec {
+    /// Parses a network description, see `network.yaml`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let docs = YamlLoader::load_from_str(s).map_err(|e| format!("Invalid network description: {}", e))?;
+        let doc = docs.first().ok_or_else(|| "Empty network description".to_string())?;
+        let regions = doc["regions"]
+            .as_vec()
+            .ok_or_else(|| "Network description has no regions".to_string())?;
+        let mut specs = vec![];
+        for (i, region) in regions.iter().enumerate() {
+            let name = region["name"]
+                .as_str()
+                .ok_or_else(|| format!("Region {} has no name", i + 1))?
+                .to_string();
+            let type_name = field(region, "type", &name)?;
+            let node_type = match NODE_TYPES.iter().find(|(n, _)| *n == type_name) {
+                Some((_, t)) if *t != NodeType::SCALAR_ENCODER => *t,
+                _ => return Err(format!("Region {} has unsupported type {}", name, type_name)),
+            };
+            let inputs = match &region["inputs"] {
+                Yaml::Array(inputs) => inputs
+                    .iter()
+                    .map(|i| i.as_str().map(|s| s.to_string()))
+                    .collect::<Option<Vec<String>>>()
+                    .ok_or_else(|| format!("Inputs of region {} must be names", name))?,
+                Yaml::String(input) => vec![input.clone()],
+                _ => return Err(format!("Region {} has no inputs", name)),
+            };
+            let mut params = vec![];
+            if let Some(hash) = region["parameters"].as_hash() {
+                for (k, v) in hash.iter() {
+                    match (k.as_str(), number(v)) {
+                        (Some(k), Some(v)) => params.push((k.to_string(), v)),
+                        _ => return Err(format!("Invalid parameter {:?} of region {}", k, name)),
+                    }
+                }
+            }
+            let id = match &region["id"] {
+                Yaml::BadValue => None,
+                id => match id.as_i64().filter(|id| (1..=u16::MAX as i64).contains(id)) {
+                    Some(id) => Some(id as u16),
+                    None => return Err(format!("Invalid id of region {}", name)),
+                },
+            };
+            specs.push(RegionSpec {
+                program: field(region, "program", &name)?.to_string(),
+                name,
+                node_type,
+                inputs,
+                parameters: params,
+                id,
+                debug: region["debug"].as_str().map(|d| d.to_string()),
+            });
+        }
+        Ok(Self { regions: specs })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let s = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&s)
+    }
+}
+
+/// Region with the arguments of its node process
+#[derive(Debug)]
+pub struct Region {
+    pub name: String,
+    pub args: NodeArgs,
+}
+
+impl Region {
+    /// Command line arguments (without binary) of the node process
+    pub fn node_args(&self) -> Vec<String> {
+        let mut args = self.args.replica_args(self.args.node_id, self.args.program.clone());
+        // Replicas do not inherit the checkpoint and the endpoint
+        if let Some(path) = &self.args.restore {
+            args.push("--restore".to_string());
+            args.push(path.clone());
+        }
+        if let Some(endpoint) = &self.args.debug {
+            args.push("--debug".to_string());
+            args.push(endpoint.clone());
+        }
+        args
+    }
+}
+
+fn int_parameter(params: &[Parameter], name: &str) -> Option<i32> {
+    params.iter().find(|p| p.name == name).and_then(|p| match p.value {
+        PushType::Int { val } => Some(val),
+        _ => None,
+    })
+}
+
+/// Number of output bits of a region: the columns of a spatial pooler
+/// or the cells of a temporal memory
+fn output_width(node_type: NodeType, params: &[Parameter]) -> Option<usize> {
+    let columns = int_parameter(params, "NUM_COLUMNS")?;
+    let width = match node_type {
+        NodeType::TEMPORAL_MEMORY => columns * int_parameter(params, "NUM_CELLS")?,
+        _ => columns,
+    };
+    Some(width.max(0) as usize)
+}
+
+fn set_int(items: &mut [Item], name: &str, value: usize) -> bool {
+    set_parameter(
+        items,
+        &Parameter {
+            name: name.to_string(),
+            value: PushType::Int { val: value as i32 },
+        },
+    )
+}
+
+/// Wires the regions of the network. Every region outputs with its own
+/// key, allocated from D_REGION upwards, and consumes the keys of its
+/// inputs. Regions may only take inputs from regions listed before
+/// them. The number of inputs of a spatial pooler is set to the width
+/// of its input regions, the columns of a temporal memory must match.
+/// Program paths are relative to `base_dir`.
+pub fn wire<P: AsRef<Path>>(spec: &NetworkSpec, bin: &str, base_dir: P) -> Result<Vec<Region>, String> {
+    let mut regions: Vec<Region> = vec![];
+    // Output key and width of the wired regions
+    let mut outputs: Vec<(u16, Option<usize>)> = vec![];
+    for (i, r) in spec.regions.iter().enumerate() {
+        if r.name == NETWORK_INPUT || regions.iter().any(|w| w.name == r.name) {
+            return Err(format!("Region name {} is reserved or used twice", r.name));
+        }
+        let node_id = r.id.unwrap_or(i as u16 + 1);
+        if regions.iter().any(|w| w.args.node_id == node_id) {
+            return Err(format!("Node id {} of region {} is used twice", node_id, r.name));
+        }
+        let mut keys = vec![];
+        let mut widths = vec![];
+        for input in r.inputs.iter() {
+            if input == NETWORK_INPUT {
+                keys.push(MessageKey::D_INPUT as u16);
+                continue;
+            }
+            match regions.iter().position(|w| &w.name == input) {
+                Some(j) => {
+                    keys.push(outputs[j].0);
+                    widths.extend(outputs[j].1);
+                }
+                None => return Err(format!("Input {} of region {} is not defined before it", input, r.name)),
+            }
+        }
+        if keys.is_empty() {
+            return Err(format!("Region {} has no inputs", r.name));
+        }
+        widths.dedup();
+        if widths.len() > 1 {
+            return Err(format!("Inputs of region {} differ in width: {:?}", r.name, widths));
+        }
+
+        let path = base_dir.as_ref().join(&r.program);
+        let code = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut items = parse_program(&Source::read_debug_code(code));
+        let defaults = parameters(&items);
+        for (name, value) in r.parameters.iter() {
+            let default = match defaults.iter().find(|p| &p.name == name) {
+                Some(p) => &p.value,
+                None => return Err(format!("Program of region {} does not define {}", r.name, name)),
+            };
+            set_parameter(
+                &mut items,
+                &Parameter {
+                    name: name.clone(),
+                    value: typed_value(default, *value),
+                },
+            );
+        }
+        if let Some(width) = widths.first() {
+            if *width > MAX_SDR_SIZE {
+                return Err(format!("Input of region {} exceeds {} bits", r.name, MAX_SDR_SIZE));
+            }
+            let (name, fixed) = match r.node_type {
+                NodeType::SPATIAL_POOLER => ("NUM_INPUTS", !set_int(&mut items, "NUM_INPUTS", *width)),
+                _ => ("NUM_COLUMNS", int_parameter(&parameters(&items), "NUM_COLUMNS") != Some(*width as i32)),
+            };
+            if fixed || r.parameters.iter().any(|(n, v)| n == name && *v as usize != *width) {
+                return Err(format!("{} of region {} must be {}", name, r.name, width));
+            }
+        }
+
+        let output_key = MessageKey::D_REGION as u16 + i as u16;
+        outputs.push((output_key, output_width(r.node_type, &parameters(&items))));
+        regions.push(Region {
+            name: r.name.clone(),
+            args: NodeArgs {
+                bin: bin.to_string(),
+                program: Source::from_items(&items),
+                node_id,
+                node_type: r.node_type,
+                keys,
+                output_key: Some(output_key),
+                checkpoint: None,
+                restore: None,
+                remove_restored: false,
+                debug: r.debug.clone(),
+                seed: None,
+            },
+        });
+    }
+    Ok(regions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NETWORK: &str = "
+regions:
+  - name: L1_SP
+    type: sp
+    program: src/core/spatial_pooler.push
+    inputs: [input]
+  - name: L1_TM
+    type: tm
+    program: src/core/temporal_memory.push
+    inputs: [L1_SP]
+    parameters:
+      NUM_CELLS: 2
+  - name: L2_SP
+    type: sp
+    program: src/core/spatial_pooler.push
+    inputs: [L1_TM]
+    id: 9
+    debug: tcp://127.0.0.1:7009
+";
+
+    #[test]
+    fn network_description_is_parsed() {
+        let spec = NetworkSpec::parse(NETWORK).unwrap();
+        assert_eq!(spec.regions.len(), 3);
+        assert_eq!(spec.regions[1].node_type, NodeType::TEMPORAL_MEMORY);
+        assert_eq!(spec.regions[1].inputs, vec!["L1_SP".to_string()]);
+        assert_eq!(spec.regions[1].parameters, vec![("NUM_CELLS".to_string(), 2.0)]);
+        assert_eq!(spec.regions[2].id, Some(9));
+        assert!(NetworkSpec::parse("regions:\n  - name: A\n    type: encoder").is_err());
+    }
+
+    #[test]
+    fn regions_are_chained_with_allocated_keys() {
+        let regions = wire(&NetworkSpec::parse(NETWORK).unwrap(), "phtm_headless", ".").unwrap();
+        let l1_sp = &regions[0].args;
+        let l1_tm = &regions[1].args;
+        let l2_sp = &regions[2].args;
+        assert_eq!(l1_sp.keys, vec![MessageKey::D_INPUT as u16]);
+        assert_eq!(l1_tm.keys, vec![l1_sp.output_key.unwrap()]);
+        assert_eq!(l2_sp.keys, vec![l1_tm.output_key.unwrap()]);
+        assert_eq!(l1_sp.output_key, Some(MessageKey::D_REGION as u16));
+        assert_eq!((l1_sp.node_id, l2_sp.node_id), (1, 9));
+        // Higher spatial pooler reads the 16 columns x 2 cells
+        let params = parameters(&parse_program(&l2_sp.program));
+        assert_eq!(int_parameter(&params, "NUM_INPUTS"), Some(32));
+        let params = parameters(&parse_program(&l1_tm.program));
+        assert_eq!(int_parameter(&params, "NUM_CELLS"), Some(2));
+        let mut args = vec!["bin".to_string()];
+        args.extend(regions[1].node_args());
+        assert_eq!(NodeArgs::parse(&args).unwrap().keys, l1_tm.keys);
+        let mut args = vec!["bin".to_string()];
+        args.extend(regions[2].node_args());
+        assert_eq!(NodeArgs::parse(&args).unwrap().debug, Some("tcp://127.0.0.1:7009".to_string()));
+    }
+
+    #[test]
+    fn invalid_wiring_is_rejected() {
+        let forward = NETWORK.replace("inputs: [input]", "inputs: [L1_TM]");
+        assert!(wire(&NetworkSpec::parse(&forward).unwrap(), "bin", ".").is_err());
+        let columns = NETWORK.replace("NUM_CELLS: 2", "NUM_COLUMNS: 8");
+        assert!(wire(&NetworkSpec::parse(&columns).unwrap(), "bin", ".").is_err());
+        let unknown = NETWORK.replace("NUM_CELLS", "NUM_FOO");
+        assert!(wire(&NetworkSpec::parse(&unknown).unwrap(), "bin", ".").is_err());
+    }
+}
@@ -0,0 +1,172 @@
+// History Module
+// Learning statistics of a node, sampled at the end of each processed
+// input and kept for a window of recent inputs. The terminal interface
+// shows them as charts.
+
+use std::collections::{HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::node::snapshot::{self, ColumnView};
+use crate::pushr::push::state::PushState;
+use crate::shared::msg::NodeType;
+
+/// Number of inputs kept
+pub const HISTORY_LENGTH: usize = 200;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Sample {
+    /// Number of active bits of the input
+    pub input: usize,
+    pub active_columns: usize,
+    /// Active columns without predictive cell at the previous input
+    pub bursting_columns: usize,
+    pub predictive_cells: usize,
+    /// Fraction of bursting active columns
+    pub anomaly: f32,
+    /// Mean permanence of the proximal (input -> boost cell) and distal
+    /// (cell -> segment) synapses
+    pub mean_permanence: f32,
+    /// Interpreter steps since the previous input
+    pub steps: u64,
+}
+
+#[derive(Debug)]
+pub struct History {
+    node_type: NodeType,
+    samples: VecDeque<Sample>,
+    /// Columns with a predictive cell at the end of the previous input
+    predicted: HashSet<usize>,
+    /// Steps at the end of the previous input
+    steps: u64,
+}
+
+impl History {
+    pub fn new(node_type: NodeType) -> Self {
+        Self {
+            node_type,
+            samples: VecDeque::new(),
+            predicted: HashSet::new(),
+            steps: 0,
+        }
+    }
+
+    /// Samples the model at the end of an input. Only the columns of a
+    /// temporal memory are predicted and burst.
+    pub fn record(&mut self, push_state: &PushState, input: &[bool], steps: u64) {
+        let model = match snapshot::capture_model(push_state) {
+            Some(model) => model,
+            None => return,
+        };
+        let state = |name: &str| model.state_names.iter().find(|(_, n)| n == name).map(|(value, _)| *value);
+        let (column_active, cell_predictive) = (state("COLUMN_ACTIVE"), state("CELL_PREDICTIVE"));
+        let predictive = |column: &ColumnView| column.cells.iter().filter(|(_, s)| Some(*s) == cell_predictive).count();
+        let active: Vec<&ColumnView> = model.columns.iter().filter(|c| Some(c.state) == column_active).collect();
+        let bursting = match self.node_type {
+            NodeType::TEMPORAL_MEMORY => active.iter().filter(|c| !self.predicted.contains(&c.id)).count(),
+            _ => 0,
+        };
+        self.predicted = model.columns.iter().filter(|c| predictive(c) > 0).map(|c| c.id).collect();
+        let sample = Sample {
+            input: input.iter().filter(|b| **b).count(),
+            active_columns: active.len(),
+            bursting_columns: bursting,
+            predictive_cells: model.columns.iter().map(predictive).sum(),
+            anomaly: if active.is_empty() {
+                0.0
+            } else {
+                bursting as f32 / active.len() as f32
+            },
+            mean_permanence: mean_permanence(push_state, &model.state_names),
+            steps: steps.saturating_sub(self.steps),
+        };
+        self.steps = steps;
+        if self.samples.len() == HISTORY_LENGTH {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// Samples from the oldest
+    pub fn samples(&self) -> Vec<Sample> {
+        self.samples.iter().cloned().collect()
+    }
+}
+
+fn mean_permanence(push_state: &PushState, state_names: &[(i32, String)]) -> f32 {
+    let graph = match push_state.graph_stack.get(0) {
+        Some(graph) => graph,
+        None => return 0.0,
+    };
+    let name = |id: usize| {
+        graph
+            .get_state(&id)
+            .and_then(|s| state_names.iter().find(|(value, _)| *value == s))
+            .map(|(_, name)| name.as_str())
+    };
+    let (mut sum, mut count) = (0.0, 0);
+    for (destination, edges) in graph.edges.iter() {
+        // Columns are connected to their boost cell as well
+        let origin = match name(*destination) {
+            Some("BOOST_CELL") => "INPUT_",
+            Some(n) if n.starts_with("SEGMENT") => "CELL_",
+            _ => continue,
+        };
+        for e in edges.iter().filter(|e| name(e.get_origin_id()).is_some_and(|n| n.starts_with(origin))) {
+            sum += e.get_weight();
+            count += 1;
+        }
+    }
+    if count == 0 {
+        0.0
+    } else {
+        sum / count as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::execution::PushExecutor;
+    use crate::node::sdr;
+    use crate::node::source::Source;
+
+    fn executor(code: &str) -> PushExecutor {
+        let mut executor = PushExecutor::new();
+        executor.initialize();
+        executor.load(Source::read_debug_code(code.to_string()));
+        executor
+    }
+
+    #[test]
+    fn spatial_pooler_inputs_are_sampled() {
+        let mut executor = executor(include_str!("../core/spatial_pooler.push"));
+        let mut history = History::new(NodeType::SPATIAL_POOLER);
+        executor.step_until("BP3".to_string());
+        history.record(&executor.push_state, &[], 0);
+        let input: Vec<bool> = (0..32).map(|i| i % 4 == 0).collect();
+        executor.inject(input.clone());
+        executor.step_until(sdr::SP_CYCLE_END.to_string());
+        history.record(&executor.push_state, &input, 5000);
+        let samples = history.samples();
+        assert_eq!(samples.len(), 2);
+        assert_eq!((samples[1].input, samples[1].steps), (8, 5000));
+        assert!(samples[1].active_columns > 0);
+        assert_eq!((samples[1].bursting_columns, samples[1].anomaly), (0, 0.0));
+        assert!(samples[1].mean_permanence > 0.0 && samples[1].mean_permanence < 1.0);
+    }
+
+    #[test]
+    fn unpredicted_columns_burst() {
+        let mut executor = executor(include_str!("../core/temporal_memory.push"));
+        let mut history = History::new(NodeType::TEMPORAL_MEMORY);
+        executor.step_until("BP1".to_string());
+        let input: Vec<bool> = (0..16).map(|i| i < 4).collect();
+        executor.inject(input.clone());
+        executor.step_until(sdr::TM_CYCLE_END.to_string());
+        history.record(&executor.push_state, &input, 100);
+        let sample = &history.samples()[0];
+        assert_eq!(sample.active_columns, 4);
+        assert_eq!((sample.bursting_columns, sample.anomaly), (4, 1.0));
+    }
+}
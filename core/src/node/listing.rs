@@ -0,0 +1,235 @@
+// Listing Module
+// Source of a program with comments and breakpoint markers kept. Maps
+// the execution stack back to the line of the next instruction and
+// finds the subroutine (EXEC.DEFINE) enclosing a line.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use crate::node::source::Source;
+use crate::pushr::push::item::Item;
+use crate::pushr::push::state::PushState;
+
+/// Tokens of the execution stack compared with the source
+const MATCH_LENGTH: usize = 16;
+
+/// Leading items of the execution stack that may be missing in the
+/// source
+const MAX_SKIPPED: usize = 2;
+
+#[derive(Clone, Debug)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+/// Subroutine defined with EXEC.DEFINE, lines are counted from 0
+#[derive(Clone, Debug, PartialEq)]
+pub struct Routine {
+    pub name: String,
+    pub first: usize,
+    pub last: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Listing {
+    lines: Vec<String>,
+    tokens: Vec<Token>,
+    /// Positions of each token text
+    positions: HashMap<String, Vec<usize>>,
+    routines: Vec<Routine>,
+}
+
+impl Listing {
+    /// Splits the program into tokens like the debug loader of the node
+    /// (`Source::read_debug_code`)
+    pub fn new(code: &str) -> Self {
+        let lines: Vec<String> = code.lines().map(|line| line.to_string()).collect();
+        let mut tokens = vec![];
+        for (i, line) in lines.iter().enumerate().filter(|(_, line)| !line.starts_with('#')) {
+            for text in Source::remove_suffix(line, "#").split_whitespace() {
+                tokens.push(Token {
+                    text: text.to_string(),
+                    line: i,
+                });
+            }
+        }
+        let mut positions: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, token) in tokens.iter().enumerate() {
+            positions.entry(token.text.clone()).or_default().push(i);
+        }
+        let routines = Listing::routines(&tokens);
+        Self {
+            lines,
+            tokens,
+            positions,
+            routines,
+        }
+    }
+
+    /// NAME EXEC.DEFINE followed by a list or a single item
+    fn routines(tokens: &[Token]) -> Vec<Routine> {
+        let mut routines = vec![];
+        for i in 1..tokens.len().saturating_sub(1) {
+            if tokens[i].text != "EXEC.DEFINE" {
+                continue;
+            }
+            let mut last = i + 1;
+            if tokens[last].text == "(" {
+                let mut depth = 0;
+                for (j, token) in tokens.iter().enumerate().skip(i + 1) {
+                    match token.text.as_str() {
+                        "(" => depth += 1,
+                        ")" => depth -= 1,
+                        _ => (),
+                    }
+                    last = j;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            routines.push(Routine {
+                name: tokens[i - 1].text.clone(),
+                first: tokens[i - 1].line,
+                last: tokens[last].line,
+            });
+        }
+        routines
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// Line of a position returned by `locate`
+    pub fn line(&self, position: usize) -> Option<usize> {
+        self.tokens.get(position).map(|token| token.line)
+    }
+
+    /// Innermost subroutine containing the line
+    pub fn routine(&self, line: usize) -> Option<&Routine> {
+        self.routines
+            .iter()
+            .filter(|routine| routine.first <= line && line <= routine.last)
+            .max_by_key(|routine| routine.first)
+    }
+
+    /// Tokens of the line without comment
+    pub fn code(&self, line: usize) -> Vec<&str> {
+        match self.lines.get(line) {
+            Some(line) if !line.starts_with('#') => Source::remove_suffix(line, "#").split_whitespace().collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Position of the next instruction in the tokens of the source. An
+    /// instruction may occur at several positions, the one whose
+    /// following tokens match the execution stack best wins. Ties are
+    /// resolved in favour of the first position after the previous one.
+    /// Items that are not part of the source, e.g. the values of
+    /// bindings or loop counters, are skipped and located at the token
+    /// before the match.
+    pub fn locate(&self, exec: &[String], previous: Option<usize>) -> Option<usize> {
+        let score = |skipped: usize, p: usize| {
+            exec[skipped..]
+                .iter()
+                .zip(self.tokens[p..].iter())
+                .take_while(|(text, token)| **text == token.text)
+                .count()
+        };
+        let after = |p: usize| previous.is_none_or(|previous| p >= previous);
+        (0..exec.len().min(MAX_SKIPPED + 1))
+            .flat_map(|skipped| {
+                let positions = self.positions.get(&exec[skipped]).map_or(&[][..], |p| p.as_slice());
+                positions.iter().map(move |p| (skipped, *p))
+            })
+            .max_by_key(|(skipped, p)| (score(*skipped, *p), after(*p), Reverse(*skipped), Reverse(*p)))
+            .map(|(skipped, p)| p.saturating_sub(skipped))
+    }
+}
+
+/// First tokens of the execution stack, lists are expanded
+pub fn exec_tokens(push_state: &PushState) -> Vec<String> {
+    let mut tokens = vec![];
+    let stack = &push_state.exec_stack;
+    for i in 0..stack.size() {
+        if tokens.len() >= MATCH_LENGTH {
+            break;
+        }
+        if let Some(item) = stack.get(i) {
+            expand(item, &mut tokens);
+        }
+    }
+    tokens.truncate(MATCH_LENGTH);
+    tokens
+}
+
+fn expand(item: &Item, tokens: &mut Vec<String>) {
+    match item {
+        Item::List { items } => {
+            tokens.push("(".to_string());
+            for i in 0..items.size() {
+                if tokens.len() >= MATCH_LENGTH {
+                    return;
+                }
+                if let Some(item) = items.get(i) {
+                    expand(item, tokens);
+                }
+            }
+            tokens.push(")".to_string());
+        }
+        // Literals are written like in the source
+        _ => tokens.push(Source::from_items(std::slice::from_ref(item))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::execution::PushExecutor;
+
+    const PROGRAM: &str = "\
+# Test program
+(
+ADD EXEC.DEFINE
+(
+\t1 INTEGER.+ # Increment
+)
+BP0 NAME.POP
+1 ADD
+2 ADD
+)";
+
+    #[test]
+    fn subroutines_enclose_their_lines() {
+        let listing = Listing::new(PROGRAM);
+        assert_eq!(listing.routine(4).map(|r| r.name.as_str()), Some("ADD"));
+        assert_eq!(listing.routine(5).map(|r| (r.first, r.last)), Some((2, 5)));
+        assert_eq!(listing.routine(6), None);
+        assert_eq!(listing.code(4), vec!["1", "INTEGER.+"]);
+        assert!(listing.code(0).is_empty());
+    }
+
+    #[test]
+    fn next_instruction_is_located() {
+        let listing = Listing::new(PROGRAM);
+        let mut executor = PushExecutor::new();
+        executor.initialize();
+        executor.load(Source::read_debug_code(PROGRAM.to_string()));
+        let mut position = None;
+        let mut lines = vec![];
+        for _ in 0..12 {
+            position = listing.locate(&exec_tokens(&executor.push_state), position);
+            lines.push(position.and_then(|p| listing.line(p)));
+            executor.step();
+        }
+        // Program, definition, breakpoint, first call and its body
+        assert_eq!(lines[0], Some(1));
+        assert!(lines.contains(&Some(2)));
+        assert!(lines.contains(&Some(6)));
+        assert!(lines.contains(&Some(7)));
+        assert!(lines.contains(&Some(4)));
+        assert!(lines.iter().all(|line| line.is_some()));
+    }
+}
@@ -0,0 +1,304 @@
+// Metrics Module
+// Quality metrics of the spatial pooler, computed from the active
+// columns after each input.
+
+use std::collections::VecDeque;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::node::message::Message;
+use crate::node::sdr;
+use crate::pushr::push::state::PushState;
+use crate::shared::msg::{MessageCommand, MessageKey, MessageType, PAYLOAD_OFFSET};
+
+/// Number of recent inputs compared with the current input
+const HISTORY_SIZE: usize = 100;
+
+/// Inputs with at least this overlap count as noisy versions of each
+/// other
+pub const MIN_NOISY_OVERLAP: f32 = 0.5;
+
+/// Fraction of active bits
+pub fn sparsity(sdr: &[bool]) -> f32 {
+    if sdr.is_empty() {
+        return 0.0;
+    }
+    sdr.iter().filter(|b| **b).count() as f32 / sdr.len() as f32
+}
+
+/// Number of shared active bits relative to the larger number of
+/// active bits. Two empty SDRs fully overlap.
+pub fn overlap(a: &[bool], b: &[bool]) -> f32 {
+    let shared = a.iter().zip(b.iter()).filter(|(x, y)| **x && **y).count();
+    let active = a.iter().filter(|x| **x).count().max(b.iter().filter(|x| **x).count());
+    if active == 0 {
+        return 1.0;
+    }
+    shared as f32 / active as f32
+}
+
+/// Moves the given fraction of the active bits to random inactive
+/// positions. The number of active bits is kept.
+pub fn add_noise<R: Rng>(sdr: &[bool], fraction: f32, rng: &mut R) -> Vec<bool> {
+    let mut active: Vec<usize> = (0..sdr.len()).filter(|i| sdr[*i]).collect();
+    let mut inactive: Vec<usize> = (0..sdr.len()).filter(|i| !sdr[*i]).collect();
+    active.shuffle(rng);
+    inactive.shuffle(rng);
+    let moved = ((active.len() as f32 * fraction).round() as usize).min(inactive.len());
+    let mut noisy = sdr.to_vec();
+    for (from, to) in active.iter().zip(inactive.iter()).take(moved) {
+        noisy[*from] = false;
+        noisy[*to] = true;
+    }
+    noisy
+}
+
+/// Flags the columns of the top graph that are in the active state.
+/// Columns are read from the COLUMN_IDS binding of the program.
+pub fn active_columns(push_state: &PushState) -> Result<Vec<bool>, String> {
+    let column_ids = sdr::int_vector_binding(push_state, "COLUMN_IDS")
+        .ok_or_else(|| "Missing integer vector COLUMN_IDS".to_string())?;
+    let state_active = sdr::int_binding(push_state, "STATE_COLUMN_ACTIVE")
+        .ok_or_else(|| "Missing integer STATE_COLUMN_ACTIVE".to_string())?;
+    let graph = push_state.graph_stack.get(0).ok_or_else(|| "No graph".to_string())?;
+    Ok(column_ids
+        .iter()
+        .map(|id| graph.get_state(&(*id as usize)) == Some(state_active))
+        .collect())
+}
+
+/// Counts how often each column was active
+#[derive(Clone, Debug, PartialEq)]
+pub struct DutyCycle {
+    pub counts: Vec<u32>,
+}
+
+impl DutyCycle {
+    pub fn new(columns: usize) -> Self {
+        Self { counts: vec![0; columns] }
+    }
+
+    pub fn update(&mut self, active: &[bool]) {
+        for (count, a) in self.counts.iter_mut().zip(active.iter()) {
+            if *a {
+                *count += 1;
+            }
+        }
+    }
+
+    /// Entropy of the column usage normalized to [0, 1]. 1 means all
+    /// columns were used equally often.
+    pub fn entropy(&self) -> f32 {
+        let total: u32 = self.counts.iter().sum();
+        if total == 0 || self.counts.len() < 2 {
+            return 0.0;
+        }
+        let entropy: f32 = self
+            .counts
+            .iter()
+            .filter(|c| **c > 0)
+            .map(|c| {
+                let p = *c as f32 / total as f32;
+                -p * p.log2()
+            })
+            .sum();
+        entropy / (self.counts.len() as f32).log2()
+    }
+}
+
+/// Spatial pooler quality. Metrics that were not measured yet are NaN.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpMetrics {
+    /// Mean fraction of active columns
+    pub sparsity: f32,
+    /// Normalized entropy of the duty cycle
+    pub entropy: f32,
+    /// Mean output overlap of repeated inputs
+    pub stability: f32,
+    /// Mean loss of output overlap for noisy versions of an input
+    pub noise_degradation: f32,
+}
+
+impl SpMetrics {
+    /// Creates the metrics message published on the bus. Payload (big
+    /// endian): node id (u16) and the metrics as f32.
+    pub fn to_message(&self, node_id: u16) -> Message {
+        let mut m = Message {
+            data: vec![0; PAYLOAD_OFFSET],
+        };
+        m.create_header(MessageType::DATA, MessageCommand::METRICS, MessageKey::D_SPOOL);
+        let mut payload = node_id.to_be_bytes().to_vec();
+        for v in [self.sparsity, self.entropy, self.stability, self.noise_degradation].iter() {
+            payload.extend_from_slice(&v.to_be_bytes());
+        }
+        m.set_payload(&mut payload);
+        m
+    }
+
+    /// Reads node id and metrics from a metrics message
+    pub fn from_message(msg: &Message) -> Option<(u16, SpMetrics)> {
+        let payload = msg.data.get(PAYLOAD_OFFSET..)?;
+        let node_id = u16::from_be_bytes([*payload.first()?, *payload.get(1)?]);
+        let read_f32 = |i: usize| -> Option<f32> {
+            let b = payload.get(2 + 4 * i..6 + 4 * i)?;
+            Some(f32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        };
+        Some((
+            node_id,
+            SpMetrics {
+                sparsity: read_f32(0)?,
+                entropy: read_f32(1)?,
+                stability: read_f32(2)?,
+                noise_degradation: read_f32(3)?,
+            },
+        ))
+    }
+}
+
+/// Running mean, NaN without values
+#[derive(Clone, Copy, Debug, Default)]
+struct Mean {
+    sum: f32,
+    count: u32,
+}
+
+impl Mean {
+    fn add(&mut self, value: f32) {
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn value(&self) -> f32 {
+        if self.count == 0 {
+            return f32::NAN;
+        }
+        self.sum / self.count as f32
+    }
+}
+
+/// Updates the metrics from pairs of input and active columns. An
+/// input is compared with the most similar recent input: if both are
+/// equal the output overlap is the stability, otherwise one is
+/// considered a noisy version of the other. Trackers created without
+/// columns take the size of the first output.
+#[derive(Clone, Debug)]
+pub struct MetricsTracker {
+    duty_cycle: DutyCycle,
+    history: VecDeque<(Vec<bool>, Vec<bool>)>,
+    sparsity: Mean,
+    stability: Mean,
+    noise_degradation: Mean,
+}
+
+impl Default for MetricsTracker {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl MetricsTracker {
+    pub fn new(columns: usize) -> Self {
+        Self {
+            duty_cycle: DutyCycle::new(columns),
+            history: VecDeque::with_capacity(HISTORY_SIZE),
+            sparsity: Mean::default(),
+            stability: Mean::default(),
+            noise_degradation: Mean::default(),
+        }
+    }
+
+    pub fn update(&mut self, input: &[bool], output: &[bool]) -> SpMetrics {
+        if self.duty_cycle.counts.is_empty() {
+            self.duty_cycle = DutyCycle::new(output.len());
+        }
+        self.sparsity.add(sparsity(output));
+        self.duty_cycle.update(output);
+        let most_similar = self
+            .history
+            .iter()
+            .map(|(i, o)| (overlap(input, i), o))
+            .filter(|(input_overlap, _)| *input_overlap >= MIN_NOISY_OVERLAP)
+            .max_by(|a, b| a.0.total_cmp(&b.0));
+        if let Some((input_overlap, previous)) = most_similar {
+            if input_overlap >= 1.0 {
+                self.stability.add(overlap(output, previous));
+            } else {
+                self.noise_degradation.add(1.0 - overlap(output, previous));
+            }
+        }
+        if self.history.len() == HISTORY_SIZE {
+            self.history.pop_front();
+        }
+        self.history.push_back((input.to_vec(), output.to_vec()));
+        self.metrics()
+    }
+
+    pub fn metrics(&self) -> SpMetrics {
+        SpMetrics {
+            sparsity: self.sparsity.value(),
+            entropy: self.duty_cycle.entropy(),
+            stability: self.stability.value(),
+            noise_degradation: self.noise_degradation.value(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn sdr(bits: &str) -> Vec<bool> {
+        bits.chars().map(|c| c == '1').collect()
+    }
+
+    #[test]
+    fn sparsity_overlap_and_noise() {
+        assert_eq!(sparsity(&sdr("1100")), 0.5);
+        assert_eq!(overlap(&sdr("1100"), &sdr("1010")), 0.5);
+        assert_eq!(overlap(&sdr("0000"), &sdr("0000")), 1.0);
+        let input = sdr("1111000000000000");
+        let noisy = add_noise(&input, 0.5, &mut StdRng::seed_from_u64(1));
+        assert_eq!(sparsity(&noisy), sparsity(&input));
+        assert_eq!(overlap(&input, &noisy), 0.5);
+    }
+
+    #[test]
+    fn entropy_of_duty_cycle() {
+        let mut duty_cycle = DutyCycle::new(4);
+        duty_cycle.update(&sdr("1000"));
+        assert_eq!(duty_cycle.entropy(), 0.0);
+        duty_cycle.update(&sdr("0111"));
+        assert!((duty_cycle.entropy() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn tracker_measures_stability_and_noise() {
+        let mut tracker = MetricsTracker::new(4);
+        let first = tracker.update(&sdr("11110000"), &sdr("1100"));
+        assert!(first.stability.is_nan() && first.noise_degradation.is_nan());
+        let repeated = tracker.update(&sdr("11110000"), &sdr("1000"));
+        assert_eq!(repeated.stability, 0.5);
+        let noisy = tracker.update(&sdr("11100001"), &sdr("0011"));
+        assert_eq!(noisy.noise_degradation, 1.0);
+        assert_eq!(noisy.sparsity, (0.5 + 0.25 + 0.5) / 3.0);
+    }
+
+    #[test]
+    fn metrics_message_roundtrip() {
+        let metrics = SpMetrics {
+            sparsity: 0.25,
+            entropy: 0.9,
+            stability: 1.0,
+            noise_degradation: f32::NAN,
+        };
+        let m = metrics.to_message(3);
+        assert_eq!(m.get_cmd(), Some(MessageCommand::METRICS));
+        let (node_id, parsed) = SpMetrics::from_message(&m).unwrap();
+        assert_eq!(node_id, 3);
+        assert_eq!(parsed.sparsity, 0.25);
+        assert!(parsed.noise_degradation.is_nan());
+    }
+}
@@ -1,4 +1,22 @@
+pub mod args;
+pub mod bus;
+pub mod checkpoint;
 pub mod control;
+pub mod debug;
+pub mod encoder;
 pub mod execution;
-pub mod message;
+pub mod hierarchy;
+pub mod history;
+pub mod listing;
+pub mod metrics;
+pub mod queue;
+pub mod random;
+pub mod replication;
+pub mod runtime;
+pub mod sdr;
+pub mod session;
+pub mod snapshot;
 pub mod source;
+pub mod supervisor;
+
+pub use phtm_msg::{channel, message, network};
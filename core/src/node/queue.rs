@@ -0,0 +1,296 @@
+// Queue Module
+// Bounded queues between the message bus and the executor. Inbound
+// messages and injected inputs are moved to the input stack, outbound
+// messages are taken from the output stack independent of each other
+// and of the steps.
+
+use std::collections::VecDeque;
+use std::sync::mpsc::Receiver;
+
+use log::{debug, warn};
+
+use crate::node::execution::PushExecutor;
+use crate::node::message::Message;
+use crate::node::sdr;
+use crate::pushr::push::io::PushMessage;
+use crate::pushr::push::vector::{BoolVector, IntVector};
+use crate::shared::msg::{MessageCommand, MessageKey, MessageType, NodeType};
+use crate::shared::msg::{CMD_OFFSET, KEY_OFFSET, PAYLOAD_OFFSET, TYPE_OFFSET};
+
+#[derive(Clone, Debug)]
+pub struct QueueConfig {
+    /// Messages buffered between the subscriber thread and the loop
+    pub inbound_capacity: usize,
+    /// Interpreter steps between two checks of the queues
+    pub steps_per_iteration: usize,
+    /// Outbound messages published per iteration
+    pub outbound_batch: usize,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            inbound_capacity: 64,
+            steps_per_iteration: 100,
+            outbound_batch: 16,
+        }
+    }
+}
+
+/// Decides if a node consumes a message. Data of other regions is only
+/// consumed with one of the keys of the node.
+pub fn accepts(msg: &Message, keys: &[u16]) -> bool {
+    msg.data.len() >= PAYLOAD_OFFSET
+        && (msg.get_type() != Some(MessageType::DATA) || keys.contains(&msg.get_prop(&KEY_OFFSET)))
+}
+
+/// Converts an inbound message to the input of the program. The header
+/// holds type, command and key, the body the SDR of the payload.
+pub fn input_message(msg: &Message, node_type: NodeType, executor: &PushExecutor) -> PushMessage {
+    let header = IntVector::new(vec![
+        msg.get_prop(&TYPE_OFFSET) as i32,
+        msg.get_prop(&CMD_OFFSET) as i32,
+        msg.get_prop(&KEY_OFFSET) as i32,
+    ]);
+    let size = sdr::input_size(&executor.push_state, node_type).unwrap_or(sdr::MAX_SDR_SIZE);
+    PushMessage::new(header, BoolVector::new(sdr::from_message(msg, size)))
+}
+
+/// Moves accepted messages of the inbound queue to the input stack
+/// until it is full. Further messages wait in the inbound queue until
+/// the program consumes an input. Returns the inputs of the accepted
+/// messages.
+pub fn feed_inbound(
+    executor: &mut PushExecutor,
+    inbound: &Receiver<Vec<u8>>,
+    keys: &[u16],
+    node_type: NodeType,
+) -> Vec<PushMessage> {
+    let mut accepted = vec![];
+    while !executor.push_state.input_stack.is_full() {
+        let msg = match inbound.try_recv() {
+            Ok(data) => Message { data },
+            Err(_) => break,
+        };
+        if !accepts(&msg, keys) {
+            continue;
+        }
+        debug!("RECV MSG (TOPIC: {})", msg.get_topic());
+        let input = input_message(&msg, node_type, executor);
+        accepted.push(input.clone());
+        executor.push_state.input_stack.push(input);
+    }
+    accepted
+}
+
+/// Inputs of a node. Injected inputs wait until the memory graph is
+/// created and the input stack has space, the inputs on the input stack
+/// are kept until the program processed them.
+#[derive(Debug)]
+pub struct InputQueue {
+    /// Inputs on the input stack, oldest first. The program removes
+    /// the oldest one at the end of its input cycle.
+    inputs: VecDeque<Vec<bool>>,
+    /// Injected inputs waiting for the input stack
+    pending: VecDeque<Vec<bool>>,
+    capacity: usize,
+}
+
+impl InputQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inputs: VecDeque::new(),
+            pending: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// Queues an injected input until the input stack has space.
+    /// Returns false if the input was rejected.
+    pub fn queue(&mut self, input: Vec<bool>, executor: &PushExecutor, node_type: NodeType) -> bool {
+        if let Err(e) = check_input(&input, executor, node_type) {
+            warn!("Input rejected: {}", e);
+            return false;
+        }
+        if self.pending.len() >= self.capacity {
+            warn!("Input rejected, {} inputs are waiting", self.pending.len());
+            return false;
+        }
+        self.pending.push_back(input);
+        true
+    }
+
+    /// Moves injected inputs, then inbound messages to the input stack
+    /// until it is full. Injected inputs are pushed like an input of the
+    /// bus with the first key of the node. Returns the pushed inputs.
+    pub fn feed(
+        &mut self,
+        executor: &mut PushExecutor,
+        keys: &[u16],
+        node_type: NodeType,
+        inbound: Option<&Receiver<Vec<u8>>>,
+    ) -> Vec<PushMessage> {
+        let mut received = vec![];
+        while !executor.push_state.input_stack.is_full() {
+            match self.pending.pop_front() {
+                // The size may be defined after the input was queued
+                Some(input) => match check_input(&input, executor, node_type) {
+                    Ok(()) => {
+                        let key = keys.first().copied().unwrap_or(MessageKey::D_INPUT as u16);
+                        let input = input_message(&sdr::to_message(&input, key), node_type, executor);
+                        self.receive(executor, input.clone());
+                        received.push(input);
+                    }
+                    Err(e) => warn!("Input rejected: {}", e),
+                },
+                None => break,
+            }
+        }
+        if let Some(inbound) = inbound {
+            for input in feed_inbound(executor, inbound, keys, node_type) {
+                self.inputs.push_back(input.body.values.clone());
+                received.push(input);
+            }
+        }
+        received
+    }
+
+    /// Pushes an input to the input stack, e.g. a replayed input.
+    /// Returns false if the input stack is full.
+    pub fn receive(&mut self, executor: &mut PushExecutor, input: PushMessage) -> bool {
+        if executor.push_state.input_stack.is_full() {
+            warn!("Input dropped, the input stack is full");
+            return false;
+        }
+        self.inputs.push_back(input.body.values.clone());
+        executor.push_state.input_stack.push(input);
+        true
+    }
+
+    /// Takes the oldest input at the end of an input cycle
+    pub fn processed(&mut self) -> Option<Vec<bool>> {
+        self.inputs.pop_front()
+    }
+
+    /// Forgets the inputs of the input stack, e.g. when the program is
+    /// reloaded
+    pub fn clear(&mut self) {
+        self.inputs.clear();
+    }
+
+    /// Inputs on the input stack, oldest first
+    pub fn inputs(&self) -> &VecDeque<Vec<bool>> {
+        &self.inputs
+    }
+
+    /// Number of injected inputs waiting for the input stack
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// Inputs must have the size of the region once the program defined it
+fn check_input(input: &[bool], executor: &PushExecutor, node_type: NodeType) -> Result<(), String> {
+    sdr::check_input_size(input, sdr::input_size(&executor.push_state, node_type), node_type)
+}
+
+/// Creates the bus message of an output of the program. Each message
+/// starts with a cleared payload of the size of the body.
+pub fn outbound_message(output: &PushMessage) -> Message {
+    let mut m = Message {
+        data: vec![0; PAYLOAD_OFFSET],
+    };
+    m.create_header(MessageType::DATA, MessageCommand::WRITE, MessageKey::UNDEFINED);
+    m.set_headers(&output.header.values);
+    m.set_payload(&mut vec![0; output.body.values.len().div_ceil(8)]);
+    for (i, _) in output.body.values.iter().enumerate().filter(|(_, b)| **b) {
+        m.set_payload_bit(&i);
+    }
+    m
+}
+
+/// Takes up to a batch of outbound messages, oldest first: outputs of
+/// the program and the name message if the send flag is set
+pub fn drain_outbound(executor: &mut PushExecutor, config: &QueueConfig) -> Vec<Message> {
+    let mut outbound = vec![];
+    while outbound.len() < config.outbound_batch {
+        match executor.push_state.output_stack.pop() {
+            Some(output) => outbound.push(outbound_message(&output)),
+            None => break,
+        }
+    }
+    if executor.push_state.send_name {
+        executor.push_state.send_name = false;
+        if let Some(name) = executor.push_state.name_stack.pop() {
+            let mut m = Message {
+                data: vec![0; PAYLOAD_OFFSET],
+            };
+            m.create_header(MessageType::DATA, MessageCommand::PRINT, MessageKey::UNDEFINED);
+            m.set_payload(&mut name.into_bytes());
+            outbound.push(m);
+        }
+    }
+    outbound
+}
+
+/// The interpreter waits while the output stack is full, further
+/// outputs would be dropped
+pub fn output_full(executor: &PushExecutor) -> bool {
+    executor.push_state.output_stack.is_full()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    fn data_message(key: u16, bits: &[usize]) -> Vec<u8> {
+        let mut sdr = vec![false; 16];
+        for b in bits.iter() {
+            sdr[*b] = true;
+        }
+        sdr::to_message(&sdr, key).data
+    }
+
+    #[test]
+    fn inbound_is_filtered_and_bounded() {
+        let mut executor = PushExecutor::new();
+        let capacity = executor.push_state.input_stack.capacity();
+        let (tx, rx) = mpsc::sync_channel(capacity + 2);
+        tx.send(data_message(1003, &[1])).unwrap();
+        for i in 0..capacity + 1 {
+            tx.send(data_message(1001, &[2 + i])).unwrap();
+        }
+        let accepted = feed_inbound(&mut executor, &rx, &[1001], NodeType::UNDEFINED);
+        assert_eq!(accepted.len(), capacity);
+        assert!(accepted[1].body.values[3]);
+        assert_eq!(executor.push_state.input_stack.size(), capacity);
+        let first = executor.push_state.input_stack.get(0).unwrap();
+        assert_eq!(first.header.values, vec![2, 2, 1001]);
+        assert!(first.body.values[2] && !first.body.values[1]);
+        // The last message waits for the program to consume an input
+        assert_eq!(rx.try_recv().unwrap(), data_message(1001, &[2 + capacity]));
+    }
+
+    #[test]
+    fn outbound_messages_are_cleared_and_in_order() {
+        let mut executor = PushExecutor::new();
+        let header = IntVector::new(vec![1005, MessageCommand::WRITE as i32, MessageType::DATA as i32]);
+        let body = |bits: Vec<bool>| BoolVector::new(bits);
+        executor
+            .push_state
+            .output_stack
+            .push(PushMessage::new(header.clone(), body(vec![true, true, false])));
+        executor
+            .push_state
+            .output_stack
+            .push(PushMessage::new(header, body(vec![false, false, true])));
+        let config = QueueConfig::default();
+        let outbound = drain_outbound(&mut executor, &config);
+        assert_eq!(outbound.len(), 2);
+        assert_eq!(outbound[0].get_prop(&KEY_OFFSET), 1005);
+        assert_eq!(sdr::from_message(&outbound[0], 3), vec![true, true, false]);
+        assert_eq!(sdr::from_message(&outbound[1], 3), vec![false, false, true]);
+        assert!(!output_full(&executor));
+    }
+}
@@ -0,0 +1,147 @@
+// Random Module
+// Seeded random instructions. The random instructions of pushr draw
+// from the thread RNG, a seeded node replaces them with instructions
+// that draw the same values from one seeded RNG, so that a session
+// can be replayed.
+
+use std::sync::{Arc, Mutex};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Distribution, Normal};
+
+use crate::pushr::push::instructions::{Instruction, InstructionCache, InstructionSet};
+use crate::pushr::push::state::PushState;
+use crate::pushr::push::vector::{BoolVector, FloatVector, IntVector};
+
+type Rand = fn(&mut PushState, &mut StdRng);
+
+/// Instructions that are replaced by seeded ones
+const INSTRUCTIONS: [(&str, Rand); 6] = [
+    ("BOOLEAN.RAND", boolean_rand),
+    ("INTEGER.RAND", integer_rand),
+    ("FLOAT.RAND", float_rand),
+    ("BOOLVECTOR.RAND", bool_vector_rand),
+    ("INTVECTOR.RAND", int_vector_rand),
+    ("FLOATVECTOR.RAND", float_vector_rand),
+];
+
+/// Replaces the random instructions of the set with instructions that
+/// share an RNG seeded with the seed
+pub fn seed_instructions(instruction_set: &mut InstructionSet, seed: u64) {
+    let rng = Arc::new(Mutex::new(StdRng::seed_from_u64(seed)));
+    for (name, rand) in INSTRUCTIONS.iter() {
+        let (rng, rand) = (Arc::clone(&rng), *rand);
+        instruction_set.add(
+            name.to_string(),
+            Instruction::new(move |push_state: &mut PushState, _: &InstructionCache| {
+                rand(push_state, &mut rng.lock().unwrap())
+            }),
+        );
+    }
+}
+
+fn boolean_rand(push_state: &mut PushState, rng: &mut StdRng) {
+    push_state.bool_stack.push(rng.gen_range(0..2) == 1);
+}
+
+fn integer_rand(push_state: &mut PushState, rng: &mut StdRng) {
+    let (min, max) = (
+        push_state.configuration.min_random_integer,
+        push_state.configuration.max_random_integer,
+    );
+    if min < max {
+        push_state.int_stack.push(rng.gen_range(min..max));
+    }
+}
+
+fn float_rand(push_state: &mut PushState, rng: &mut StdRng) {
+    let (min, max) = (
+        push_state.configuration.min_random_float,
+        push_state.configuration.max_random_float,
+    );
+    if min < max {
+        push_state.float_stack.push(rng.gen_range(min..max));
+    }
+}
+
+/// Size from the INTEGER stack, sparsity from the FLOAT stack. Like
+/// pushr the last bit is never flipped.
+fn bool_vector_rand(push_state: &mut PushState, rng: &mut StdRng) {
+    let size = match push_state.int_stack.pop() {
+        Some(size) => size,
+        None => return,
+    };
+    let sparsity = match push_state.float_stack.pop() {
+        Some(sparsity) if size >= 0 && (0.0..=1.0).contains(&sparsity) => sparsity,
+        _ => return,
+    };
+    // Bits are flipped from the more frequent value
+    let default = sparsity > 0.5;
+    let sparsity = (100.0 * sparsity.min(1.0 - sparsity)).round() / 100.0;
+    let mut values = vec![default; size as usize];
+    for _ in 0..(sparsity * size as f32) as i32 {
+        loop {
+            let i = rng.gen_range(0..size - 1) as usize;
+            if values[i] == default {
+                values[i] = !default;
+                break;
+            }
+        }
+    }
+    push_state.bool_vector_stack.push(BoolVector::new(values));
+}
+
+/// Size, max and min from the INTEGER stack
+fn int_vector_rand(push_state: &mut PushState, rng: &mut StdRng) {
+    if let Some(params) = push_state.int_stack.pop_vec(3) {
+        let (size, min, max) = (params[2], params[0], params[1]);
+        if size >= 0 && min < max {
+            let values = (0..size).map(|_| rng.gen_range(min..max)).collect();
+            push_state.int_vector_stack.push(IntVector::new(values));
+        }
+    }
+}
+
+/// Size from the INTEGER stack, mean and standard deviation from the
+/// FLOAT stack
+fn float_vector_rand(push_state: &mut PushState, rng: &mut StdRng) {
+    if let Some(size) = push_state.int_stack.pop() {
+        if let Some(params) = push_state.float_stack.pop_vec(2) {
+            if let Ok(normal) = Normal::new(params[1], params[0]) {
+                if size >= 0 && params[0] >= 0.0 {
+                    let values = (0..size).map(|_| normal.sample(rng)).collect();
+                    push_state.float_vector_stack.push(FloatVector::new(values));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::execution::PushExecutor;
+
+    fn run(program: &str, seed: u64) -> PushState {
+        let mut executor = PushExecutor::new();
+        executor.initialize();
+        seed_instructions(&mut executor.instruction_set, seed);
+        executor.load(program.to_string());
+        while !executor.step() {}
+        executor.push_state
+    }
+
+    #[test]
+    fn seeded_instructions_repeat_their_values() {
+        let program = "( INTEGER.RAND FLOAT.RAND 0.5 16 BOOLVECTOR.RAND 1.0 0.0 8 FLOATVECTOR.RAND -5 5 8 INTVECTOR.RAND )";
+        let (a, b) = (run(program, 7), run(program, 7));
+        assert_eq!(a.int_stack.get(0), b.int_stack.get(0));
+        assert_eq!(a.float_stack.get(0), b.float_stack.get(0));
+        assert_eq!(a.bool_vector_stack.get(0).unwrap().values, b.bool_vector_stack.get(0).unwrap().values);
+        assert_eq!(a.float_vector_stack.get(0).unwrap().values, b.float_vector_stack.get(0).unwrap().values);
+        assert_eq!(a.int_vector_stack.get(0).unwrap().values.len(), 8);
+        let c = run(program, 8);
+        assert_ne!(a.bool_vector_stack.get(0).unwrap().values, c.bool_vector_stack.get(0).unwrap().values);
+    }
+}
@@ -0,0 +1,93 @@
+// Replication Module
+// Copies of a running node. A REPLICATE request asks the registry for
+// the node ids in use and spawns a headless replica with the first free
+// id, optionally with the learned state of the node.
+
+use std::env;
+use std::fs::File;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+use log::info;
+
+use crate::node::args::NodeArgs;
+use crate::node::channel::Request;
+use crate::node::checkpoint::Checkpoint;
+use crate::node::execution::PushExecutor;
+use crate::node::message::Message;
+use crate::node::network::{self, NodeInfo};
+use crate::node::source::Source;
+use crate::shared::msg::{MessageCommand, MessageType, PAYLOAD_OFFSET};
+
+/// Replicas run without terminal interface, the binary is expected
+/// next to the binary of the node
+const HEADLESS_BIN: &str = "phtm_headless";
+
+/// REPLICATE request that waits for the node table of the registry to
+/// pick a fresh node id. It is acknowledged once the replica runs, the
+/// ACK carries the id of the replica.
+pub struct Replication {
+    pub request: Request,
+    pub with_state: bool,
+    /// Id of the node table request to the registry
+    pub registry_request: u16,
+}
+
+impl Replication {
+    /// The payload flag asks for a replica with the learned state
+    pub fn new(request: Request, registry_request: u16) -> Self {
+        let msg = &request.msg;
+        let with_state = msg.data.len() >= PAYLOAD_OFFSET + 2 && msg.get_prop(&PAYLOAD_OFFSET) != 0;
+        Self {
+            request,
+            with_state,
+            registry_request,
+        }
+    }
+
+    /// Retries of a request have the id of the request
+    pub fn is_retry(&self, request: &Request) -> bool {
+        self.request.sender == request.sender && self.request.msg.get_id() == request.msg.get_id()
+    }
+}
+
+pub fn is_request(msg: &Message) -> bool {
+    msg.get_type() == Some(MessageType::NETWORK) && msg.get_cmd() == Some(MessageCommand::REPLICATE)
+}
+
+/// Spawns a headless replica running the program of the code stack
+/// with the first node id that is not in the registry. With state the
+/// replica restores a temporary checkpoint of the graph and bindings
+/// and deletes it. The replica logs to a file in the temporary
+/// directory. Returns the id and process of the replica.
+pub fn replicate(
+    executor: &PushExecutor,
+    node_args: &NodeArgs,
+    nodes: &[NodeInfo],
+    with_state: bool,
+) -> Result<(u16, Child), String> {
+    let node_id = network::free_node_id(nodes).ok_or_else(|| "No free node id".to_string())?;
+    let program = Source::from_code_stack(&executor.push_state);
+    let mut args = node_args.replica_args(node_id, program);
+    if with_state {
+        let path = env::temp_dir().join(format!("phtm_{:05}.chk", node_id));
+        Checkpoint::from_state(&executor.push_state)
+            .save(&path)
+            .map_err(|e| format!("Failed to save checkpoint: {}", e))?;
+        args.push("--restore-once".to_string());
+        args.push(path.to_string_lossy().to_string());
+    }
+    let log_path = env::temp_dir().join(format!("phtm_{:05}.log", node_id));
+    let log = File::create(&log_path).map_err(|e| format!("Failed to create {}: {}", log_path.display(), e))?;
+    let err_log = log.try_clone().map_err(|e| format!("Failed to create {}: {}", log_path.display(), e))?;
+    let bin = Path::new(&node_args.bin).with_file_name(HEADLESS_BIN);
+    let child = Command::new(&bin)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(log)
+        .stderr(err_log)
+        .spawn()
+        .map_err(|e| format!("Failed to spawn {}: {}", bin.display(), e))?;
+    info!("Replica {} logs to {}", node_id, log_path.display());
+    Ok((node_id, child))
+}
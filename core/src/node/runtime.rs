@@ -0,0 +1,1118 @@
+// Runtime Module
+// Async node runtime shared by the headless node and the TUI. It owns
+// the executor and the lifecycle, takes commands over a channel and
+// orchestrates the bus, the input queue, the debug endpoint and the
+// session recording. Attached front ends follow the node through
+// snapshots.
+
+use std::process::Child;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::task::JoinHandle;
+
+use crate::node::args::NodeArgs;
+use crate::node::bus::{self, Bus};
+use crate::node::channel::{self, ChannelEvent, Request};
+use crate::node::checkpoint::{self, Checkpoint};
+use crate::node::control::{Event, State};
+use crate::node::debug::{self, Breakpoints, DebugServer};
+use crate::node::execution::PushExecutor;
+use crate::node::history::History;
+use crate::node::metrics::MetricsTracker;
+use crate::node::network::{self, NodeInfo};
+use crate::node::queue::{self, InputQueue, QueueConfig};
+use crate::node::random;
+use crate::node::replication::{self, Replication};
+use crate::node::sdr;
+use crate::node::session::{self, Recorder, Session};
+use crate::node::snapshot::{self, Snapshot};
+use crate::node::source::Source;
+use crate::pushr::push::io::PushMessage;
+use crate::pushr::push::item::Item;
+use crate::pushr::push::vector::{BoolVector, IntVector};
+use crate::shared::msg::{MessageCommand, NodeState, NodeType};
+use crate::shared::msg::PAYLOAD_OFFSET;
+
+/// Host of the message broker
+pub const DEFAULT_BROKER: &str = "tcp://localhost";
+
+/// Commands buffered between the front end and the runtime
+const COMMAND_CAPACITY: usize = 32;
+
+/// Minimum time between two snapshots while the program runs
+const SNAPSHOT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Upper bound of the steps between two checks of commands and queues
+pub const MAX_STEP_BUDGET: usize = 100_000;
+
+/// Wait for commands while the program is paused
+const IDLE_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Instruction at which a run pauses
+#[derive(Clone, Debug, PartialEq)]
+pub enum RunTarget {
+    /// Next breakpoint of the program (BP0, BP1, ...)
+    Breakpoint,
+    /// Next execution of the instruction with the name
+    Instruction(String),
+}
+
+impl RunTarget {
+    /// Checks the label of the next item of the execution stack
+    pub fn reached(&self, next: &str) -> bool {
+        match self {
+            RunTarget::Breakpoint => {
+                next.len() > 2 && next.starts_with("BP") && next[2..].chars().all(|c| c.is_ascii_digit())
+            }
+            RunTarget::Instruction(name) => next == name,
+        }
+    }
+}
+
+/// Commands of a front end or of the control channel
+#[derive(Debug)]
+pub enum RuntimeCommand {
+    /// Executes steps while paused
+    Step(usize),
+    Pause,
+    Resume,
+    /// Runs until the target is the next instruction, then pauses
+    RunUntil(RunTarget),
+    /// Sets the number of steps between two checks of commands and
+    /// queues
+    SetStepBudget(usize),
+    /// Adds the synapses of the graph node to the snapshots
+    Inspect(Option<usize>),
+    /// Sets or clears the breakpoint at the source line
+    ToggleBreakpoint(usize),
+    /// Sets or clears the breakpoint at the instruction with the name
+    ToggleInstructionBreakpoint(String),
+    /// Reloads the program and discards the learned state
+    Reset,
+    /// Pushes an SDR to the input stack, inputs wait while the input
+    /// stack is full or the memory graph is not created yet
+    Inject(Vec<bool>),
+    /// Injects like Inject and replies whether the input was queued
+    InjectAcked(Vec<bool>, oneshot::Sender<bool>),
+    SetParameter(String, String),
+    /// Replaces the program code and resets the node
+    LoadProgram(String),
+    /// Restores the graph and bindings of a checkpoint
+    LoadCheckpoint(Checkpoint),
+    /// Replies with a checkpoint of the current state
+    Checkpoint(oneshot::Sender<Checkpoint>),
+    /// Replies with the recorded session
+    Session(oneshot::Sender<Session>),
+    /// Leaves the current broker and connects to the given host
+    ConnectBus(String),
+    /// Shutdown with optional checkpoint path
+    Shutdown(Option<String>),
+}
+
+#[derive(Clone, Debug)]
+pub struct RuntimeConfig {
+    pub queue: QueueConfig,
+    /// Connects to the broker, offline nodes discard their outputs
+    pub connect: bool,
+    /// Host of the broker
+    pub broker: String,
+    /// Starts paused before the memory graph is created
+    pub start_paused: bool,
+    /// Records the changes of the state, seeds unseeded nodes
+    pub record: bool,
+    /// Session whose changes are applied at their steps
+    pub replay: Option<Session>,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            queue: QueueConfig::default(),
+            connect: true,
+            broker: DEFAULT_BROKER.to_string(),
+            start_paused: false,
+            record: false,
+            replay: None,
+        }
+    }
+}
+
+/// Front end side of a runtime
+#[derive(Clone)]
+pub struct RuntimeHandle {
+    pub commands: mpsc::Sender<RuntimeCommand>,
+    pub snapshots: watch::Receiver<Snapshot>,
+}
+
+/// Creates an executor with the node program
+pub fn load_executor(node_args: &NodeArgs) -> PushExecutor {
+    let mut executor = PushExecutor::new();
+    executor.initialize();
+    if let Some(seed) = node_args.seed {
+        random::seed_instructions(&mut executor.instruction_set, seed);
+    }
+    // Load program from input
+    executor.load(Source::read_debug_code(node_args.program.clone()));
+    // Inject interpreter binary
+    executor.push_state.name_bindings.insert("BIN".to_string(), Item::id(node_args.bin.clone()));
+    executor
+}
+
+/// Hash of the transpiled program, which matches the hash of replicas
+pub fn program_hash(executor: &PushExecutor) -> u64 {
+    Source::program_hash(&Source::from_code_stack(&executor.push_state))
+}
+
+/// Reads the learning flag of the program, learning is on if the
+/// program has no LEARNING_ENABLED binding
+fn learning_enabled(executor: &PushExecutor) -> bool {
+    sdr::bool_binding(&executor.push_state, "LEARNING_ENABLED").unwrap_or(true)
+}
+
+pub struct NodeRuntime {
+    node_args: NodeArgs,
+    node_info: NodeInfo,
+    config: RuntimeConfig,
+    executor: PushExecutor,
+    state: State,
+    paused: bool,
+    /// Steps requested while paused
+    pending_steps: usize,
+    /// Pauses the run when reached
+    run_target: Option<RunTarget>,
+    /// Graph node selected by the front end
+    inspected: Option<usize>,
+    breakpoints: Breakpoints,
+    steps: u64,
+    checkpoint: Option<String>,
+    bus: Option<Bus>,
+    debug: Option<DebugServer>,
+    replication: Option<Replication>,
+    replicas: Vec<Child>,
+    /// Spatial pooler quality of the processed inputs
+    metrics: MetricsTracker,
+    /// Learning statistics of the recent inputs
+    history: History,
+    inputs: InputQueue,
+    recorder: Recorder,
+    commands: mpsc::Receiver<RuntimeCommand>,
+    snapshots: watch::Sender<Snapshot>,
+    last_snapshot: Instant,
+    /// Front ends see the effect of a command immediately
+    snapshot_due: bool,
+}
+
+impl NodeRuntime {
+    /// Loads the program of the node. The bus is connected when the
+    /// runtime starts.
+    pub fn new(mut node_args: NodeArgs, config: RuntimeConfig) -> (Self, RuntimeHandle) {
+        // Replays draw the random values of the recording
+        match &config.replay {
+            Some(session) => node_args.seed = Some(session.seed),
+            None if config.record && node_args.seed.is_none() => node_args.seed = Some(rand::random()),
+            None => (),
+        }
+        let executor = load_executor(&node_args);
+        let breakpoints = Breakpoints::new(&node_args.program);
+        let history = History::new(node_args.node_type);
+        let node_info = NodeInfo {
+            node_id: node_args.node_id,
+            node_type: node_args.node_type,
+            program_hash: program_hash(&executor),
+            keys: node_args.keys.clone(),
+        };
+        let session = match (config.record, node_args.seed) {
+            (true, Some(seed)) => Some(Session::new(node_info.program_hash, seed)),
+            _ => None,
+        };
+        let recorder = Recorder::new(session, config.replay.as_ref());
+        let (command_tx, command_rx) = mpsc::channel(COMMAND_CAPACITY);
+        let (snapshot_tx, snapshot_rx) = watch::channel(Snapshot::default());
+        let runtime = Self {
+            checkpoint: node_args.checkpoint.clone(),
+            node_args,
+            node_info,
+            paused: config.start_paused,
+            inputs: InputQueue::new(config.queue.inbound_capacity),
+            config,
+            executor,
+            state: State::Initializing,
+            pending_steps: 0,
+            run_target: None,
+            inspected: None,
+            breakpoints,
+            steps: 0,
+            bus: None,
+            debug: None,
+            replication: None,
+            replicas: vec![],
+            metrics: MetricsTracker::default(),
+            history,
+            recorder,
+            commands: command_rx,
+            snapshots: snapshot_tx,
+            last_snapshot: Instant::now(),
+            snapshot_due: true,
+        };
+        let handle = RuntimeHandle {
+            commands: command_tx,
+            snapshots: snapshot_rx,
+        };
+        (runtime, handle)
+    }
+
+    /// Starts a runtime on the tokio runtime. The task returns the
+    /// final state of the node.
+    pub fn spawn(node_args: NodeArgs, config: RuntimeConfig) -> (RuntimeHandle, JoinHandle<State>) {
+        let (runtime, handle) = Self::new(node_args, config);
+        (handle, tokio::spawn(runtime.run()))
+    }
+
+    /// Runs the node until it is shut down or fails. Each iteration
+    /// applies commands, moves inbound messages to the input stack,
+    /// executes a batch of steps and publishes outbound messages.
+    pub async fn run(mut self) -> State {
+        if self.config.connect {
+            self.connect_bus(&self.config.broker.clone());
+        }
+        if let Some(endpoint) = &self.node_args.debug {
+            match DebugServer::bind(endpoint) {
+                Ok(server) => self.debug = Some(server),
+                Err(e) => warn!("{}", e),
+            }
+        }
+        info!("Creating memory graph");
+        loop {
+            // Reap exited replicas
+            self.replicas.retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+
+            // Commands of the front end and the control channel
+            let mut commands = vec![];
+            loop {
+                match self.commands.try_recv() {
+                    Ok(command) => commands.push(command),
+                    Err(mpsc::error::TryRecvError::Empty) => break,
+                    // All front ends are gone
+                    Err(mpsc::error::TryRecvError::Disconnected) => {
+                        commands.push(RuntimeCommand::Shutdown(None));
+                        break;
+                    }
+                }
+            }
+            commands.extend(self.poll_control());
+            self.serve_debug();
+            if commands.into_iter().any(|command| self.apply(command)) {
+                break;
+            }
+
+            // Inbound messages, bounded by the input stack
+            self.feed_inbound();
+            self.replay_due();
+
+            // Interpreter steps, waiting while the output stack is full
+            if !self.execute() {
+                break;
+            }
+
+            // Outbound messages, independent of inbound messages
+            self.publish_outbound();
+            self.update_snapshot();
+
+            if self.paused && self.pending_steps == 0 {
+                let command = tokio::select! {
+                    command = self.commands.recv() => command,
+                    _ = tokio::time::sleep(IDLE_INTERVAL) => None,
+                };
+                if let Some(command) = command {
+                    if self.apply(command) {
+                        break;
+                    }
+                }
+            } else {
+                tokio::task::yield_now().await;
+            }
+        }
+        self.shutdown();
+        self.state
+    }
+
+    /// Connects to the broker and announces the node to the registry
+    fn connect_bus(&mut self, broker: &str) {
+        match Bus::connect(broker, self.node_info.node_id, &self.config.queue) {
+            Ok(mut bus) => {
+                bus.announce(&self.node_info, &self.state);
+                self.bus = Some(bus);
+            }
+            Err(e) => warn!("{}, running offline", e),
+        }
+    }
+
+    /// Leaves the network and closes the sockets
+    fn disconnect_bus(&mut self) {
+        if let Some(bus) = self.bus.take() {
+            bus.leave(&self.node_info);
+        }
+        self.replication = None;
+    }
+
+    /// Reloads the program, the memory graph is created again
+    fn reload(&mut self) {
+        if self.state != State::Initializing {
+            self.update_state(Event::Reset);
+        }
+        if let Some(r) = self.replication.take() {
+            if let Some(bus) = self.bus.as_mut() {
+                bus.reply(&r.request, Err("Node was reset".to_string()));
+            }
+        }
+        self.executor = load_executor(&self.node_args);
+        self.inputs.clear();
+        self.breakpoints.restart();
+        self.metrics = MetricsTracker::default();
+        self.history = History::new(self.node_args.node_type);
+        self.pending_steps = 0;
+    }
+
+    /// Applies an event to the lifecycle state and publishes changes
+    fn update_state(&mut self, event: Event) {
+        let previous = self.state.clone();
+        self.state = previous.clone().next(event);
+        if self.state != previous {
+            info!("State: {}", self.state);
+            if let Some(bus) = &self.bus {
+                bus.publish(&self.state.to_message(self.node_info.node_id));
+            }
+        }
+    }
+
+    /// Applies a command. Returns true if the node shuts down.
+    fn apply(&mut self, command: RuntimeCommand) -> bool {
+        self.snapshot_due = true;
+        match command {
+            RuntimeCommand::Step(n) => self.pending_steps += n,
+            RuntimeCommand::Pause => {
+                info!("Paused");
+                self.paused = true;
+                self.run_target = None;
+            }
+            RuntimeCommand::Resume => {
+                info!("Resumed");
+                self.paused = false;
+                self.pending_steps = 0;
+                self.run_target = None;
+            }
+            RuntimeCommand::RunUntil(target) => {
+                info!("Running to {:?}", target);
+                self.paused = false;
+                self.pending_steps = 0;
+                self.run_target = Some(target);
+            }
+            RuntimeCommand::Inspect(id) => self.inspected = id,
+            RuntimeCommand::ToggleBreakpoint(line) => self.breakpoints.toggle_line(line),
+            RuntimeCommand::ToggleInstructionBreakpoint(name) => self.breakpoints.toggle_instruction(name),
+            RuntimeCommand::SetStepBudget(budget) => {
+                self.config.queue.steps_per_iteration = budget.clamp(1, MAX_STEP_BUDGET);
+                info!("{} steps per iteration", self.config.queue.steps_per_iteration);
+            }
+            RuntimeCommand::Reset => {
+                info!("Resetting memory graph");
+                self.record(session::Action::Reset);
+                self.reload();
+            }
+            RuntimeCommand::Inject(input) => {
+                self.inputs.queue(input, &self.executor, self.node_info.node_type);
+            }
+            RuntimeCommand::InjectAcked(input, reply) => {
+                let queued = self.inputs.queue(input, &self.executor, self.node_info.node_type);
+                let _ = reply.send(queued);
+            }
+            RuntimeCommand::SetParameter(name, value) => match self.executor.set_parameter(&name, &value) {
+                Ok(()) => {
+                    info!("{} = {}", name, value);
+                    self.update_learning();
+                    self.record(session::Action::SetParameter { name, value });
+                }
+                Err(e) => warn!("Failed to set {}: {}", name, e),
+            },
+            RuntimeCommand::LoadProgram(code) => {
+                info!("Loading program");
+                self.record(session::Action::LoadProgram { code: code.clone() });
+                self.node_args.program = code;
+                self.breakpoints.load(&self.node_args.program);
+                self.reload();
+                // Registry learns the new hash with the next heartbeat
+                self.node_info.program_hash = program_hash(&self.executor);
+            }
+            RuntimeCommand::LoadCheckpoint(checkpoint) => match checkpoint.apply(&mut self.executor.push_state) {
+                Ok(()) => {
+                    info!("Checkpoint restored");
+                    self.update_learning();
+                    self.record(session::Action::LoadCheckpoint {
+                        checkpoint: checkpoint.to_json(),
+                    });
+                }
+                Err(e) => warn!("Failed to restore checkpoint ({})", e),
+            },
+            RuntimeCommand::Checkpoint(reply) => {
+                let _ = reply.send(Checkpoint::from_state(&self.executor.push_state));
+            }
+            RuntimeCommand::Session(reply) => {
+                // Not recording nodes drop the reply
+                if let Some(session) = self.recorder.session(self.steps, &self.executor.push_state) {
+                    let _ = reply.send(session);
+                }
+            }
+            RuntimeCommand::ConnectBus(broker) => {
+                self.disconnect_bus();
+                self.connect_bus(&broker);
+            }
+            RuntimeCommand::Shutdown(path) => {
+                if path.is_some() {
+                    self.checkpoint = path;
+                }
+                info!("Shutdown requested");
+                return true;
+            }
+        }
+        false
+    }
+
+    fn update_learning(&mut self) {
+        // The binding is read once the memory graph is created
+        if self.state == State::Initializing {
+            return;
+        }
+        let learning = learning_enabled(&self.executor);
+        if learning != self.state.is_learning() {
+            self.update_state(Event::LearningChanged(learning));
+        }
+    }
+
+    /// Pushes a replayed input to the input stack
+    fn receive(&mut self, input: PushMessage) {
+        self.recorder.record_input(self.steps, &input);
+        if self.inputs.receive(&mut self.executor, input) {
+            self.update_state(Event::InputReceived);
+        }
+    }
+
+    /// Moves injected inputs and inbound messages to the input stack
+    fn feed_inbound(&mut self) {
+        // Inputs wait in the queue until the memory graph exists
+        if self.state == State::Initializing {
+            return;
+        }
+        let inbound = self.bus.as_ref().map(|bus| &bus.inbound);
+        let received = self
+            .inputs
+            .feed(&mut self.executor, &self.node_args.keys, self.node_info.node_type, inbound);
+        for input in received.iter() {
+            self.recorder.record_input(self.steps, input);
+            self.update_state(Event::InputReceived);
+        }
+    }
+
+    fn record(&mut self, action: session::Action) {
+        self.recorder.record(self.steps, action);
+    }
+
+    /// Applies the changes of the replayed session that were recorded
+    /// at the current step
+    fn replay_due(&mut self) {
+        while let Some(entry) = self.recorder.next_due(self.steps) {
+            match entry.action {
+                session::Action::Input { header, sdr } => match session::from_bits(&sdr) {
+                    Ok(sdr) => self.receive(PushMessage::new(IntVector::new(header), BoolVector::new(sdr))),
+                    Err(e) => warn!("Replay skipped input at step {}: {}", entry.step, e),
+                },
+                session::Action::SetParameter { name, value } => {
+                    self.apply(RuntimeCommand::SetParameter(name, value));
+                }
+                session::Action::Reset => {
+                    self.apply(RuntimeCommand::Reset);
+                }
+                session::Action::LoadProgram { code } => {
+                    self.apply(RuntimeCommand::LoadProgram(code));
+                }
+                session::Action::LoadCheckpoint { checkpoint } => match Checkpoint::parse(&checkpoint) {
+                    Ok(checkpoint) => {
+                        self.apply(RuntimeCommand::LoadCheckpoint(checkpoint));
+                    }
+                    Err(e) => warn!("Replay skipped checkpoint at step {}: {}", entry.step, e),
+                },
+            }
+        }
+        self.recorder.check_replay_end(self.steps, &self.executor.push_state);
+    }
+
+    /// Executes up to a batch of steps, while paused only the requested
+    /// ones. Returns false if the execution stack ran empty.
+    fn execute(&mut self) -> bool {
+        let cycle_end = sdr::cycle_end(self.node_info.node_type);
+        let graph_created = sdr::graph_created(self.node_info.node_type);
+        for _ in 0..self.config.queue.steps_per_iteration {
+            if (self.paused && self.pending_steps == 0) || queue::output_full(&self.executor) {
+                break;
+            }
+            self.replay_due();
+            self.pending_steps = self.pending_steps.saturating_sub(1);
+            self.steps += 1;
+            if self.executor.step() {
+                warn!("Empty Execution Stack");
+                self.update_state(Event::Failed("Empty execution stack".to_string()));
+                return false;
+            }
+            let next = self.executor.push_state.exec_stack.get(0).map(|i| i.to_string());
+            if self.state == State::Initializing && next.as_deref() == Some(graph_created) {
+                self.graph_created();
+            }
+            if self.state.node_state() == NodeState::COMPUTING && self.executor.push_state.input_stack.size() == 0 {
+                self.update_state(Event::InputProcessed);
+            }
+            // Output SDR and spatial pooler quality after each input cycle
+            if cycle_end.is_some() && next.as_deref() == cycle_end {
+                // Programs pass the end of a cycle without input as well
+                let input = self.inputs.processed();
+                if let Some(input) = &input {
+                    self.history.record(&self.executor.push_state, input, self.steps);
+                }
+                self.publish_output(input.as_deref());
+            }
+            if let Some(next) = &next {
+                if self.breakpoints.at_instruction(next) {
+                    info!("Paused at {}", next);
+                    self.pause_at_breakpoint();
+                    break;
+                }
+            }
+            if let (Some(target), Some(next)) = (&self.run_target, &next) {
+                if target.reached(next) {
+                    info!("Paused at {}", next);
+                    self.paused = true;
+                    self.run_target = None;
+                    self.snapshot_due = true;
+                    break;
+                }
+            }
+            if let Some(line) = self.breakpoints.at_line(&self.executor.push_state) {
+                info!("Paused at line {}", line + 1);
+                self.pause_at_breakpoint();
+                break;
+            }
+        }
+        true
+    }
+
+    fn pause_at_breakpoint(&mut self) {
+        self.paused = true;
+        self.pending_steps = 0;
+        self.run_target = None;
+        self.snapshot_due = true;
+    }
+
+    /// Restores the checkpoint of the node once the memory graph exists
+    fn graph_created(&mut self) {
+        info!("Memory graph created");
+        if let Some(path) = &self.node_args.restore {
+            checkpoint::restore(path, self.node_args.remove_restored, &mut self.executor.push_state);
+        }
+        let learning = learning_enabled(&self.executor);
+        self.update_state(Event::GraphInitialized { learning });
+    }
+
+    /// Publishes the output SDR at the end of an input cycle and updates
+    /// the spatial pooler metrics with the processed input. Offline
+    /// nodes only update the metrics.
+    fn publish_output(&mut self, input: Option<&[bool]>) {
+        let output = match sdr::output(&self.executor.push_state, self.node_args.node_type) {
+            Ok(output) => output,
+            Err(e) => {
+                warn!("Failed to read output: {}", e);
+                return;
+            }
+        };
+        let metrics = input
+            .filter(|_| self.node_args.node_type == NodeType::SPATIAL_POOLER)
+            .map(|input| self.metrics.update(input, &output));
+        let bus = match self.bus.as_ref() {
+            Some(bus) => bus,
+            None => return,
+        };
+        if let Some(key) = self.node_args.output_key {
+            bus.publish(&sdr::to_message(&output, key));
+        }
+        if let Some(metrics) = metrics {
+            bus.publish(&metrics.to_message(self.node_args.node_id));
+        }
+    }
+
+    /// Publishes a batch of outbound messages, offline nodes discard
+    /// them. Returns false if nothing was sent.
+    fn publish_outbound(&mut self) -> bool {
+        let outbound = queue::drain_outbound(&mut self.executor, &self.config.queue);
+        if let Some(bus) = self.bus.as_ref() {
+            for m in outbound.iter() {
+                bus.publish(m);
+            }
+        }
+        !outbound.is_empty()
+    }
+
+    /// Sends heartbeats, retries unacknowledged requests and handles
+    /// one event of the control channel. Returns the command of a
+    /// lifecycle request.
+    fn poll_control(&mut self) -> Option<RuntimeCommand> {
+        let bus = self.bus.as_mut()?;
+        bus.heartbeat(&self.node_info);
+        let failed = bus.retry();
+        if self.replication.as_ref().is_some_and(|r| failed.contains(&r.registry_request)) {
+            self.finish_replication(Err("Registry not reachable".to_string()));
+        }
+        let registry_request = self.replication.as_ref().map(|r| r.registry_request);
+        match self.bus.as_mut()?.poll()? {
+            ChannelEvent::Request(request) if replication::is_request(&request.msg) => {
+                self.request_replication(request);
+                None
+            }
+            ChannelEvent::Request(request) => self.handle_request(request),
+            ChannelEvent::Acked(id, reply) if registry_request == Some(id) => {
+                let nodes = NodeInfo::list_from_payload(reply.data.get(PAYLOAD_OFFSET..).unwrap_or(&[]));
+                self.replicate(&nodes);
+                None
+            }
+            ChannelEvent::Nacked(id, reason) if registry_request == Some(id) => {
+                self.finish_replication(Err(format!("Registry rejected query: {}", reason)));
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Applies a request of the control channel and replies to it
+    fn handle_request(&mut self, request: Request) -> Option<RuntimeCommand> {
+        let result = bus::handle_request(&mut self.executor, &mut self.recorder, self.steps, &request.msg);
+        if let Err(e) = &result {
+            warn!("Rejected request {}: {}", request.msg.get_topic(), e);
+        }
+        if let Some(bus) = self.bus.as_mut() {
+            bus.reply(&request, result.as_ref().map(|_| vec![]).map_err(|e| e.clone()));
+        }
+        self.update_learning();
+        result.ok().flatten()
+    }
+
+    /// Asks the registry for the node ids in use, the REPLICATE request
+    /// is answered when the replica runs
+    fn request_replication(&mut self, request: Request) {
+        let bus = match self.bus.as_mut() {
+            Some(bus) => bus,
+            None => return,
+        };
+        let result = match &self.replication {
+            // Retry of the running replication
+            Some(r) if r.is_retry(&request) => return,
+            Some(_) => Err("Replication in progress".to_string()),
+            // Replicas copy the created memory graph
+            None if !matches!(self.state, State::Ready { .. } | State::Computing { .. }) => {
+                Err(format!("Cannot replicate while {:?}", self.state.node_state()))
+            }
+            None => match bus.request(network::request(MessageCommand::READ)) {
+                Ok(registry_request) => {
+                    self.replication = Some(Replication::new(request, registry_request));
+                    self.update_state(Event::ReplicationRequested);
+                    return;
+                }
+                Err(e) => Err(format!("Failed to query registry: {}", e)),
+            },
+        };
+        bus.reply(&request, result);
+    }
+
+    /// Spawns the replica with the node table of the registry
+    fn replicate(&mut self, nodes: &[NodeInfo]) {
+        let with_state = match &self.replication {
+            Some(r) => r.with_state,
+            None => return,
+        };
+        let result = match replication::replicate(&self.executor, &self.node_args, nodes, with_state) {
+            Ok((replica_id, child)) => {
+                info!("Replicated to {}", channel::node_identity(replica_id));
+                self.replicas.push(child);
+                Ok(replica_id.to_be_bytes().to_vec())
+            }
+            Err(e) => {
+                warn!("Replication failed: {}", e);
+                Err(e)
+            }
+        };
+        self.finish_replication(result);
+    }
+
+    /// Answers the REPLICATE request
+    fn finish_replication(&mut self, result: Result<Vec<u8>, String>) {
+        if let Some(r) = self.replication.take() {
+            if let Some(bus) = self.bus.as_mut() {
+                bus.reply(&r.request, result);
+            }
+            self.update_state(Event::ReplicationFinished);
+        }
+    }
+
+    /// Answers a request of the debug protocol with the snapshot after
+    /// the command of the request is applied
+    fn serve_debug(&mut self) {
+        let request = match self.debug.as_ref().and_then(|server| server.poll()) {
+            Some(request) => request,
+            None => return,
+        };
+        let result = debug::parse_request(&request).map(|command| {
+            if let Some(command) = command {
+                self.apply(command);
+            }
+        });
+        let snapshot = self.snapshot();
+        if let Some(server) = self.debug.as_ref() {
+            server.reply(&request, result.map(|_| &snapshot));
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            node_id: self.node_info.node_id,
+            node_type: self.node_info.node_type,
+            state: self.state.clone(),
+            paused: self.paused,
+            steps: self.steps,
+            step_budget: self.config.queue.steps_per_iteration,
+            stacks: snapshot::capture_stacks(&self.executor.push_state),
+            bindings: snapshot::capture_bindings(&self.executor.push_state),
+            line: self.breakpoints.locate(&self.executor.push_state),
+            breakpoints: self.breakpoints.lines(),
+            instruction_breakpoints: self.breakpoints.instructions(),
+            graph: snapshot::capture_graph(&self.executor.push_state),
+            model: snapshot::capture_model(&self.executor.push_state),
+            inspection: self
+                .inspected
+                .and_then(|id| snapshot::capture_inspection(&self.executor.push_state, id)),
+            history: self.history.samples(),
+        }
+    }
+
+    /// Sends a snapshot to attached front ends, at most one per
+    /// interval while the program runs
+    fn update_snapshot(&mut self) {
+        if self.snapshots.receiver_count() == 0
+            || !(self.snapshot_due || self.last_snapshot.elapsed() >= SNAPSHOT_INTERVAL)
+        {
+            return;
+        }
+        self.last_snapshot = Instant::now();
+        self.snapshot_due = false;
+        self.snapshots.send_replace(self.snapshot());
+    }
+
+    /// Flushes the outbound messages, saves the checkpoint and leaves
+    /// the network
+    fn shutdown(&mut self) {
+        self.update_state(Event::ShutdownRequested);
+        while self.publish_outbound() {}
+        info!("Outbound messages flushed");
+
+        if let Some(path) = &self.checkpoint {
+            checkpoint::save(path, &self.executor.push_state);
+        }
+
+        self.update_state(Event::Drained);
+        self.disconnect_bus();
+        self.snapshot_due = true;
+        self.update_snapshot();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    use crate::pushr::push::item::PushType;
+
+    fn offline_runtime(program: &str) -> (NodeRuntime, RuntimeHandle) {
+        let args = vec!["phtm".to_string(), program.to_string(), "--id".to_string(), "3".to_string()];
+        let config = RuntimeConfig {
+            connect: false,
+            start_paused: true,
+            ..RuntimeConfig::default()
+        };
+        NodeRuntime::new(NodeArgs::parse(&args).unwrap(), config)
+    }
+
+    #[test]
+    fn commands_control_paused_execution() {
+        let (mut runtime, _handle) = offline_runtime("( 1 2 BP1 3 INTEGER.+ EXEC.Y )");
+        assert!(runtime.execute());
+        assert_eq!(runtime.steps, 0);
+        runtime.apply(RuntimeCommand::Step(3));
+        assert!(runtime.execute());
+        assert_eq!(runtime.steps, 3);
+        assert_eq!(runtime.pending_steps, 0);
+        // Graph creation ends before BP1
+        assert_eq!(runtime.state, State::Ready { learning: true });
+        runtime.apply(RuntimeCommand::Step(3));
+        runtime.execute();
+        assert_eq!(runtime.executor.push_state.int_stack.get(0), Some(&5));
+        runtime.apply(RuntimeCommand::Reset);
+        assert_eq!(runtime.state, State::Initializing);
+        assert_eq!(runtime.executor.push_state.int_stack.size(), 0);
+        assert!(runtime.apply(RuntimeCommand::Shutdown(None)));
+    }
+
+    #[test]
+    fn run_pauses_at_target() {
+        let (mut runtime, _handle) = offline_runtime("( 1 BP1 2 INTEGER.+ BP2 EXEC.Y )");
+        runtime.apply(RuntimeCommand::RunUntil(RunTarget::Breakpoint));
+        assert!(runtime.execute());
+        assert!(runtime.paused);
+        assert_eq!(runtime.executor.push_state.exec_stack.get(0).map(|i| i.to_string()), Some("BP1".to_string()));
+        runtime.apply(RuntimeCommand::RunUntil(RunTarget::Instruction("INTEGER.+".to_string())));
+        runtime.execute();
+        assert!(runtime.paused);
+        assert_eq!(runtime.executor.push_state.int_stack.size(), 2);
+        runtime.apply(RuntimeCommand::SetStepBudget(0));
+        assert_eq!(runtime.config.queue.steps_per_iteration, 1);
+        assert!(RunTarget::Breakpoint.reached("BP10"));
+        assert!(!RunTarget::Breakpoint.reached("BPX"));
+    }
+
+    #[test]
+    fn run_pauses_at_source_breakpoint() {
+        let (mut runtime, _handle) = offline_runtime("(\n1 2 # Literals\nBP1 3 INTEGER.+\nEXEC.Y\n)");
+        runtime.apply(RuntimeCommand::ToggleBreakpoint(2));
+        runtime.apply(RuntimeCommand::Resume);
+        assert!(runtime.execute());
+        assert!(runtime.paused);
+        assert_eq!(runtime.executor.push_state.int_stack.size(), 2);
+        let snapshot = runtime.snapshot();
+        assert_eq!(snapshot.line, Some(2));
+        assert_eq!(snapshot.breakpoints, vec![2]);
+        runtime.apply(RuntimeCommand::ToggleBreakpoint(2));
+        assert!(runtime.snapshot().breakpoints.is_empty());
+    }
+
+    #[test]
+    fn run_pauses_at_each_instruction_breakpoint() {
+        let (mut runtime, _handle) = offline_runtime("( 1 BP1 EXEC.Y ( 2 INTEGER.+ ) )");
+        runtime.apply(RuntimeCommand::ToggleInstructionBreakpoint("INTEGER.+".to_string()));
+        // Setting a breakpoint does not resume the node
+        assert!(runtime.paused);
+        runtime.apply(RuntimeCommand::Resume);
+        runtime.execute();
+        assert!(runtime.paused);
+        assert_eq!(runtime.executor.push_state.int_stack.size(), 2);
+        runtime.apply(RuntimeCommand::Resume);
+        runtime.execute();
+        assert!(runtime.paused);
+        assert_eq!(runtime.executor.push_state.int_stack.size(), 2);
+        assert_eq!(runtime.executor.push_state.int_stack.get(1), Some(&3));
+        assert_eq!(runtime.snapshot().instruction_breakpoints, vec!["INTEGER.+".to_string()]);
+        runtime.apply(RuntimeCommand::ToggleInstructionBreakpoint("INTEGER.+".to_string()));
+        assert!(runtime.snapshot().instruction_breakpoints.is_empty());
+    }
+
+    #[test]
+    fn injected_input_is_received() {
+        let (mut runtime, _handle) = offline_runtime("( BP1 EXEC.Y )");
+        runtime.apply(RuntimeCommand::Inject(vec![true, false]));
+        runtime.feed_inbound();
+        // Waits for the memory graph
+        assert_eq!(runtime.executor.push_state.input_stack.size(), 0);
+        runtime.apply(RuntimeCommand::Step(1));
+        runtime.execute();
+        runtime.apply(RuntimeCommand::Inject(vec![true, true]));
+        runtime.feed_inbound();
+        assert_eq!(runtime.executor.push_state.input_stack.size(), 2);
+        assert_eq!(runtime.inputs.inputs().back().unwrap()[..3], [true, true, false]);
+        assert_eq!(runtime.state.node_state(), NodeState::COMPUTING);
+    }
+
+    #[test]
+    fn learning_changed_while_initializing_applies_once_initialized() {
+        let (mut runtime, _handle) = offline_runtime("( FALSE LEARNING_ENABLED BOOLEAN.DEFINE BP0 BP1 EXEC.Y )");
+        run_to(&mut runtime, "BP0");
+        runtime.apply(RuntimeCommand::SetParameter("LEARNING_ENABLED".to_string(), "true".to_string()));
+        assert_eq!(runtime.state, State::Initializing);
+        run_to(&mut runtime, "BP1");
+        assert_eq!(runtime.state, State::Ready { learning: true });
+    }
+
+    /// Offline spatial pooler node with the additional arguments
+    fn spatial_pooler_runtime(args: &[&str]) -> (NodeRuntime, RuntimeHandle) {
+        let args: Vec<String> = ["phtm", include_str!("../core/spatial_pooler.push"), "--type", "sp"]
+            .iter()
+            .chain(args.iter())
+            .map(|a| a.to_string())
+            .collect();
+        NodeRuntime::new(NodeArgs::parse(&args).unwrap(), RuntimeConfig {
+            connect: false,
+            start_paused: true,
+            ..RuntimeConfig::default()
+        })
+    }
+
+    fn run_to(runtime: &mut NodeRuntime, marker: &str) {
+        runtime.apply(RuntimeCommand::RunUntil(RunTarget::Instruction(marker.to_string())));
+        while !runtime.paused {
+            assert!(runtime.execute());
+        }
+    }
+
+    #[test]
+    fn spatial_pooler_is_ready_once_its_columns_exist() {
+        let (mut runtime, _handle) = spatial_pooler_runtime(&[]);
+        run_to(&mut runtime, "BP1");
+        assert_eq!(runtime.state, State::Initializing);
+        let size = sdr::input_size(&runtime.executor.push_state, NodeType::SPATIAL_POOLER).unwrap();
+        runtime.apply(RuntimeCommand::Inject(vec![true; size]));
+        runtime.feed_inbound();
+        assert_eq!(runtime.executor.push_state.input_stack.size(), 0);
+        run_to(&mut runtime, sdr::SP_GRAPH_CREATED);
+        assert_eq!(runtime.state, State::Ready { learning: true });
+        runtime.feed_inbound();
+        assert_eq!(runtime.executor.push_state.input_stack.size(), 1);
+    }
+
+    #[test]
+    fn spatial_pooler_measures_each_processed_input() {
+        let (mut runtime, _handle) = spatial_pooler_runtime(&[]);
+        run_to(&mut runtime, sdr::SP_GRAPH_CREATED);
+        let size = sdr::input_size(&runtime.executor.push_state, NodeType::SPATIAL_POOLER).unwrap();
+        // Both inputs wait on the input stack before the first cycle
+        runtime.apply(RuntimeCommand::Inject((0..size).map(|i| i % 4 == 0).collect()));
+        runtime.apply(RuntimeCommand::Inject((0..size).map(|i| i % 4 == 2).collect()));
+        runtime.feed_inbound();
+        assert_eq!(runtime.inputs.inputs().len(), 2);
+        // Metrics published at the end of each cycle
+        let mut metrics = Vec::new();
+        for _ in 0..2 {
+            run_to(&mut runtime, sdr::SP_CYCLE_END);
+            metrics.push(runtime.metrics.metrics());
+        }
+        assert_ne!(metrics[0], metrics[1]);
+        // No input repeated, the stability is not measured
+        assert!(metrics[1].stability.is_nan());
+        assert!(runtime.inputs.inputs().is_empty());
+        assert_eq!(runtime.state, State::Ready { learning: true });
+    }
+
+    #[test]
+    fn spatial_pooler_restores_checkpoint_once_its_graph_exists() {
+        let path = env::temp_dir().join("phtm_restore_test.chk");
+        let (mut runtime, _handle) = spatial_pooler_runtime(&["--restore-once", path.to_str().unwrap()]);
+        // Node ids are global, the checkpoint is taken from the same
+        // graph right before its creation ends
+        run_to(&mut runtime, "BP3");
+        let exec = |runtime: &NodeRuntime, i| runtime.executor.push_state.exec_stack.get(i).map(|i| i.to_string());
+        while exec(&runtime, 1).as_deref() != Some(sdr::SP_GRAPH_CREATED) {
+            runtime.apply(RuntimeCommand::Step(1));
+            assert!(runtime.execute());
+        }
+        assert_eq!(runtime.state, State::Initializing);
+        let mut checkpoint = Checkpoint::from_state(&runtime.executor.push_state);
+        checkpoint.bindings.push(("RESTORED".to_string(), PushType::Int { val: 1 }));
+        checkpoint.save(&path).unwrap();
+        runtime.apply(RuntimeCommand::Step(1));
+        runtime.execute();
+        assert_eq!(runtime.state, State::Ready { learning: true });
+        assert!(runtime.executor.push_state.name_bindings.contains_key("RESTORED"));
+        // Replicas delete their temporary checkpoint
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn inputs_of_another_size_are_rejected() {
+        let args: Vec<String> = ["phtm", "( NUM_INPUTS EXEC.DEFINE 2 BP4 EXEC.Y )", "--type", "sp"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        let (mut runtime, _handle) = NodeRuntime::new(NodeArgs::parse(&args).unwrap(), RuntimeConfig {
+            connect: false,
+            start_paused: true,
+            ..RuntimeConfig::default()
+        });
+        // The size is not known before the program defined it
+        runtime.apply(RuntimeCommand::Inject(vec![true; 3]));
+        assert_eq!(runtime.inputs.pending(), 1);
+        runtime.apply(RuntimeCommand::Step(4));
+        runtime.execute();
+        runtime.apply(RuntimeCommand::Inject(vec![true; 3]));
+        runtime.apply(RuntimeCommand::Inject(vec![true, false]));
+        assert_eq!(runtime.inputs.pending(), 2);
+        runtime.feed_inbound();
+        assert_eq!(runtime.executor.push_state.input_stack.size(), 1);
+        assert_eq!(runtime.inputs.inputs().back().unwrap()[..2], [true, false]);
+    }
+
+    #[test]
+    fn untyped_programs_check_num_inputs() {
+        let (mut runtime, _handle) = offline_runtime("( NUM_INPUTS EXEC.DEFINE 2 BP1 EXEC.Y )");
+        assert_eq!(runtime.node_info.node_type, NodeType::UNDEFINED);
+        runtime.apply(RuntimeCommand::Step(4));
+        runtime.execute();
+        assert_eq!(runtime.snapshot().input_size(), Some(2));
+        runtime.apply(RuntimeCommand::Inject(vec![true; 3]));
+        runtime.apply(RuntimeCommand::Inject(vec![true, false]));
+        assert_eq!(runtime.inputs.pending(), 1);
+    }
+
+    #[test]
+    fn replay_applies_recorded_changes_at_their_steps() {
+        let config = RuntimeConfig {
+            connect: false,
+            start_paused: true,
+            record: true,
+            ..RuntimeConfig::default()
+        };
+        let args: Vec<String> = ["phtm", "( BP1 EXEC.Y INTEGER.RAND )"].iter().map(|a| a.to_string()).collect();
+        let (mut runtime, _handle) = NodeRuntime::new(NodeArgs::parse(&args).unwrap(), config.clone());
+        runtime.apply(RuntimeCommand::Step(2));
+        runtime.execute();
+        runtime.apply(RuntimeCommand::Inject(vec![true, false]));
+        runtime.feed_inbound();
+        runtime.apply(RuntimeCommand::Step(5));
+        runtime.execute();
+        let (tx, mut rx) = oneshot::channel();
+        runtime.apply(RuntimeCommand::Session(tx));
+        let session = rx.try_recv().unwrap();
+        assert_eq!(session.steps, 7);
+        assert_eq!(session.entries.len(), 1);
+        assert_eq!(session.entries[0].step, 2);
+
+        let (mut replay, _handle) = NodeRuntime::new(NodeArgs::parse(&args).unwrap(), RuntimeConfig {
+            replay: Some(session),
+            ..config
+        });
+        replay.apply(RuntimeCommand::Step(7));
+        replay.execute();
+        replay.replay_due();
+        assert!(replay.recorder.replay_done());
+        assert_eq!(replay.executor.push_state.input_stack.size(), 1);
+        let ints = |r: &NodeRuntime| (0..3).map(|i| r.executor.push_state.int_stack.get(i).copied()).collect::<Vec<_>>();
+        assert_eq!(ints(&replay), ints(&runtime));
+    }
+
+    #[tokio::test]
+    async fn runtime_runs_until_shutdown() {
+        let (runtime, mut handle) = offline_runtime("( BP1 EXEC.Y 1 )");
+        let task = tokio::spawn(runtime.run());
+        handle.commands.send(RuntimeCommand::Resume).await.unwrap();
+        handle
+            .snapshots
+            .wait_for(|s| s.steps > 10 && s.state == State::Ready { learning: true })
+            .await
+            .unwrap();
+        handle.commands.send(RuntimeCommand::Shutdown(None)).await.unwrap();
+        assert_eq!(task.await.unwrap(), State::Done);
+        assert!(handle.snapshots.borrow().stack("EXEC").is_some());
+    }
+}
@@ -0,0 +1,251 @@
+// SDR Module
+// Input and output SDRs of the regions: the spatial pooler outputs
+// its active columns, the temporal memory its active cells.
+
+use crate::node::message::Message;
+use crate::node::metrics;
+use crate::pushr::push::item::{Item, PushType};
+use crate::pushr::push::state::PushState;
+use crate::shared::msg::{MessageCommand, MessageKey, MessageType, NodeType};
+use crate::shared::msg::{DEF_PL_SIZE, KEY_OFFSET, PAYLOAD_OFFSET};
+
+/// Marker at the end of the processing of an input of the spatial
+/// pooler program
+pub const SP_CYCLE_END: &str = "BP10";
+
+/// Marker at the end of the processing of an input of the temporal
+/// memory program
+pub const TM_CYCLE_END: &str = "BP4";
+
+/// Marker at the end of the graph creation of the spatial pooler
+/// program, after the columns and their topology are added
+pub const SP_GRAPH_CREATED: &str = "BP4";
+
+/// Marker at the end of the graph creation of the temporal memory
+/// program
+pub const TM_GRAPH_CREATED: &str = "BP1";
+
+/// Maximum number of bits of a message payload
+pub const MAX_SDR_SIZE: usize = DEF_PL_SIZE * 8;
+
+// Accessors of the name bindings of the programs, None if the name is
+// unbound or bound to another type
+
+pub(crate) fn int_binding(push_state: &PushState, name: &str) -> Option<i32> {
+    match push_state.name_bindings.get(name) {
+        Some(Item::Literal {
+            push_type: PushType::Int { val },
+        }) => Some(*val),
+        _ => None,
+    }
+}
+
+pub(crate) fn float_binding(push_state: &PushState, name: &str) -> Option<f32> {
+    match push_state.name_bindings.get(name) {
+        Some(Item::Literal {
+            push_type: PushType::Float { val },
+        }) => Some(*val),
+        _ => None,
+    }
+}
+
+pub(crate) fn bool_binding(push_state: &PushState, name: &str) -> Option<bool> {
+    match push_state.name_bindings.get(name) {
+        Some(Item::Literal {
+            push_type: PushType::Bool { val },
+        }) => Some(*val),
+        _ => None,
+    }
+}
+
+pub(crate) fn int_vector_binding<'a>(push_state: &'a PushState, name: &str) -> Option<&'a [i32]> {
+    match push_state.name_bindings.get(name) {
+        Some(Item::Literal {
+            push_type: PushType::IntVector { val },
+        }) => Some(&val.values),
+        _ => None,
+    }
+}
+
+/// Converts a number to the type of the binding as defined by the
+/// program. Integers are rounded.
+pub(crate) fn typed_value(default: &PushType, value: f32) -> PushType {
+    match default {
+        PushType::Int { .. } => PushType::Int { val: value.round() as i32 },
+        _ => PushType::Float { val: value },
+    }
+}
+
+/// Marker that is reached once the memory graph of the program exists.
+/// Programs without type end the graph creation like the temporal
+/// memory.
+pub fn graph_created(node_type: NodeType) -> &'static str {
+    match node_type {
+        NodeType::SPATIAL_POOLER => SP_GRAPH_CREATED,
+        _ => TM_GRAPH_CREATED,
+    }
+}
+
+/// Marker that is reached once per processed input
+pub fn cycle_end(node_type: NodeType) -> Option<&'static str> {
+    match node_type {
+        NodeType::SPATIAL_POOLER => Some(SP_CYCLE_END),
+        NodeType::TEMPORAL_MEMORY => Some(TM_CYCLE_END),
+        _ => None,
+    }
+}
+
+/// Binding of the number of input bits of the region: NUM_INPUTS for
+/// the spatial pooler and nodes without type, NUM_COLUMNS for the
+/// temporal memory
+pub fn input_size_name(node_type: NodeType) -> Option<&'static str> {
+    match node_type {
+        NodeType::SPATIAL_POOLER | NodeType::UNDEFINED => Some("NUM_INPUTS"),
+        NodeType::TEMPORAL_MEMORY => Some("NUM_COLUMNS"),
+        _ => None,
+    }
+}
+
+/// Number of input bits of the region, unknown until the program
+/// defined it
+pub fn input_size(push_state: &PushState, node_type: NodeType) -> Option<usize> {
+    int_binding(push_state, input_size_name(node_type)?).map(|n| n.max(0) as usize)
+}
+
+/// Checks the size of an input against the size of the region
+pub fn check_input_size(input: &[bool], size: Option<usize>, node_type: NodeType) -> Result<(), String> {
+    match (size, input_size_name(node_type)) {
+        (Some(size), Some(name)) if input.len() != size => {
+            Err(format!("Input has {} bits, {} is {}", input.len(), name, size))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Active columns of the spatial pooler or active cells of the temporal
+/// memory. Cells are ordered by column and, within a column, by id.
+pub fn output(push_state: &PushState, node_type: NodeType) -> Result<Vec<bool>, String> {
+    match node_type {
+        NodeType::SPATIAL_POOLER => metrics::active_columns(push_state),
+        NodeType::TEMPORAL_MEMORY => active_cells(push_state),
+        t => Err(format!("No output SDR for node type {:?}", t)),
+    }
+}
+
+fn active_cells(push_state: &PushState) -> Result<Vec<bool>, String> {
+    let column_ids =
+        int_vector_binding(push_state, "COLUMN_IDS").ok_or_else(|| "Missing integer vector COLUMN_IDS".to_string())?;
+    let num_cells = int_binding(push_state, "NUM_CELLS").ok_or_else(|| "Missing integer NUM_CELLS".to_string())?;
+    let active_states: Vec<i32> = ["STATE_CELL_ACTIVE", "STATE_CELL_ACTIVE_WINNER"]
+        .iter()
+        .filter_map(|name| int_binding(push_state, name))
+        .collect();
+    let graph = push_state.graph_stack.get(0).ok_or_else(|| "No graph".to_string())?;
+    let mut sdr = vec![];
+    for column_id in column_ids.iter() {
+        // Cells are the origins of the incoming edges of a column
+        let mut cells: Vec<usize> = graph
+            .edges
+            .get(&(*column_id as usize))
+            .map(|edges| edges.iter().map(|e| e.get_origin_id()).collect())
+            .unwrap_or_default();
+        cells.sort_unstable();
+        cells.resize(num_cells.max(0) as usize, usize::MAX);
+        for cell in cells.iter() {
+            sdr.push(graph.get_state(cell).is_some_and(|s| active_states.contains(&s)));
+        }
+    }
+    Ok(sdr)
+}
+
+/// Creates the data message of an SDR. The key may be allocated by the
+/// network and have no MessageKey variant.
+pub fn to_message(sdr: &[bool], key: u16) -> Message {
+    let mut m = Message {
+        data: vec![0; PAYLOAD_OFFSET],
+    };
+    m.create_header(MessageType::DATA, MessageCommand::WRITE, MessageKey::UNDEFINED);
+    m.set_prop(&KEY_OFFSET, &key);
+    m.set_payload(&mut vec![0; sdr.len().div_ceil(8)]);
+    for (i, _) in sdr.iter().enumerate().filter(|(_, b)| **b) {
+        m.set_payload_bit(&i);
+    }
+    m
+}
+
+/// Reads an SDR of the given size from a data message
+pub fn from_message(msg: &Message, size: usize) -> Vec<bool> {
+    let mut sdr = vec![false; size.min(MAX_SDR_SIZE)];
+    msg.parse_to(&mut sdr);
+    sdr
+}
+
+/// Parses a file of SDRs, one per line written as 0 and 1. Spaces and
+/// commas are ignored, lines starting with # are comments.
+pub fn parse_file(content: &str) -> Result<Vec<Vec<bool>>, String> {
+    let mut sdrs = vec![];
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let sdr = line
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != ',')
+            .map(|c| match c {
+                '0' => Ok(false),
+                '1' => Ok(true),
+                c => Err(format!("Invalid character '{}' in line {}", c, i + 1)),
+            })
+            .collect::<Result<Vec<bool>, String>>()?;
+        sdrs.push(sdr);
+    }
+    Ok(sdrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pushr::push::graph::Graph;
+    use crate::pushr::push::vector::IntVector;
+
+    #[test]
+    fn sdr_message_roundtrip() {
+        let sdr = vec![true, false, false, true, false, false, false, false, false, true];
+        let m = to_message(&sdr, 1003);
+        assert_eq!(m.get_prop(&KEY_OFFSET), 1003);
+        assert_eq!(m.get_cmd(), Some(MessageCommand::WRITE));
+        assert_eq!(from_message(&m, sdr.len()), sdr);
+    }
+
+    #[test]
+    fn sdr_file_is_parsed() {
+        let sdrs = parse_file("# Two inputs\n0110\n\n1, 0, 0, 1\n").unwrap();
+        assert_eq!(sdrs, vec![vec![false, true, true, false], vec![true, false, false, true]]);
+        assert!(parse_file("01x0").is_err());
+    }
+
+    #[test]
+    fn active_cells_are_ordered_by_column() {
+        let mut push_state = PushState::new();
+        let mut graph = Graph::new();
+        let c1 = graph.add_node(0);
+        let c2 = graph.add_node(0);
+        let cells: Vec<usize> = [1, 0, 0, 2].iter().map(|s| graph.add_node(*s)).collect();
+        graph.add_edge(cells[0], c1, 1.0);
+        graph.add_edge(cells[1], c1, 1.0);
+        graph.add_edge(cells[2], c2, 1.0);
+        graph.add_edge(cells[3], c2, 1.0);
+        push_state.graph_stack.push(graph);
+        push_state.name_bindings.insert(
+            "COLUMN_IDS".to_string(),
+            Item::intvec(IntVector::new(vec![c2 as i32, c1 as i32])),
+        );
+        push_state.name_bindings.insert("NUM_CELLS".to_string(), Item::int(2));
+        push_state.name_bindings.insert("STATE_CELL_ACTIVE".to_string(), Item::int(1));
+        push_state.name_bindings.insert("STATE_CELL_ACTIVE_WINNER".to_string(), Item::int(2));
+        let sdr = output(&push_state, NodeType::TEMPORAL_MEMORY).unwrap();
+        assert_eq!(sdr, vec![false, true, true, false]);
+        assert_eq!(input_size(&push_state, NodeType::TEMPORAL_MEMORY), None);
+    }
+}
@@ -0,0 +1,211 @@
+// Session Module
+// Record of a terminal interface session for bug reports: the hash of
+// the program, the seed of the random instructions and every change of
+// the state with the step at which it was applied. A replay applies
+// the changes at the same steps, so that the node passes through the
+// same states.
+
+use std::collections::VecDeque;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::node::checkpoint::Checkpoint;
+use crate::node::source::Source;
+use crate::pushr::push::io::PushMessage;
+use crate::pushr::push::state::PushState;
+
+/// Change of the state of the node
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    /// Input pushed to the input stack, the header holds type, command
+    /// and key, the SDR is written as 0 and 1
+    Input { header: Vec<i32>, sdr: String },
+    SetParameter { name: String, value: String },
+    /// Reloads the program
+    Reset,
+    LoadProgram { code: String },
+    /// Restores the checkpoint, written as checkpoint file
+    LoadCheckpoint { checkpoint: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Steps executed before the change
+    pub step: u64,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// Hash of the transpiled program the node started with
+    pub program_hash: u64,
+    pub seed: u64,
+    /// Steps executed when the session was saved
+    pub steps: u64,
+    /// Hash of the learned state when the session was saved
+    pub state_hash: u64,
+    pub entries: Vec<Entry>,
+}
+
+impl Session {
+    pub fn new(program_hash: u64, seed: u64) -> Self {
+        Self {
+            program_hash,
+            seed,
+            steps: 0,
+            state_hash: 0,
+            entries: vec![],
+        }
+    }
+
+    pub fn record(&mut self, step: u64, action: Action) {
+        self.entries.push(Entry { step, action });
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        serde_json::from_str(content).map_err(|e| format!("Invalid session: {}", e))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// Sessions are replayed with the program they were recorded with
+    pub fn check_program(&self, program_hash: u64) -> Result<(), String> {
+        if program_hash == self.program_hash {
+            Ok(())
+        } else {
+            Err(format!(
+                "Session was recorded with program {:016x}, the program is {:016x}",
+                self.program_hash, program_hash
+            ))
+        }
+    }
+}
+
+/// Records the changes of a runtime and hands out the changes of a
+/// replayed session at their steps
+#[derive(Debug, Default)]
+pub struct Recorder {
+    /// Changes of the state recorded since the start
+    session: Option<Session>,
+    /// Changes of the replayed session that are not applied yet
+    replay: VecDeque<Entry>,
+    /// Steps and state hash of the replayed session when it was saved
+    replay_end: Option<(u64, u64)>,
+}
+
+impl Recorder {
+    pub fn new(session: Option<Session>, replay: Option<&Session>) -> Self {
+        Self {
+            session,
+            replay: replay.map(|s| s.entries.iter().cloned().collect()).unwrap_or_default(),
+            replay_end: replay.map(|s| (s.steps, s.state_hash)),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.session.is_some()
+    }
+
+    pub fn record(&mut self, step: u64, action: Action) {
+        if let Some(session) = self.session.as_mut() {
+            session.record(step, action);
+        }
+    }
+
+    pub fn record_input(&mut self, step: u64, input: &PushMessage) {
+        if !self.is_recording() {
+            return;
+        }
+        self.record(step, Action::Input {
+            header: input.header.values.clone(),
+            sdr: to_bits(&input.body.values),
+        });
+    }
+
+    /// Recorded session with the steps and the hash of the state when
+    /// it is saved, not recording runtimes have none
+    pub fn session(&self, steps: u64, push_state: &PushState) -> Option<Session> {
+        self.session.as_ref().map(|session| Session {
+            steps,
+            state_hash: state_hash(push_state),
+            ..session.clone()
+        })
+    }
+
+    /// Takes the next change of the replayed session that was recorded
+    /// up to the step
+    pub fn next_due(&mut self, step: u64) -> Option<Entry> {
+        if self.replay.front().is_some_and(|entry| entry.step <= step) {
+            self.replay.pop_front()
+        } else {
+            None
+        }
+    }
+
+    /// Compares the state with the recorded one at the step the
+    /// replayed session was saved
+    pub fn check_replay_end(&mut self, step: u64, push_state: &PushState) {
+        if let Some((steps, state_hash)) = self.replay_end {
+            if steps == step {
+                self.replay_end = None;
+                if self::state_hash(push_state) == state_hash {
+                    info!("Replay reached step {} in the recorded state", steps);
+                } else {
+                    warn!("Replay diverged from the recorded state at step {}", steps);
+                }
+            }
+        }
+    }
+
+    /// All changes are applied and the end state was compared
+    pub fn replay_done(&self) -> bool {
+        self.replay.is_empty() && self.replay_end.is_none()
+    }
+}
+
+/// Hash of the graph and literal bindings, as saved by a checkpoint
+pub fn state_hash(push_state: &PushState) -> u64 {
+    Source::program_hash(&Checkpoint::from_state(push_state).to_json())
+}
+
+pub fn to_bits(sdr: &[bool]) -> String {
+    sdr.iter().map(|b| if *b { '1' } else { '0' }).collect()
+}
+
+pub fn from_bits(bits: &str) -> Result<Vec<bool>, String> {
+    bits.chars()
+        .map(|c| match c {
+            '0' => Ok(false),
+            '1' => Ok(true),
+            c => Err(format!("Invalid character '{}' in SDR", c)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_json_roundtrip() {
+        let mut session = Session::new(0xdead_beef, 42);
+        session.record(0, Action::SetParameter { name: "NUM_INPUTS".to_string(), value: "4".to_string() });
+        session.record(1200, Action::Input { header: vec![2, 1, 1003], sdr: to_bits(&[true, false, false, true]) });
+        session.record(5000, Action::Reset);
+        session.steps = 6000;
+        let json = session.to_json();
+        assert!(json.contains("\"action\": \"set_parameter\""));
+        assert!(json.contains("\"sdr\": \"1001\""));
+        assert_eq!(Session::parse(&json), Ok(session.clone()));
+        assert!(session.check_program(0xdead_beef).is_ok());
+        assert!(session.check_program(1).is_err());
+        assert!(Session::parse("{\"seed\": 1}").is_err());
+        assert_eq!(from_bits("0110"), Ok(vec![false, true, true, false]));
+        assert!(from_bits("01x").is_err());
+    }
+}
@@ -0,0 +1,615 @@
+// Snapshot Module
+// View of a running node for attached front ends: lifecycle state,
+// run control, the top items of the stacks, the name bindings, the
+// source line of the next instruction, graph statistics, the
+// states of inputs, columns and cells, the synapses of an inspected
+// node and the learning statistics of recent inputs. Remote front
+// ends receive it as versioned JSON payload of the debug protocol.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use num_traits::FromPrimitive;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::node::control::State;
+use crate::node::history::Sample;
+use crate::node::sdr;
+use crate::pushr::push::item::{Item, PushType};
+use crate::pushr::push::buffer::PushBuffer;
+use crate::pushr::push::stack::{PushPrint, PushStack};
+use crate::pushr::push::state::PushState;
+use crate::shared::msg::NodeType;
+
+/// Version of the snapshot payload, payloads of other versions are
+/// rejected
+pub const SNAPSHOT_VERSION: u16 = 1;
+
+/// Number of items captured from the top of each stack
+pub const STACK_DEPTH: usize = 50;
+
+/// Longer item labels are cut
+const MAX_LABEL_LENGTH: usize = 80;
+
+/// Prefix of the node state constants of the programs
+const STATE_PREFIX: &str = "STATE_";
+
+/// Prefix of the states of cells, without state prefix
+const CELL_PREFIX: &str = "CELL_";
+
+/// Prefix of the states of distal segments, without state prefix
+const SEGMENT_PREFIX: &str = "SEGMENT";
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StackView {
+    pub name: String,
+    pub size: usize,
+    /// Labels of the top items, top first
+    pub items: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub node_id: u16,
+    #[serde(with = "node_type")]
+    pub node_type: NodeType,
+    pub state: State,
+    pub paused: bool,
+    /// Interpreter steps since the start of the node
+    pub steps: u64,
+    /// Steps between two checks of commands and queues
+    pub step_budget: usize,
+    pub stacks: Vec<StackView>,
+    /// Labels of the name bindings, ordered by name
+    pub bindings: Vec<(String, String)>,
+    /// Source line of the next instruction, counted from 0
+    pub line: Option<usize>,
+    /// Source lines at which the program pauses
+    pub breakpoints: Vec<usize>,
+    /// Instructions at which the program pauses
+    pub instruction_breakpoints: Vec<String>,
+    /// Statistics of the memory graph, none before it is created
+    pub graph: Option<GraphStats>,
+    /// Columns and inputs of the memory graph, none before the program
+    /// defined its column ids
+    pub model: Option<ModelView>,
+    /// Details of the node selected by the front end
+    pub inspection: Option<Inspection>,
+    /// Statistics of the recent inputs, from the oldest
+    pub history: Vec<Sample>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GraphStats {
+    pub nodes: usize,
+    pub edges: usize,
+    /// Number of nodes in each state defined by the program
+    /// (`STATE_*` bindings), ordered by state
+    pub states: Vec<(String, usize)>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelView {
+    /// Columns per row (`TOPOLOGY_EDGE_LENGTH`)
+    pub edge_length: usize,
+    /// Names of the states defined by the program by value
+    pub state_names: Vec<(i32, String)>,
+    /// Columns in the order of `COLUMN_IDS`
+    pub columns: Vec<ColumnView>,
+    /// Input nodes as (id, state) in the order of `INPUT_IDS`
+    pub inputs: Vec<(usize, i32)>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ColumnView {
+    pub id: usize,
+    pub state: i32,
+    /// Cells connected to the column as (id, state)
+    pub cells: Vec<(usize, i32)>,
+}
+
+/// Synapses of a column or cell. Proximal synapses connect the inputs
+/// to the boost cell of a column, distal synapses connect presynaptic
+/// cells to the segments of a cell.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Inspection {
+    pub id: usize,
+    pub state: i32,
+    /// Boost cell of a column
+    pub boost: Option<BoostView>,
+    /// Proximal synapses as (input id, permanence)
+    pub proximal: Vec<(usize, f32)>,
+    /// Distal segments of a cell
+    pub segments: Vec<SegmentView>,
+    /// Connected permanence of proximal synapses (`PERM_CON_THRESHOLD`)
+    pub proximal_threshold: Option<f32>,
+    /// Connected permanence of distal synapses (`CONNECTED_PERMANENCE`)
+    pub distal_threshold: Option<f32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BoostView {
+    pub id: usize,
+    /// Weight of the edge column -> boost cell
+    pub factor: f32,
+    /// Weight of the edge boost cell -> column
+    pub overlap: f32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SegmentView {
+    pub id: usize,
+    pub state: i32,
+    /// Presynaptic cells as (id, permanence)
+    pub synapses: Vec<(usize, f32)>,
+}
+
+impl Inspection {
+    /// Number of proximal synapses at or above the threshold
+    pub fn connected(&self) -> Option<usize> {
+        let threshold = self.proximal_threshold?;
+        Some(self.proximal.iter().filter(|(_, p)| *p >= threshold).count())
+    }
+}
+
+impl ModelView {
+    /// Name of a state without prefix, e.g. COLUMN_ACTIVE
+    pub fn state_name(&self, state: i32) -> Option<&str> {
+        self.state_names
+            .iter()
+            .find(|(value, _)| *value == state)
+            .map(|(_, name)| name.as_str())
+    }
+}
+
+impl Default for Snapshot {
+    fn default() -> Self {
+        Self {
+            node_id: 0,
+            node_type: NodeType::UNDEFINED,
+            state: State::Initializing,
+            paused: false,
+            steps: 0,
+            step_budget: 0,
+            stacks: vec![],
+            bindings: vec![],
+            line: None,
+            breakpoints: vec![],
+            instruction_breakpoints: vec![],
+            graph: None,
+            model: None,
+            inspection: None,
+            history: vec![],
+        }
+    }
+}
+
+/// Label of an item, lists are abbreviated
+pub fn item_label(item: &Item) -> String {
+    match item {
+        Item::List { .. } => "( ... )".to_string(),
+        _ => item.to_string(),
+    }
+}
+
+fn cut(mut label: String) -> String {
+    if let Some((i, _)) = label.char_indices().nth(MAX_LABEL_LENGTH) {
+        label.truncate(i);
+        label.push_str("...");
+    }
+    label
+}
+
+fn view<T, F>(name: &str, stack: &PushStack<T>, label: F) -> StackView
+where
+    T: Clone + fmt::Display + PartialEq + PushPrint,
+    F: Fn(&T) -> String,
+{
+    StackView {
+        name: name.to_string(),
+        size: stack.size(),
+        items: (0..STACK_DEPTH.min(stack.size()))
+            .filter_map(|i| stack.get(i).map(|item| cut(label(item))))
+            .collect(),
+    }
+}
+
+/// Buffers list their items from the oldest
+fn buffer_view<T, F>(name: &str, buffer: &PushBuffer<T>, label: F) -> StackView
+where
+    T: Clone + Default + fmt::Debug + fmt::Display + PartialEq,
+    F: Fn(&T) -> String,
+{
+    StackView {
+        name: name.to_string(),
+        size: buffer.size(),
+        items: buffer.iter().take(STACK_DEPTH).map(|item| cut(label(item))).collect(),
+    }
+}
+
+/// Captures the top items of all stacks and buffers of the interpreter
+pub fn capture_stacks(push_state: &PushState) -> Vec<StackView> {
+    vec![
+        view("BOOL", &push_state.bool_stack, |b| b.to_string()),
+        view("INT", &push_state.int_stack, |i| i.to_string()),
+        view("FLOAT", &push_state.float_stack, |f| f.to_string()),
+        view("EXEC", &push_state.exec_stack, item_label),
+        view("CODE", &push_state.code_stack, item_label),
+        view("NAME", &push_state.name_stack, |n| n.clone()),
+        view("INDEX", &push_state.index_stack, |i| i.to_string()),
+        view("BOOLVEC", &push_state.bool_vector_stack, |v| v.to_string()),
+        view("INTVEC", &push_state.int_vector_stack, |v| v.to_string()),
+        view("FLOATVEC", &push_state.float_vector_stack, |v| v.to_string()),
+        buffer_view("INPUT", &push_state.input_stack, |m| m.to_string()),
+        buffer_view("OUTPUT", &push_state.output_stack, |m| m.to_string()),
+        buffer_view("GRAPH", &push_state.graph_stack, |g| {
+            format!("{} nodes, {} edges", g.node_size(), g.edge_size())
+        }),
+    ]
+}
+
+/// Captures the labels of the name bindings
+pub fn capture_bindings(push_state: &PushState) -> Vec<(String, String)> {
+    let mut bindings: Vec<(String, String)> = push_state
+        .name_bindings
+        .iter()
+        .map(|(name, item)| (name.clone(), cut(item_label(item))))
+        .collect();
+    bindings.sort();
+    bindings
+}
+
+/// States defined by the program (`STATE_*` bindings) as (value,
+/// name without prefix), ordered by value
+pub fn state_names(push_state: &PushState) -> Vec<(i32, String)> {
+    let mut states: Vec<(i32, String)> = push_state
+        .name_bindings
+        .iter()
+        .filter(|(name, _)| name.starts_with(STATE_PREFIX))
+        .filter_map(|(name, item)| match item {
+            Item::Literal {
+                push_type: PushType::Int { val },
+            } => Some((*val, name.trim_start_matches(STATE_PREFIX).to_string())),
+            _ => None,
+        })
+        .collect();
+    states.sort();
+    states
+}
+
+/// Counts the nodes and edges of the memory graph and the nodes in
+/// each state defined by the program
+pub fn capture_graph(push_state: &PushState) -> Option<GraphStats> {
+    let graph = push_state.graph_stack.get(0)?;
+    let states = state_names(push_state);
+    Some(GraphStats {
+        nodes: graph.node_size(),
+        edges: graph.edge_size(),
+        states: states
+            .into_iter()
+            .map(|(state, name)| (name, graph.nodes.values().filter(|n| n.get_state() == state).count()))
+            .collect(),
+    })
+}
+
+/// Captures the states of the columns, their cells and the inputs.
+/// Cells are the nodes with a cell state connected to a column in
+/// either direction.
+pub fn capture_model(push_state: &PushState) -> Option<ModelView> {
+    let graph = push_state.graph_stack.get(0)?;
+    let column_ids = sdr::int_vector_binding(push_state, "COLUMN_IDS")?;
+    let state_names = state_names(push_state);
+    let cell_states: Vec<i32> = state_names
+        .iter()
+        .filter(|(_, name)| name.starts_with(CELL_PREFIX))
+        .map(|(value, _)| *value)
+        .collect();
+    let state = |id: usize| graph.nodes.get(&id).map(|n| n.get_state());
+    let mut cells: HashMap<usize, Vec<(usize, i32)>> = HashMap::new();
+    for (destination, edges) in graph.edges.iter() {
+        for e in edges.iter() {
+            for (column, cell) in [(*destination, e.get_origin_id()), (e.get_origin_id(), *destination)] {
+                match state(cell) {
+                    Some(s) if cell_states.contains(&s) => cells.entry(column).or_default().push((cell, s)),
+                    _ => (),
+                }
+            }
+        }
+    }
+    let columns: Vec<ColumnView> = column_ids
+        .iter()
+        .filter_map(|id| {
+            let id = *id as usize;
+            let mut cells = cells.remove(&id).unwrap_or_default();
+            cells.sort_unstable();
+            cells.dedup();
+            state(id).map(|state| ColumnView { id, state, cells })
+        })
+        .collect();
+    let inputs = sdr::int_vector_binding(push_state, "INPUT_IDS")
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|id| state(*id as usize).map(|s| (*id as usize, s)))
+        .collect();
+    // Square layout without topology
+    let edge_length = match sdr::int_binding(push_state, "TOPOLOGY_EDGE_LENGTH") {
+        Some(n) if n > 0 => n as usize,
+        _ => (columns.len() as f64).sqrt().ceil().max(1.0) as usize,
+    };
+    Some(ModelView {
+        edge_length,
+        state_names,
+        columns,
+        inputs,
+    })
+}
+
+/// Reads the synapses of a column or cell from the edges of the graph:
+/// the boost cell of a column with its input synapses and the segments
+/// of a cell with their presynaptic cells
+pub fn capture_inspection(push_state: &PushState, id: usize) -> Option<Inspection> {
+    let graph = push_state.graph_stack.get(0)?;
+    let state = graph.get_state(&id)?;
+    let state_names = state_names(push_state);
+    let has_state = |node: usize, prefix: &str| {
+        graph
+            .get_state(&node)
+            .and_then(|s| state_names.iter().find(|(value, _)| *value == s))
+            .is_some_and(|(_, name)| name.starts_with(prefix))
+    };
+    let incoming = |node: usize| -> Vec<(usize, f32)> {
+        graph
+            .edges
+            .get(&node)
+            .map(|edges| edges.iter().map(|e| (e.get_origin_id(), e.get_weight())).collect())
+            .unwrap_or_default()
+    };
+    let mut inspection = Inspection {
+        id,
+        state,
+        proximal_threshold: sdr::float_binding(push_state, "PERM_CON_THRESHOLD"),
+        distal_threshold: sdr::float_binding(push_state, "CONNECTED_PERMANENCE"),
+        ..Inspection::default()
+    };
+    for (origin, weight) in incoming(id) {
+        if has_state(origin, "BOOST_CELL") {
+            // Inputs and the column are connected to the boost cell
+            let mut factor = 0.0;
+            for (input, permanence) in incoming(origin) {
+                if input == id {
+                    factor = permanence;
+                } else {
+                    inspection.proximal.push((input, permanence));
+                }
+            }
+            inspection.boost = Some(BoostView {
+                id: origin,
+                factor,
+                overlap: weight,
+            });
+        } else if has_state(origin, SEGMENT_PREFIX) {
+            inspection.segments.push(SegmentView {
+                id: origin,
+                state: graph.get_state(&origin).unwrap_or_default(),
+                synapses: incoming(origin),
+            });
+        }
+    }
+    inspection.proximal.sort_by_key(|(input, _)| *input);
+    inspection.segments.sort_by_key(|segment| segment.id);
+    Some(inspection)
+}
+
+/// Node types are encoded by their message value
+mod node_type {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(node_type: &NodeType, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u16(*node_type as u16)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NodeType, D::Error> {
+        let value = u16::deserialize(deserializer)?;
+        Ok(NodeType::from_u16(value).unwrap_or(NodeType::UNDEFINED))
+    }
+}
+
+/// Payload of the debug protocol
+#[derive(Serialize, Deserialize)]
+struct Payload<T> {
+    version: u16,
+    snapshot: T,
+}
+
+/// Version of a payload, read before the snapshot
+#[derive(Deserialize)]
+struct PayloadVersion {
+    version: u16,
+}
+
+impl Snapshot {
+    pub fn stack(&self, name: &str) -> Option<&StackView> {
+        self.stacks.iter().find(|s| s.name == name)
+    }
+
+    /// Label of the i-th item from the top of a stack
+    pub fn item(&self, stack: &str, i: usize) -> Option<&String> {
+        self.stack(stack).and_then(|s| s.items.get(i))
+    }
+
+    /// Item that is executed next
+    pub fn exec_item(&self) -> Option<&String> {
+        self.item("EXEC", 0)
+    }
+
+    /// Number of input bits once the program defined it
+    pub fn input_size(&self) -> Option<usize> {
+        let name = sdr::input_size_name(self.node_type)?;
+        let (_, label) = self.bindings.iter().find(|(n, _)| n == name)?;
+        label.parse().ok()
+    }
+
+    /// Encodes the snapshot as JSON with the payload version
+    pub fn to_payload(&self) -> Vec<u8> {
+        serde_json::to_vec(&Payload {
+            version: SNAPSHOT_VERSION,
+            snapshot: self,
+        })
+        .unwrap_or_default()
+    }
+
+    pub fn from_payload(data: &[u8]) -> Result<Self, String> {
+        let PayloadVersion { version } =
+            serde_json::from_slice(data).map_err(|e| format!("Invalid snapshot: {}", e))?;
+        if version != SNAPSHOT_VERSION {
+            return Err(format!("Unsupported snapshot version {}, expected {}", version, SNAPSHOT_VERSION));
+        }
+        let payload: Payload<Self> = serde_json::from_slice(data).map_err(|e| format!("Invalid snapshot: {}", e))?;
+        Ok(payload.snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::execution::PushExecutor;
+    use crate::node::source::Source;
+
+    #[test]
+    fn stacks_are_captured_top_first() {
+        let mut executor = PushExecutor::new();
+        executor.initialize();
+        executor.load("( 1 2 3 ( 4 5 ) TRUE )".to_string());
+        // Unpack the program and push the literals
+        for _ in 0..4 {
+            executor.step();
+        }
+        executor.push_state.name_bindings.insert("N".to_string(), Item::int(5));
+        let snapshot = Snapshot {
+            stacks: capture_stacks(&executor.push_state),
+            bindings: capture_bindings(&executor.push_state),
+            ..Snapshot::default()
+        };
+        assert_eq!(snapshot.item("INT", 0), Some(&"3".to_string()));
+        assert_eq!(snapshot.stack("INT").unwrap().size, 3);
+        assert_eq!(snapshot.item("EXEC", 0), Some(&"( ... )".to_string()));
+        assert_eq!(snapshot.item("BOOL", 0), None);
+        assert_eq!(snapshot.stack("GRAPH").unwrap().size, 0);
+        assert!(snapshot.bindings.contains(&("N".to_string(), "5".to_string())));
+    }
+
+    #[test]
+    fn snapshot_payload_round_trip() {
+        let snapshot = Snapshot {
+            node_id: 7,
+            node_type: NodeType::SPATIAL_POOLER,
+            state: State::Failure("Empty execution stack".to_string()),
+            paused: true,
+            steps: 1 << 40,
+            step_budget: 1000,
+            stacks: vec![StackView {
+                name: "EXEC".to_string(),
+                size: 12,
+                items: vec!["BP1".to_string(), "( ... )".to_string()],
+            }],
+            bindings: vec![("NUM_COLUMNS".to_string(), "16".to_string())],
+            line: Some(61),
+            breakpoints: vec![61, 138],
+            instruction_breakpoints: vec!["GROW_SYNAPSES".to_string()],
+            graph: Some(GraphStats {
+                nodes: 48,
+                edges: 512,
+                states: vec![("INPUT_ON".to_string(), 3)],
+            }),
+            model: Some(ModelView {
+                edge_length: 2,
+                state_names: vec![(4, "COLUMN_ACTIVE".to_string()), (-1, "NEGATIVE".to_string())],
+                columns: vec![ColumnView {
+                    id: 20,
+                    state: 4,
+                    cells: vec![(21, 8), (22, 6)],
+                }],
+                inputs: vec![(1, 1), (2, 2)],
+            }),
+            inspection: Some(Inspection {
+                id: 20,
+                state: 4,
+                boost: Some(BoostView {
+                    id: 23,
+                    factor: 1.5,
+                    overlap: 3.0,
+                }),
+                proximal: vec![(1, 0.4), (2, 0.6)],
+                segments: vec![SegmentView {
+                    id: 30,
+                    state: 11,
+                    synapses: vec![(22, 1.01)],
+                }],
+                proximal_threshold: Some(0.5),
+                distal_threshold: None,
+            }),
+            history: vec![Sample {
+                input: 8,
+                active_columns: 4,
+                bursting_columns: 1,
+                predictive_cells: 3,
+                anomaly: 0.25,
+                mean_permanence: 0.48,
+                steps: 5021,
+            }],
+        };
+        assert_eq!(Snapshot::from_payload(&snapshot.to_payload()).unwrap(), snapshot);
+        let empty = Snapshot::default();
+        assert_eq!(Snapshot::from_payload(&empty.to_payload()).unwrap(), empty);
+        assert!(Snapshot::from_payload(&snapshot.to_payload()[..20]).is_err());
+        let payload = serde_json::to_vec(&Payload {
+            version: SNAPSHOT_VERSION + 1,
+            snapshot: &empty,
+        })
+        .unwrap();
+        assert!(Snapshot::from_payload(&payload).unwrap_err().contains("version"));
+    }
+
+    #[test]
+    fn graph_states_are_counted() {
+        let mut executor = PushExecutor::new();
+        executor.initialize();
+        executor.load(Source::read_debug_code(include_str!("../core/spatial_pooler.push").to_string()));
+        assert_eq!(capture_graph(&executor.push_state), None);
+        // Nodes and edges of inputs and columns are added before BP3
+        executor.step_until("BP3".to_string());
+        let graph = capture_graph(&executor.push_state).unwrap();
+        let count = |name: &str| graph.states.iter().find(|(n, _)| n == name).map(|(_, c)| *c);
+        assert_eq!(graph.states[0].0, "INPUT_ON");
+        assert_eq!(count("COLUMN_INACTIVE"), Some(16));
+        assert!(graph.nodes > 16 && graph.edges > 0);
+        let model = capture_model(&executor.push_state).unwrap();
+        assert_eq!((model.edge_length, model.columns.len(), model.inputs.len()), (4, 16, 32));
+        assert_eq!(model.state_name(model.columns[0].state), Some("COLUMN_INACTIVE"));
+        // Boost cells are no cells of the column
+        assert!(model.columns[0].cells.is_empty());
+        let inspection = capture_inspection(&executor.push_state, model.columns[0].id).unwrap();
+        let boost = inspection.boost.as_ref().unwrap();
+        assert_eq!((boost.factor, boost.overlap), (1.0, 0.0));
+        assert!(!inspection.proximal.is_empty());
+        assert!(inspection.proximal.iter().all(|(id, _)| model.inputs.iter().any(|(input, _)| input == id)));
+        assert_eq!(inspection.proximal_threshold, Some(0.5));
+        assert!(inspection.connected().is_some());
+        assert!(inspection.segments.is_empty());
+    }
+
+    #[test]
+    fn cells_are_assigned_to_columns() {
+        let mut executor = PushExecutor::new();
+        executor.initialize();
+        executor.load(Source::read_debug_code(include_str!("../core/temporal_memory.push").to_string()));
+        executor.step_until("BP1".to_string());
+        let model = capture_model(&executor.push_state).unwrap();
+        assert_eq!(model.columns.len(), 16);
+        assert!(model.inputs.is_empty());
+        for column in model.columns.iter() {
+            assert_eq!(column.cells.len(), 1);
+            assert_eq!(model.state_name(column.cells[0].1), Some("CELL_INACTIVE"));
+        }
+    }
+}
@@ -6,6 +6,9 @@ use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::path::Path;
 
+use crate::pushr::push::item::{Item, PushType};
+use crate::pushr::push::state::PushState;
+
 pub struct Source {
     pub filenames: String,
 }
@@ -81,6 +84,70 @@ impl Source {
 
     }
 
+    /// Transpiles the code stack of the node back to program code,
+    /// e.g. to pass it to a replica. Other than the display of items,
+    /// literals are written such that the parser reads the same values.
+    pub fn from_code_stack(push_state: &PushState) -> String {
+        let items: Vec<Item> = (0..push_state.code_stack.size())
+            .filter_map(|i| push_state.code_stack.copy(i))
+            .collect();
+        Source::from_items(&items)
+    }
+
+    /// Transpiles items to program code, see `from_code_stack`
+    pub fn from_items(items: &[Item]) -> String {
+        let mut buffer = String::new();
+        for item in items.iter() {
+            Source::write_item(item, &mut buffer);
+        }
+        buffer.trim_end().to_string()
+    }
+
+    fn write_item(item: &Item, buffer: &mut String) {
+        let join = |values: Vec<String>| values.join(",");
+        match item {
+            Item::List { items } => {
+                buffer.push_str("( ");
+                for i in 0..items.size() {
+                    if let Some(item) = items.get(i) {
+                        Source::write_item(item, buffer);
+                    }
+                }
+                buffer.push(')');
+            }
+            Item::InstructionMeta { name } | Item::Identifier { name } => buffer.push_str(name),
+            Item::Literal { push_type } => match push_type {
+                PushType::Bool { val } => buffer.push_str(if *val { "TRUE" } else { "FALSE" }),
+                PushType::Int { val } => buffer.push_str(&val.to_string()),
+                // Debug format keeps the decimal point and all digits
+                PushType::Float { val } => buffer.push_str(&format!("{:?}", val)),
+                PushType::BoolVector { val } => buffer.push_str(&format!(
+                    "BOOL[{}]",
+                    join(val.values.iter().map(|v| (*v as u8).to_string()).collect())
+                )),
+                PushType::IntVector { val } => buffer.push_str(&format!(
+                    "INT[{}]",
+                    join(val.values.iter().map(|v| v.to_string()).collect())
+                )),
+                PushType::FloatVector { val } => buffer.push_str(&format!(
+                    "FLOAT[{}]",
+                    join(val.values.iter().map(|v| format!("{:?}", v)).collect())
+                )),
+                // Not part of parsed programs
+                PushType::Index { .. } | PushType::Graph { .. } => buffer.push_str("NOOP"),
+            },
+        }
+        buffer.push(' ');
+    }
+
+    /// Hash of the program code to identify nodes running the same
+    /// program (64 bit FNV-1a, stable across builds).
+    pub fn program_hash(code: &str) -> u64 {
+        code.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+            (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
     /// Strip everything after suffix from string
     pub fn remove_suffix<'a>(s: &'a str, suffix: &str) -> &'a str {
         match s.split(suffix).nth(0) {
@@ -94,6 +161,7 @@ impl Source {
 mod tests {
 
     use super::*;
+    use crate::node::execution::PushExecutor;
 
     #[test]
     pub fn read_code_removes_comments() {
@@ -101,4 +169,31 @@ mod tests {
         let sp_sources = Source::read_debug_code(sp_code);
         assert!(!sp_sources.contains("#"));
     }
+
+    #[test]
+    pub fn code_stack_transpiles_to_same_program() {
+        let mut executor = PushExecutor::new();
+        executor.initialize();
+        executor.load(Source::read_debug_code(include_str!("../core/temporal_memory.push").to_string()));
+        let code = Source::from_code_stack(&executor.push_state);
+        let mut replica = PushExecutor::new();
+        replica.initialize();
+        replica.load(code.clone());
+        assert_eq!(Source::from_code_stack(&replica.push_state), code);
+        assert_eq!(
+            replica.push_state.code_stack.to_string(),
+            executor.push_state.code_stack.to_string()
+        );
+    }
+
+    #[test]
+    pub fn transpiled_literals_keep_values() {
+        let mut executor = PushExecutor::new();
+        executor.initialize();
+        executor.load("( 0.0005 1.0 INT[1,-2] FLOAT[0.25] BOOL[1,0] TRUE X )".to_string());
+        assert_eq!(
+            Source::from_code_stack(&executor.push_state),
+            "( 0.0005 1.0 INT[1,-2] FLOAT[0.25] BOOL[1,0] TRUE X )"
+        );
+    }
 }
@@ -0,0 +1,163 @@
+// Supervisor Module
+// Restart policy and log forwarding of the processes started by the
+// supervisor: the broker and the nodes of a network.
+
+use std::convert::TryFrom;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+use std::time::Duration;
+
+use yaml_rust::{Yaml, YamlLoader};
+
+/// Delay before the first restart, doubled with each further restart
+const RESTART_DELAY: Duration = Duration::from_millis(500);
+
+/// Upper bound of the restart delay
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RestartPolicy {
+    Never,
+    /// Restart processes that exit with an error or a signal
+    OnFailure,
+    Always,
+}
+
+impl FromStr for RestartPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" => Ok(RestartPolicy::Never),
+            "on-failure" => Ok(RestartPolicy::OnFailure),
+            "always" => Ok(RestartPolicy::Always),
+            _ => Err(format!("Unknown restart policy {}, expected never, on-failure or always", s)),
+        }
+    }
+}
+
+/// Supervisor settings of the network description
+#[derive(Clone, Debug, PartialEq)]
+pub struct SupervisorConfig {
+    /// Broker binary, relative to the network description. No broker
+    /// is started without it.
+    pub broker: Option<String>,
+    pub restart: RestartPolicy,
+    /// Restarts per process before it is given up
+    pub max_restarts: u32,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            broker: None,
+            restart: RestartPolicy::OnFailure,
+            max_restarts: 3,
+        }
+    }
+}
+
+impl SupervisorConfig {
+    /// Reads the optional `supervisor` section of a network description
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let docs = YamlLoader::load_from_str(s).map_err(|e| format!("Invalid network description: {}", e))?;
+        let mut config = Self::default();
+        let section = match docs.first() {
+            Some(doc) => &doc["supervisor"],
+            None => return Ok(config),
+        };
+        if let Yaml::String(broker) = &section["broker"] {
+            config.broker = Some(broker.clone());
+        }
+        if let Some(policy) = section["restart"].as_str() {
+            config.restart = policy.parse()?;
+        }
+        // 0 disables restarts
+        match &section["max_restarts"] {
+            Yaml::BadValue => (),
+            n => {
+                config.max_restarts = n
+                    .as_i64()
+                    .and_then(|n| u32::try_from(n).ok())
+                    .ok_or_else(|| "max_restarts must be a non-negative number".to_string())?
+            }
+        }
+        Ok(config)
+    }
+
+    /// Decides if a process that exited after the given number of
+    /// restarts is started again
+    pub fn should_restart(&self, success: bool, restarts: u32) -> bool {
+        restarts < self.max_restarts
+            && match self.restart {
+                RestartPolicy::Never => false,
+                RestartPolicy::OnFailure => !success,
+                RestartPolicy::Always => true,
+            }
+    }
+}
+
+/// Delay before the given restart (0 for the first one)
+pub fn restart_delay(restarts: u32) -> Duration {
+    RESTART_DELAY
+        .checked_mul(1 << restarts.min(16))
+        .unwrap_or(MAX_RESTART_DELAY)
+        .min(MAX_RESTART_DELAY)
+}
+
+/// Copies the lines of a process output with the process name as
+/// prefix until the output is closed
+pub fn forward_lines<R: BufRead, W: Write>(name: &str, reader: R, writer: &mut W) {
+    for line in reader.lines() {
+        match line {
+            Ok(line) => {
+                if writeln!(writer, "[{}] {}", name, line).is_err() {
+                    break;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn supervisor_section_is_parsed() {
+        let config = SupervisorConfig::parse("supervisor:\n  broker: zmqbroker\n  restart: always\n  max_restarts: 5\nregions: []").unwrap();
+        assert_eq!(config.broker, Some("zmqbroker".to_string()));
+        assert_eq!(config.restart, RestartPolicy::Always);
+        assert_eq!(config.max_restarts, 5);
+        assert_eq!(SupervisorConfig::parse("regions: []").unwrap(), SupervisorConfig::default());
+        assert!(SupervisorConfig::parse("supervisor:\n  restart: sometimes").is_err());
+        assert_eq!(SupervisorConfig::parse("supervisor:\n  max_restarts: 0").unwrap().max_restarts, 0);
+        for n in ["-1", "many"] {
+            let error = SupervisorConfig::parse(&format!("supervisor:\n  max_restarts: {}", n)).unwrap_err();
+            assert_eq!(error, "max_restarts must be a non-negative number");
+        }
+    }
+
+    #[test]
+    fn restarts_follow_policy() {
+        let mut config = SupervisorConfig::default();
+        assert!(config.should_restart(false, 0));
+        assert!(!config.should_restart(true, 0));
+        assert!(!config.should_restart(false, 3));
+        config.restart = RestartPolicy::Always;
+        assert!(config.should_restart(true, 2));
+        config.restart = RestartPolicy::Never;
+        assert!(!config.should_restart(false, 0));
+        assert_eq!(restart_delay(0), RESTART_DELAY);
+        assert_eq!(restart_delay(1), RESTART_DELAY * 2);
+        assert_eq!(restart_delay(40), MAX_RESTART_DELAY);
+    }
+
+    #[test]
+    fn lines_are_prefixed() {
+        let mut out = vec![];
+        forward_lines("L1_SP", "State: READY\nDone.\n".as_bytes(), &mut out);
+        assert_eq!(String::from_utf8(out).unwrap(), "[L1_SP] State: READY\n[L1_SP] Done.\n");
+    }
+}
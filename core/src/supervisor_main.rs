@@ -0,0 +1,215 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use phtm::cli::{self, Args};
+use phtm::node::hierarchy::{self, NetworkSpec};
+use phtm::node::supervisor::{self, SupervisorConfig};
+use signal_hook::consts::{SIGINT, SIGTERM};
+
+/// Time the broker gets to bind its sockets before the nodes start
+const BROKER_STARTUP: Duration = Duration::from_millis(500);
+
+/// Time processes get to shut down before they are killed
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+const USAGE: &[&str] = &[
+    "Usage: phtm_supervisor [--bin FILE] [--broker FILE | --no-broker] [--restart never|on-failure|always]",
+    "                       [--max-restarts N] <network.yaml>",
+];
+
+/// Supervised process with its command line
+struct Process {
+    name: String,
+    bin: String,
+    args: Vec<String>,
+    child: Option<Child>,
+    restarts: u32,
+    /// Time of the next start of an exited process
+    restart_at: Option<Instant>,
+}
+
+impl Process {
+    fn new(name: &str, bin: &str, args: Vec<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            bin: bin.to_string(),
+            args,
+            child: None,
+            restarts: 0,
+            restart_at: None,
+        }
+    }
+
+    /// Starts the process and forwards its output with the name as prefix
+    fn start(&mut self) -> Result<(), String> {
+        let mut child = Command::new(&self.bin)
+            .args(&self.args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {}", self.bin, e))?;
+        if let Some(stdout) = child.stdout.take() {
+            let name = self.name.clone();
+            thread::spawn(move || supervisor::forward_lines(&name, BufReader::new(stdout), &mut io::stdout()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            let name = self.name.clone();
+            thread::spawn(move || supervisor::forward_lines(&name, BufReader::new(stderr), &mut io::stderr()));
+        }
+        self.child = Some(child);
+        Ok(())
+    }
+
+    fn is_running(&self) -> bool {
+        self.child.is_some() || self.restart_at.is_some()
+    }
+
+    /// Reaps the exited process and schedules its restart
+    fn supervise(&mut self, config: &SupervisorConfig) {
+        if let Some(at) = self.restart_at {
+            if Instant::now() >= at {
+                self.restart_at = None;
+                self.restarts += 1;
+                println!("Restarting {} ({}/{})", self.name, self.restarts, config.max_restarts);
+                if let Err(e) = self.start() {
+                    println!("{}", e);
+                }
+            }
+            return;
+        }
+        let status = match self.child.as_mut().map(|c| c.try_wait()) {
+            Some(Ok(Some(status))) => status,
+            Some(Ok(None)) | None => return,
+            Some(Err(e)) => {
+                println!("Failed to wait for {}: {}", self.name, e);
+                return;
+            }
+        };
+        self.child = None;
+        println!("{} exited ({})", self.name, status);
+        if config.should_restart(status.success(), self.restarts) {
+            self.restart_at = Some(Instant::now() + supervisor::restart_delay(self.restarts));
+        }
+    }
+
+    /// Asks the process to shut down like the control command does
+    fn terminate(&self) {
+        if let Some(child) = &self.child {
+            // SAFETY: kill only sends a signal to the child process
+            unsafe {
+                libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+            }
+        }
+    }
+}
+
+/// Terminates the processes and kills those that do not exit in time
+fn stop(processes: &mut [Process]) {
+    for p in processes.iter_mut() {
+        p.restart_at = None;
+        p.terminate();
+    }
+    let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+    for p in processes.iter_mut() {
+        if let Some(mut child) = p.child.take() {
+            while matches!(child.try_wait(), Ok(None)) && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(50));
+            }
+            if matches!(child.try_wait(), Ok(None)) {
+                println!("Killing {}", p.name);
+                let _ = child.kill();
+            }
+            let _ = child.wait();
+        }
+    }
+}
+
+fn main() {
+    // Nodes run the headless binary next to the supervisor by default
+    let mut bin = env::current_exe()
+        .map(|p| p.with_file_name("phtm_headless").to_string_lossy().to_string())
+        .unwrap_or_else(|_| "phtm_headless".to_string());
+    let mut broker: Option<Option<String>> = None;
+    let mut restart = None;
+    let mut max_restarts = None;
+    let mut network = None;
+    let mut args = Args::new(USAGE);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bin" => bin = args.value(),
+            "--broker" => broker = Some(Some(args.value())),
+            "--no-broker" => broker = Some(None),
+            "--restart" => restart = Some(args.value()),
+            "--max-restarts" => max_restarts = Some(args.value()),
+            a if a.starts_with("--") || network.is_some() => args.usage(),
+            a => network = Some(a.to_string()),
+        }
+    }
+    let network = network.unwrap_or_else(|| cli::usage(USAGE));
+
+    // Program and broker paths of the network file are relative to it
+    let base_dir = Path::new(&network).parent().unwrap_or_else(|| Path::new("."));
+    let (mut config, regions) = fs::read_to_string(&network)
+        .map_err(|e| format!("Failed to read {}: {}", network, e))
+        .and_then(|s| {
+            let config = SupervisorConfig::parse(&s)?;
+            let regions = hierarchy::wire(&NetworkSpec::parse(&s)?, &bin, base_dir)?;
+            Ok((config, regions))
+        })
+        .unwrap_or_else(|e| cli::fail(e));
+    config.broker = match broker {
+        Some(broker) => broker,
+        None => config.broker.map(|b| base_dir.join(b).to_string_lossy().to_string()),
+    };
+    config.restart = restart.unwrap_or(config.restart);
+    config.max_restarts = max_restarts.unwrap_or(config.max_restarts);
+
+    let terminate = Arc::new(AtomicBool::new(false));
+    for signal in [SIGINT, SIGTERM].iter() {
+        signal_hook::flag::register(*signal, Arc::clone(&terminate)).expect("Failed to register signal handler");
+    }
+
+    let mut brokers: Vec<Process> = config.broker.iter().map(|b| Process::new("broker", b, vec![])).collect();
+    for p in brokers.iter_mut() {
+        if let Err(e) = p.start() {
+            cli::fail(e);
+        }
+        thread::sleep(BROKER_STARTUP);
+    }
+    println!("region\tid\tkeys\toutput");
+    let mut nodes: Vec<Process> = vec![];
+    for region in regions.iter() {
+        println!(
+            "{}\t{}\t{:?}\t{}",
+            region.name,
+            region.args.node_id,
+            region.args.keys,
+            region.args.output_key.unwrap_or(0)
+        );
+        let mut node = Process::new(&region.name, &bin, region.node_args());
+        if let Err(e) = node.start() {
+            println!("{}", e);
+            terminate.store(true, Ordering::Relaxed);
+        }
+        nodes.push(node);
+    }
+
+    // Supervise until all nodes are done or the supervisor is stopped
+    while !terminate.load(Ordering::Relaxed) && nodes.iter().any(|n| n.is_running()) {
+        for p in brokers.iter_mut().chain(nodes.iter_mut()) {
+            p.supervise(&config);
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    println!("Stopping network");
+    stop(&mut nodes);
+    stop(&mut brokers);
+    println!("Done.");
+}
@@ -0,0 +1,57 @@
+use std::fs;
+use std::process;
+
+use phtm::cli::{self, Args};
+use phtm::evolution::evaluation::Dataset;
+use phtm::evolution::sweep::{self, Mode, ResultsTable, SweepConfig};
+use phtm::node::source::Source;
+
+const DEFAULT_DATASET: &str = "../data/rec-center-hourly.csv";
+
+const USAGE: &[&str] = &[
+    "Usage: phtm_sweep --param NAME=v1,v2,..|NAME=min:max [--param ..]... [--samples N] [--metric METRIC]",
+    "                  [--data FILE] [--seed N] [--threads N] [--rows N] [--out FILE] <program.push>",
+    "METRIC: anomaly|predicted|duration for temporal memory, entropy|stability|noise|duration for spatial pooler programs",
+];
+
+fn main() {
+    let mut config = SweepConfig::default();
+    let mut data = DEFAULT_DATASET.to_string();
+    let mut out = "sweep.tsv".to_string();
+    let mut ranges = vec![];
+    let mut program = None;
+    let mut args = Args::new(USAGE);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--param" => match args.next().map(|a| a.parse()) {
+                Some(Ok(range)) => ranges.push(range),
+                Some(Err(e)) => {
+                    println!("{}", e);
+                    process::exit(2);
+                }
+                None => args.usage(),
+            },
+            "--samples" => config.mode = Mode::Random(args.value()),
+            "--metric" => config.metric = args.value(),
+            "--data" => data = args.value(),
+            "--seed" => config.seed = args.value(),
+            "--threads" => config.threads = args.value(),
+            "--rows" => config.evaluation.rows = args.value(),
+            "--out" => out = args.value(),
+            a if a.starts_with("--") || program.is_some() => args.usage(),
+            a => program = Some(a.to_string()),
+        }
+    }
+    let program = match program {
+        Some(p) if !ranges.is_empty() => p,
+        _ => cli::usage(USAGE),
+    };
+
+    let code = fs::read_to_string(&program).unwrap_or_else(|e| cli::fail(format!("Failed to read {}: {}", program, e)));
+    let dataset = Dataset::load(&data).unwrap_or_else(|e| cli::fail(e));
+    let runs = sweep::sweep(&Source::read_debug_code(code), &dataset, &ranges, &config)
+        .unwrap_or_else(|e| cli::fail(format!("Sweep failed: {}", e)));
+    let table = ResultsTable(&runs).to_string();
+    print!("{}", table);
+    fs::write(&out, table).expect("Failed to write results");
+}
@@ -1,10 +1,7 @@
 extern crate pushr;
 extern crate rand;
 extern crate time;
-#[macro_use]
-extern crate enum_primitive_derive;
 extern crate num_traits;
 
 pub mod node;
-#[path = "../../msg/rs/msg.rs"]
-pub mod shared;
+pub use phtm_msg::shared;
@@ -1,7 +1,7 @@
 import os
 import yaml
 
-with open(os.path.dirname(__file__) + "../msg_ids.yaml", 'r') as stream:
+with open(os.path.dirname(__file__) + "/../msg_ids.yaml", 'r') as stream:
     try:
         print('>> Reading yaml ...')
         data = yaml.safe_load(stream)
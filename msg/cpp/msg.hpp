@@ -0,0 +1,66 @@
+#pragma once
+
+#define ID_OFFSET 0
+#define TYPE_OFFSET 2
+#define CMD_OFFSET 4
+#define KEY_OFFSET 6
+#define PAYLOAD_OFFSET 8
+#define MSG_KEY_DIV 1000
+#define DEF_PL_SIZE 512
+
+namespace dh {
+
+enum MessageType : uint16_t  { 
+    UNDEFINED= 0,
+    CONFIGURATION= 1,
+    DATA= 2,
+    NETWORK= 3,
+    DEBUG= 4
+};
+enum MessageCommand : uint16_t  { 
+    RESERVED= 0,
+    READ= 1,
+    WRITE= 2,
+    PRINT= 3,
+    ACK= 4,
+    INPUT= 5,
+    REPLICATE= 6,
+    NACK= 7,
+    ANNOUNCE= 8,
+    HEARTBEAT= 9,
+    JOIN= 10,
+    LEAVE= 11,
+    PAUSE= 12,
+    RESUME= 13,
+    RESET= 14,
+    SHUTDOWN= 15,
+    STATE= 16,
+    METRICS= 17,
+    STEP= 18
+};
+enum MessageKey : uint16_t  { 
+    UNDEFINED= 0,
+    C_ACTBTS= 1,
+    C_RAWDAT= 2,
+    C_SDRLEN= 3,
+    C_PARAM= 4,
+    D_INPUT= 1001,
+    D_SPOOL= 1002,
+    D_REGION= 1003
+};
+enum NodeType : uint16_t  { 
+    UNDEFINED= 0,
+    SCALAR_ENCODER= 1,
+    SPATIAL_POOLER= 2,
+    TEMPORAL_MEMORY= 3
+};
+enum NodeState : uint16_t  { 
+    INITIALIZING= 0,
+    READY= 1,
+    COMPUTING= 2,
+    REPLICATING= 3,
+    DRAINING= 4,
+    DONE= 5,
+    FAILED= 6
+};
+}
@@ -0,0 +1,399 @@
+// Channel Module
+// Reliable request/reply side channel for configuration and control.
+// Requests carry a message id and are sent again until the receiver
+// answers with an ACK (or NACK) carrying the same id.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::message::Message;
+use crate::shared::msg::{MessageCommand, MessageKey, MessageType, PAYLOAD_OFFSET};
+
+/// Endpoint of the control router in the message broker
+pub const CONTROL_ENDPOINT: &str = "tcp://localhost:5556";
+
+/// Routing identity of the broker itself. Requests sent to it are
+/// handled by the node registry instead of being forwarded.
+pub const BROKER_IDENTITY: &str = "BROKER";
+
+/// Number of handled requests that are remembered to answer
+/// retries without applying them twice
+const REPLY_CACHE_SIZE: usize = 64;
+
+/// Routing identity of a node on the control channel
+pub fn node_identity(node_id: u16) -> String {
+    format!("N{:05}", node_id)
+}
+
+/// Creates a request that writes a named parameter of a node
+pub fn parameter_write(name: &str, value: &str) -> Message {
+    let mut m = Message {
+        data: vec![0; PAYLOAD_OFFSET],
+    };
+    m.create_header(MessageType::CONFIGURATION, MessageCommand::WRITE, MessageKey::C_PARAM);
+    m.set_payload(&mut format!("{} {}", name, value).into_bytes());
+    m
+}
+
+/// Creates the reply to a request: an ACK with an optional payload
+/// or a NACK containing the reason why the request failed.
+pub fn acknowledge(request: &Message, result: Result<Vec<u8>, String>) -> Message {
+    let mut m = Message {
+        data: request.data[..PAYLOAD_OFFSET].to_vec(),
+    };
+    match result {
+        Ok(mut payload) => {
+            m.set_cmd(MessageCommand::ACK);
+            m.set_payload(&mut payload);
+        }
+        Err(reason) => {
+            m.set_cmd(MessageCommand::NACK);
+            m.set_payload(&mut reason.into_bytes());
+        }
+    }
+    m
+}
+
+/// Extracts name and value from a parameter write request
+pub fn parse_parameter_write(msg: &Message) -> Option<(String, String)> {
+    let payload = msg.get_payload_string();
+    let mut it = payload.split_whitespace();
+    match (it.next(), it.next(), it.next()) {
+        (Some(name), Some(value), None) => Some((name.to_string(), value.to_string())),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ChannelConfig {
+    /// Time to wait for an acknowledge before a request is sent again
+    pub timeout: Duration,
+    /// Number of times a request is sent again before it is reported as failed
+    pub max_retries: usize,
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_millis(500),
+            max_retries: 3,
+        }
+    }
+}
+
+struct Pending {
+    target: Vec<u8>,
+    data: Vec<u8>,
+    sent_at: Instant,
+    attempts: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Delivery {
+    /// Request timed out and has to be sent again
+    Resend { target: Vec<u8>, data: Vec<u8> },
+    /// Request was not acknowledged after all retries
+    Failed { id: u16, target: Vec<u8> },
+}
+
+/// Keeps track of requests that have not been acknowledged yet
+pub struct Outbox {
+    config: ChannelConfig,
+    next_id: u16,
+    pending: HashMap<u16, Pending>,
+}
+
+impl Outbox {
+    pub fn new(config: ChannelConfig) -> Self {
+        Self {
+            config,
+            next_id: 1,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Assigns a fresh message id to the request and tracks it
+    /// until it is acknowledged. Id 0 is reserved for messages that
+    /// do not expect an acknowledge.
+    pub fn register(&mut self, target: &[u8], msg: &mut Message, now: Instant) -> u16 {
+        while self.next_id == 0 || self.pending.contains_key(&self.next_id) {
+            self.next_id = self.next_id.wrapping_add(1);
+        }
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        msg.set_id(id);
+        self.pending.insert(
+            id,
+            Pending {
+                target: target.to_vec(),
+                data: msg.data.clone(),
+                sent_at: now,
+                attempts: 1,
+            },
+        );
+        id
+    }
+
+    /// Removes the request with the given id. Returns false if the
+    /// id is unknown, e.g. for the acknowledge of a retry.
+    pub fn acknowledge(&mut self, id: u16) -> bool {
+        self.pending.remove(&id).is_some()
+    }
+
+    pub fn is_pending(&self, id: u16) -> bool {
+        self.pending.contains_key(&id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Returns the requests that timed out. Requests that exceeded
+    /// the number of retries are dropped and reported as failed.
+    pub fn expire(&mut self, now: Instant) -> Vec<Delivery> {
+        let mut deliveries = vec![];
+        let mut failed = vec![];
+        for (id, p) in self.pending.iter_mut() {
+            if now.duration_since(p.sent_at) < self.config.timeout {
+                continue;
+            }
+            if p.attempts > self.config.max_retries {
+                failed.push(*id);
+                continue;
+            }
+            p.attempts += 1;
+            p.sent_at = now;
+            deliveries.push(Delivery::Resend {
+                target: p.target.clone(),
+                data: p.data.clone(),
+            });
+        }
+        for id in failed {
+            if let Some(p) = self.pending.remove(&id) {
+                deliveries.push(Delivery::Failed { id, target: p.target });
+            }
+        }
+        deliveries
+    }
+}
+
+/// Inbound request together with the identity of its sender
+pub struct Request {
+    pub sender: Vec<u8>,
+    pub msg: Message,
+}
+
+pub enum ChannelEvent {
+    Request(Request),
+    Acked(u16, Message),
+    Nacked(u16, String),
+}
+
+/// DEALER socket connected to the control router of the broker. The
+/// router forwards each message to the identity in its first frame.
+pub struct ControlChannel {
+    socket: zmq::Socket,
+    outbox: Outbox,
+    replies: VecDeque<(Vec<u8>, u16, Vec<u8>)>,
+}
+
+impl ControlChannel {
+    /// Connects to the broker. Nodes pass their identity so that
+    /// requests can be routed to them, clients may stay anonymous.
+    pub fn connect(
+        context: &zmq::Context,
+        endpoint: &str,
+        identity: Option<&str>,
+        config: ChannelConfig,
+    ) -> zmq::Result<Self> {
+        let socket = context.socket(zmq::DEALER)?;
+        if let Some(identity) = identity {
+            socket.set_identity(identity.as_bytes())?;
+        }
+        socket.set_linger(500)?;
+        socket.connect(endpoint)?;
+        Ok(Self {
+            socket,
+            outbox: Outbox::new(config),
+            replies: VecDeque::new(),
+        })
+    }
+
+    /// Sends a request to the given identity. Returns the message id
+    /// the acknowledge will refer to.
+    pub fn request(&mut self, target: &str, mut msg: Message) -> zmq::Result<u16> {
+        let id = self
+            .outbox
+            .register(target.as_bytes(), &mut msg, Instant::now());
+        self.socket
+            .send_multipart([target.as_bytes(), &msg.data[..]].iter(), 0)?;
+        Ok(id)
+    }
+
+    /// Sends a message without expecting an acknowledge, e.g. a heartbeat
+    pub fn notify(&mut self, target: &str, msg: &Message) -> zmq::Result<()> {
+        self.socket
+            .send_multipart([target.as_bytes(), &msg.data[..]].iter(), 0)
+    }
+
+    /// Answers a request with an ACK or, if it could not be
+    /// applied, with a NACK containing the reason.
+    pub fn reply(&mut self, request: &Request, result: Result<Vec<u8>, String>) -> zmq::Result<()> {
+        let m = acknowledge(&request.msg, result);
+        self.socket
+            .send_multipart([&request.sender[..], &m.data[..]].iter(), 0)?;
+        if self.replies.len() >= REPLY_CACHE_SIZE {
+            self.replies.pop_front();
+        }
+        self.replies
+            .push_back((request.sender.clone(), request.msg.get_id(), m.data));
+        Ok(())
+    }
+
+    /// Waits up to timeout_ms for the next inbound request or
+    /// acknowledge. Retries of requests that were already answered
+    /// are acknowledged again and not returned.
+    pub fn poll(&mut self, timeout_ms: i64) -> zmq::Result<Option<ChannelEvent>> {
+        let mut timeout = timeout_ms;
+        loop {
+            if self.socket.poll(zmq::POLLIN, timeout)? == 0 {
+                return Ok(None);
+            }
+            timeout = 0;
+            let mut frames = self.socket.recv_multipart(0)?;
+            if frames.len() != 2 || frames[1].len() < PAYLOAD_OFFSET {
+                println!("Dropping malformed control message ({} frames)", frames.len());
+                continue;
+            }
+            let msg = Message {
+                data: frames.pop().unwrap(),
+            };
+            let sender = frames.pop().unwrap();
+            let id = msg.get_id();
+            match msg.get_cmd() {
+                Some(MessageCommand::ACK) => {
+                    if self.outbox.acknowledge(id) {
+                        return Ok(Some(ChannelEvent::Acked(id, msg)));
+                    }
+                }
+                Some(MessageCommand::NACK) => {
+                    if self.outbox.acknowledge(id) {
+                        return Ok(Some(ChannelEvent::Nacked(id, msg.get_payload_string())));
+                    }
+                }
+                _ if id == 0 => {
+                    // Notifications are not acknowledged
+                    return Ok(Some(ChannelEvent::Request(Request { sender, msg })));
+                }
+                _ => {
+                    let cached = self
+                        .replies
+                        .iter()
+                        .find(|(s, i, _)| *s == sender && *i == id)
+                        .map(|(_, _, reply)| reply.clone());
+                    match cached {
+                        Some(reply) => {
+                            self.socket
+                                .send_multipart([&sender[..], &reply[..]].iter(), 0)?;
+                        }
+                        None => return Ok(Some(ChannelEvent::Request(Request { sender, msg }))),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sends timed out requests again. Returns the ids of requests
+    /// that were given up.
+    pub fn retry(&mut self) -> zmq::Result<Vec<u16>> {
+        let mut failed = vec![];
+        for delivery in self.outbox.expire(Instant::now()) {
+            match delivery {
+                Delivery::Resend { target, data } => {
+                    self.socket
+                        .send_multipart([&target[..], &data[..]].iter(), 0)?;
+                }
+                Delivery::Failed { id, .. } => failed.push(id),
+            }
+        }
+        Ok(failed)
+    }
+
+    pub fn is_pending(&self, id: u16) -> bool {
+        self.outbox.is_pending(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> ChannelConfig {
+        ChannelConfig {
+            timeout: Duration::from_millis(100),
+            max_retries: 2,
+        }
+    }
+
+    #[test]
+    fn outbox_assigns_distinct_nonzero_ids() {
+        let mut outbox = Outbox::new(test_config());
+        let now = Instant::now();
+        let mut m1 = parameter_write("A", "1");
+        let mut m2 = parameter_write("B", "2");
+        let id1 = outbox.register(b"N00001", &mut m1, now);
+        let id2 = outbox.register(b"N00001", &mut m2, now);
+        assert_ne!(id1, 0);
+        assert_ne!(id1, id2);
+        assert_eq!(m1.get_id(), id1);
+        assert_eq!(m2.get_id(), id2);
+    }
+
+    #[test]
+    fn outbox_resends_until_retries_exceeded() {
+        let mut outbox = Outbox::new(test_config());
+        let start = Instant::now();
+        let mut m = parameter_write("A", "1");
+        let id = outbox.register(b"N00001", &mut m, start);
+        assert!(outbox.expire(start).is_empty());
+        for i in 1..=2 {
+            let deliveries = outbox.expire(start + Duration::from_millis(100 * i));
+            assert_eq!(
+                deliveries,
+                vec![Delivery::Resend {
+                    target: b"N00001".to_vec(),
+                    data: m.data.clone()
+                }]
+            );
+        }
+        let deliveries = outbox.expire(start + Duration::from_millis(300));
+        assert_eq!(
+            deliveries,
+            vec![Delivery::Failed {
+                id,
+                target: b"N00001".to_vec()
+            }]
+        );
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn outbox_stops_tracking_acknowledged_requests() {
+        let mut outbox = Outbox::new(test_config());
+        let start = Instant::now();
+        let mut m = parameter_write("A", "1");
+        let id = outbox.register(b"N00001", &mut m, start);
+        assert!(outbox.acknowledge(id));
+        assert!(!outbox.acknowledge(id));
+        assert!(outbox.expire(start + Duration::from_secs(1)).is_empty());
+    }
+
+    #[test]
+    fn parameter_write_roundtrip() {
+        let m = parameter_write("PERM_INCREMENT", "0.02");
+        assert_eq!(
+            parse_parameter_write(&m),
+            Some(("PERM_INCREMENT".to_string(), "0.02".to_string()))
+        );
+    }
+}
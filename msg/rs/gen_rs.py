@@ -22,7 +22,7 @@ with open(os.path.dirname(__file__) + '/msg.rs', 'w') as fout:
 
     for x,y in data.items():
         if type(y) is list:
-            fout.write('#[derive(Primitive)]\n')
+            fout.write('#[derive(Primitive, Debug, Clone, Copy, PartialEq, Eq)]\n')
             fout.write('pub enum ' + x + ' { \n')
             for yi in y[:-1]:
                 k = list(yi)[0];
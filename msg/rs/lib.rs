@@ -0,0 +1,13 @@
+// Message bus definitions shared by the nodes and the broker: the
+// generated message constants, the binary message format, the control
+// channel and the node registry.
+
+#[macro_use]
+extern crate enum_primitive_derive;
+extern crate num_traits;
+
+pub mod channel;
+pub mod message;
+pub mod network;
+#[path = "msg.rs"]
+pub mod shared;
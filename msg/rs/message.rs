@@ -3,7 +3,6 @@ use crate::shared::msg::{MessageCommand, MessageKey, MessageType};
 use crate::shared::msg::{
     CMD_OFFSET, DEF_PL_SIZE, ID_OFFSET, KEY_OFFSET, PAYLOAD_OFFSET, TYPE_OFFSET,
 };
-use crate::pushr::push::vector::{IntVector};
 
 pub struct Message {
     pub data: Vec<u8>,
@@ -24,11 +23,13 @@ impl Message {
         self.set_key(msg_key);
     }
 
-    pub fn parse_to(&self, sdr: &mut Vec<bool>) {
-        for i in 0..sdr.len() {
-            let byte = i >> 3 + PAYLOAD_OFFSET;
+    /// Reads the payload bits into the SDR. Bits beyond the message
+    /// length are cleared.
+    pub fn parse_to(&self, sdr: &mut [bool]) {
+        for (i, b) in sdr.iter_mut().enumerate() {
+            let byte = (i >> 3) + PAYLOAD_OFFSET;
             let bit = i % 8;
-            sdr[i] = self.data[byte] & (1 << bit) != 0;
+            *b = self.data.get(byte).is_some_and(|d| d & (1 << bit) != 0);
         }
     }
 
@@ -78,6 +79,10 @@ impl Message {
         return u16::from_be_bytes([self.data[*offset], self.data[*offset + 1]]);
     }
 
+    pub fn get_id(&self) -> u16 {
+        self.get_prop(&ID_OFFSET)
+    }
+
     pub fn get_type(&self) -> Option<MessageType> {
         return MessageType::from_u16(self.get_prop(&TYPE_OFFSET));
     }
@@ -96,6 +101,10 @@ impl Message {
         self.data[*offset + 1] = raw_prop[1];
     }
 
+    pub fn set_id(&mut self, id: u16) {
+        self.set_prop(&ID_OFFSET, &id)
+    }
+
     pub fn set_type(&mut self, msg_type: MessageType) {
         if let Some(v) = msg_type.to_u16() {
             self.set_prop(&TYPE_OFFSET, &v)
@@ -114,15 +123,18 @@ impl Message {
         }
     }
 
-    pub fn set_headers(&mut self, headers: &IntVector) {
-        if headers.values.len() > 0 {
-            self.set_key(MessageKey::from_u16(headers.values[0] as u16).unwrap());
+    /// Sets key, command and type from a header vector of the output
+    /// stack. Keys are copied as is, unknown commands and types are
+    /// ignored.
+    pub fn set_headers(&mut self, headers: &[i32]) {
+        if let Some(key) = headers.first() {
+            self.set_prop(&KEY_OFFSET, &(*key as u16));
         }
-        if headers.values.len() > 1 {
-            self.set_cmd(MessageCommand::from_u16(headers.values[1] as u16).unwrap());
+        if let Some(cmd) = headers.get(1).and_then(|c| MessageCommand::from_u16(*c as u16)) {
+            self.set_cmd(cmd);
         }
-        if headers.values.len() > 2 {
-            self.set_type(MessageType::from_u16(headers.values[2] as u16).unwrap());
+        if let Some(msg_type) = headers.get(2).and_then(|t| MessageType::from_u16(*t as u16)) {
+            self.set_type(msg_type);
         }
     }
 
@@ -131,6 +143,17 @@ impl Message {
         self.data.resize(PAYLOAD_OFFSET, 0);
         self.data.append(payload);
     }
+
+    /// Interprets the payload as UTF-8 string. Trailing zero bytes
+    /// from the default payload size are dropped.
+    pub fn get_payload_string(&self) -> String {
+        if self.data.len() <= PAYLOAD_OFFSET {
+            return String::new();
+        }
+        let payload = &self.data[PAYLOAD_OFFSET..];
+        let end = payload.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+        String::from_utf8_lossy(&payload[..end]).to_string()
+    }
 }
 
 #[cfg(test)]
@@ -170,4 +193,29 @@ mod tests {
         m.clear_payload_bit(&((idx + 5) as usize)); // 0 remains set
         assert_eq!(m.data[offset], expected);
     }
+
+    #[test]
+    fn test_parse_to() {
+        let mut m = Message {
+            data: vec![0; 2 + PAYLOAD_OFFSET],
+        };
+        m.set_payload_bit(&1);
+        m.set_payload_bit(&9);
+        let mut sdr = vec![true; 20];
+        m.parse_to(&mut sdr);
+        let active: Vec<usize> = (0..sdr.len()).filter(|i| sdr[*i]).collect();
+        assert_eq!(active, vec![1, 9]);
+    }
+
+    #[test]
+    fn test_payload_string_roundtrip() {
+        let mut m = Message {
+            data: vec![0; DEF_PL_SIZE + PAYLOAD_OFFSET],
+        };
+        m.create_header(MessageType::CONFIGURATION, MessageCommand::WRITE, MessageKey::C_PARAM);
+        m.set_id(42);
+        m.set_payload(&mut "PERM_INCREMENT 0.02".to_string().into_bytes());
+        assert_eq!(m.get_id(), 42);
+        assert_eq!(m.get_payload_string(), "PERM_INCREMENT 0.02");
+    }
 }
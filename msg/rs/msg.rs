@@ -12,14 +12,15 @@ pub const PAYLOAD_OFFSET: usize = 8;
 pub const MSG_KEY_DIV: usize = 1000;
 pub const DEF_PL_SIZE: usize = 512;
 
-#[derive(Primitive)]
+#[derive(Primitive, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MessageType { 
     UNDEFINED = 0,
     CONFIGURATION = 1,
     DATA = 2,
-    NETWORK= 3
+    NETWORK = 3,
+    DEBUG= 4
 }
-#[derive(Primitive)]
+#[derive(Primitive, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MessageCommand { 
     RESERVED = 0,
     READ = 1,
@@ -27,21 +28,46 @@ pub enum MessageCommand {
     PRINT = 3,
     ACK = 4,
     INPUT = 5,
-    REPLICATE= 6
+    REPLICATE = 6,
+    NACK = 7,
+    ANNOUNCE = 8,
+    HEARTBEAT = 9,
+    JOIN = 10,
+    LEAVE = 11,
+    PAUSE = 12,
+    RESUME = 13,
+    RESET = 14,
+    SHUTDOWN = 15,
+    STATE = 16,
+    METRICS = 17,
+    STEP= 18
 }
-#[derive(Primitive)]
+#[derive(Primitive, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MessageKey { 
     UNDEFINED = 0,
     C_ACTBTS = 1,
     C_RAWDAT = 2,
     C_SDRLEN = 3,
+    C_PARAM = 4,
     D_INPUT = 1001,
-    D_SPOOL= 1002
+    D_SPOOL = 1002,
+    D_REGION= 1003
 }
-#[derive(Primitive)]
+#[derive(Primitive, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum NodeType { 
     UNDEFINED = 0,
     SCALAR_ENCODER = 1,
-    SPATIAL_POOLER= 2
+    SPATIAL_POOLER = 2,
+    TEMPORAL_MEMORY= 3
+}
+#[derive(Primitive, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeState { 
+    INITIALIZING = 0,
+    READY = 1,
+    COMPUTING = 2,
+    REPLICATING = 3,
+    DRAINING = 4,
+    DONE = 5,
+    FAILED= 6
 }
 }
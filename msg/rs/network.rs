@@ -0,0 +1,251 @@
+// Network Module
+// Node announcements, heartbeats and the registry of running nodes
+// kept by the broker.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::message::Message;
+use crate::num_traits::{FromPrimitive, ToPrimitive};
+use crate::shared::msg::{MessageCommand, MessageKey, MessageType, NodeType, PAYLOAD_OFFSET};
+
+/// Interval between two heartbeats of a node
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Nodes without heartbeat for this duration are considered dead
+pub const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Description of a running node as announced on the network
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeInfo {
+    pub node_id: u16,
+    pub node_type: NodeType,
+    pub program_hash: u64,
+    pub keys: Vec<u16>,
+}
+
+impl NodeInfo {
+    /// Encodes the node info as big endian payload:
+    /// id (u16), type (u16), program hash (u64), #keys (u16), keys (u16)
+    pub fn to_payload(&self) -> Vec<u8> {
+        let mut payload = vec![];
+        payload.extend_from_slice(&self.node_id.to_be_bytes());
+        payload.extend_from_slice(&self.node_type.to_u16().unwrap_or(0).to_be_bytes());
+        payload.extend_from_slice(&self.program_hash.to_be_bytes());
+        payload.extend_from_slice(&(self.keys.len() as u16).to_be_bytes());
+        for k in self.keys.iter() {
+            payload.extend_from_slice(&k.to_be_bytes());
+        }
+        payload
+    }
+
+    /// Decodes a node info from the start of the payload. Returns the
+    /// node info and the number of bytes read.
+    pub fn from_payload(payload: &[u8]) -> Option<(NodeInfo, usize)> {
+        let read_u16 = |pos: usize| -> Option<u16> {
+            payload.get(pos..pos + 2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+        };
+        let node_id = read_u16(0)?;
+        let node_type = NodeType::from_u16(read_u16(2)?).unwrap_or(NodeType::UNDEFINED);
+        let mut hash = [0u8; 8];
+        hash.copy_from_slice(payload.get(4..12)?);
+        let num_keys = read_u16(12)? as usize;
+        let mut keys = vec![];
+        for i in 0..num_keys {
+            keys.push(read_u16(14 + 2 * i)?);
+        }
+        Some((
+            NodeInfo {
+                node_id,
+                node_type,
+                program_hash: u64::from_be_bytes(hash),
+                keys,
+            },
+            14 + 2 * num_keys,
+        ))
+    }
+
+    /// Decodes a list of node infos as sent by the registry
+    pub fn list_from_payload(payload: &[u8]) -> Vec<NodeInfo> {
+        let mut nodes = vec![];
+        let mut pos = 0;
+        while let Some((info, len)) = NodeInfo::from_payload(&payload[pos..]) {
+            nodes.push(info);
+            pos += len;
+        }
+        nodes
+    }
+
+    /// Creates a network message of the given command (ANNOUNCE,
+    /// HEARTBEAT, JOIN or LEAVE) that carries this node info.
+    pub fn to_message(&self, cmd: MessageCommand) -> Message {
+        let mut m = Message {
+            data: vec![0; PAYLOAD_OFFSET],
+        };
+        m.create_header(MessageType::NETWORK, cmd, MessageKey::UNDEFINED);
+        m.set_payload(&mut self.to_payload());
+        m
+    }
+
+    /// Reads the node info from the payload of a network message
+    pub fn from_message(msg: &Message) -> Option<NodeInfo> {
+        if msg.data.len() <= PAYLOAD_OFFSET {
+            return None;
+        }
+        NodeInfo::from_payload(&msg.data[PAYLOAD_OFFSET..]).map(|(info, _)| info)
+    }
+}
+
+impl fmt::Display for NodeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:5} {:16} {:016x} {:?}",
+            self.node_id,
+            format!("{:?}", self.node_type),
+            self.program_hash,
+            self.keys
+        )
+    }
+}
+
+/// Creates a network request without payload, e.g. READ of the
+/// registry or lifecycle commands
+pub fn request(cmd: MessageCommand) -> Message {
+    let mut m = Message {
+        data: vec![0; PAYLOAD_OFFSET],
+    };
+    m.create_header(MessageType::NETWORK, cmd, MessageKey::UNDEFINED);
+    m
+}
+
+/// Smallest node id that is not taken by one of the given nodes. Ids
+/// are only reserved once the new node announces itself.
+pub fn free_node_id(nodes: &[NodeInfo]) -> Option<u16> {
+    (1..=u16::MAX).find(|id| !nodes.iter().any(|n| n.node_id == *id))
+}
+
+struct Entry {
+    info: NodeInfo,
+    last_seen: Instant,
+}
+
+/// Live table of the nodes in the network
+pub struct Registry {
+    timeout: Duration,
+    nodes: BTreeMap<u16, Entry>,
+}
+
+impl Registry {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            nodes: BTreeMap::new(),
+        }
+    }
+
+    /// Adds or refreshes a node. Returns true if the node joined,
+    /// i.e. it was not known before.
+    pub fn update(&mut self, info: NodeInfo, now: Instant) -> bool {
+        let joined = !self.nodes.contains_key(&info.node_id);
+        self.nodes.insert(
+            info.node_id,
+            Entry {
+                info,
+                last_seen: now,
+            },
+        );
+        joined
+    }
+
+    /// Removes a node that left the network
+    pub fn remove(&mut self, node_id: u16) -> Option<NodeInfo> {
+        self.nodes.remove(&node_id).map(|e| e.info)
+    }
+
+    /// Removes and returns the nodes whose heartbeat timed out
+    pub fn expire(&mut self, now: Instant) -> Vec<NodeInfo> {
+        let timeout = self.timeout;
+        let dead: Vec<u16> = self
+            .nodes
+            .iter()
+            .filter(|(_, e)| now.duration_since(e.last_seen) > timeout)
+            .map(|(id, _)| *id)
+            .collect();
+        dead.into_iter().filter_map(|id| self.remove(id)).collect()
+    }
+
+    /// Nodes ordered by id
+    pub fn nodes(&self) -> Vec<&NodeInfo> {
+        self.nodes.values().map(|e| &e.info).collect()
+    }
+
+    /// Encodes the table as concatenation of node info payloads
+    pub fn to_payload(&self) -> Vec<u8> {
+        self.nodes
+            .values()
+            .flat_map(|e| e.info.to_payload())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_info(node_id: u16) -> NodeInfo {
+        NodeInfo {
+            node_id,
+            node_type: NodeType::SPATIAL_POOLER,
+            program_hash: 0xdead_beef_0123_4567,
+            keys: vec![1001, 1002],
+        }
+    }
+
+    #[test]
+    fn node_info_message_roundtrip() {
+        let info = test_info(7);
+        let m = info.to_message(MessageCommand::ANNOUNCE);
+        assert_eq!(m.get_cmd(), Some(MessageCommand::ANNOUNCE));
+        assert_eq!(NodeInfo::from_message(&m), Some(info));
+    }
+
+    #[test]
+    fn node_info_list_roundtrip() {
+        let mut registry = Registry::new(HEARTBEAT_TIMEOUT);
+        let now = Instant::now();
+        registry.update(test_info(3), now);
+        registry.update(test_info(1), now);
+        let nodes = NodeInfo::list_from_payload(&registry.to_payload());
+        assert_eq!(nodes, vec![test_info(1), test_info(3)]);
+    }
+
+    #[test]
+    fn free_node_id_fills_gaps() {
+        assert_eq!(free_node_id(&[]), Some(1));
+        assert_eq!(free_node_id(&[test_info(1), test_info(3)]), Some(2));
+        assert_eq!(free_node_id(&[test_info(2), test_info(1)]), Some(3));
+    }
+
+    #[test]
+    fn registry_reports_join_once() {
+        let mut registry = Registry::new(HEARTBEAT_TIMEOUT);
+        let now = Instant::now();
+        assert!(registry.update(test_info(1), now));
+        assert!(!registry.update(test_info(1), now + HEARTBEAT_INTERVAL));
+        assert_eq!(registry.nodes().len(), 1);
+    }
+
+    #[test]
+    fn registry_expires_silent_nodes() {
+        let mut registry = Registry::new(Duration::from_secs(5));
+        let start = Instant::now();
+        registry.update(test_info(1), start);
+        registry.update(test_info(2), start);
+        registry.update(test_info(2), start + Duration::from_secs(4));
+        let dead = registry.expire(start + Duration::from_secs(6));
+        assert_eq!(dead, vec![test_info(1)]);
+        assert_eq!(registry.nodes(), vec![&test_info(2)]);
+    }
+}
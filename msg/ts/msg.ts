@@ -11,6 +11,7 @@ export enum MessageType {
     CONFIGURATION= 1,
     DATA= 2,
     NETWORK= 3,
+    DEBUG= 4,
 }
 
 export enum MessageCommand { 
@@ -21,6 +22,18 @@ export enum MessageCommand {
     ACK= 4,
     INPUT= 5,
     REPLICATE= 6,
+    NACK= 7,
+    ANNOUNCE= 8,
+    HEARTBEAT= 9,
+    JOIN= 10,
+    LEAVE= 11,
+    PAUSE= 12,
+    RESUME= 13,
+    RESET= 14,
+    SHUTDOWN= 15,
+    STATE= 16,
+    METRICS= 17,
+    STEP= 18,
 }
 
 export enum MessageKey { 
@@ -28,13 +41,26 @@ export enum MessageKey {
     C_ACTBTS= 1,
     C_RAWDAT= 2,
     C_SDRLEN= 3,
+    C_PARAM= 4,
     D_INPUT= 1001,
     D_SPOOL= 1002,
+    D_REGION= 1003,
 }
 
 export enum NodeType { 
     UNDEFINED= 0,
     SCALAR_ENCODER= 1,
     SPATIAL_POOLER= 2,
+    TEMPORAL_MEMORY= 3,
+}
+
+export enum NodeState { 
+    INITIALIZING= 0,
+    READY= 1,
+    COMPUTING= 2,
+    REPLICATING= 3,
+    DRAINING= 4,
+    DONE= 5,
+    FAILED= 6,
 }
 
@@ -1,4 +1,10 @@
 use std::thread;
+use std::time::Instant;
+
+use phtm_msg::channel;
+use phtm_msg::message::Message;
+use phtm_msg::network::{self, NodeInfo, Registry};
+use phtm_msg::shared::msg::{MessageCommand, MessageType, PAYLOAD_OFFSET};
 
 fn trace_task(context: &zmq::Context) {
     println!("Starting trace task");
@@ -16,6 +22,103 @@ fn trace_task(context: &zmq::Context) {
     }
 }
 
+/// Publishes a join or leave event of a node on the bus
+fn publish_event(publisher: &zmq::Socket, info: &NodeInfo, cmd: MessageCommand) {
+    let m = info.to_message(cmd);
+    println!("Node {:?}: {}", cmd, info);
+    let topic = m.get_topic();
+    if let Err(e) = publisher.send_multipart([topic.as_bytes(), &m.data].iter(), 0) {
+        println!("Failed publishing {:?} of node {}: {}", cmd, info.node_id, e);
+    }
+}
+
+/// Handles network messages addressed to the broker. Returns the
+/// reply for requests, notifications (id 0) are not answered.
+fn handle_network(registry: &mut Registry, publisher: &zmq::Socket, msg: &Message) -> Option<Message> {
+    let result = match (msg.get_type(), msg.get_cmd()) {
+        (Some(MessageType::NETWORK), Some(MessageCommand::ANNOUNCE))
+        | (Some(MessageType::NETWORK), Some(MessageCommand::HEARTBEAT)) => match NodeInfo::from_message(msg) {
+            Some(info) => {
+                if registry.update(info.clone(), Instant::now()) {
+                    publish_event(publisher, &info, MessageCommand::JOIN);
+                }
+                Ok(vec![])
+            }
+            None => Err("Malformed node info".to_string()),
+        },
+        (Some(MessageType::NETWORK), Some(MessageCommand::LEAVE)) => match NodeInfo::from_message(msg) {
+            Some(info) => {
+                if let Some(info) = registry.remove(info.node_id) {
+                    publish_event(publisher, &info, MessageCommand::LEAVE);
+                }
+                Ok(vec![])
+            }
+            None => Err("Malformed node info".to_string()),
+        },
+        (Some(MessageType::NETWORK), Some(MessageCommand::READ)) => Ok(registry.to_payload()),
+        _ => Err(format!("Unsupported request {}", msg.get_topic())),
+    };
+    if msg.get_id() == 0 {
+        return None;
+    }
+    Some(channel::acknowledge(msg, result))
+}
+
+/// Routes control messages between DEALER sockets. Each message
+/// arrives as [sender, target, payload] and is forwarded as
+/// [target, sender, payload], so replies take the same way back.
+/// Messages addressed to the broker update the node registry, whose
+/// join and leave events are published on the bus.
+fn control_task(context: &zmq::Context) {
+    println!("Starting control task");
+    let router = context.socket(zmq::ROUTER).unwrap();
+    router
+        .set_router_mandatory(true)
+        .expect("failed setting router mandatory");
+    router
+        .bind("tcp://*:5556")
+        .expect("failed binding control socket");
+    let publisher = context.socket(zmq::PUB).unwrap();
+    publisher
+        .connect("inproc://events")
+        .expect("failed connecting event publisher");
+    let mut registry = Registry::new(network::HEARTBEAT_TIMEOUT);
+    loop {
+        for info in registry.expire(Instant::now()) {
+            publish_event(&publisher, &info, MessageCommand::LEAVE);
+        }
+        if router.poll(zmq::POLLIN, 500).expect("Failed polling control socket") == 0 {
+            continue;
+        }
+        let frames = router.recv_multipart(0).expect("Failed receiving control message");
+        if frames.len() != 3 {
+            println!("Dropping control message with {} frames", frames.len());
+            continue;
+        }
+        let (sender, target, payload) = (&frames[0], &frames[1], &frames[2]);
+        if target.as_slice() == channel::BROKER_IDENTITY.as_bytes() {
+            if payload.len() < PAYLOAD_OFFSET {
+                continue;
+            }
+            let msg = Message { data: payload.clone() };
+            if let Some(reply) = handle_network(&mut registry, &publisher, &msg) {
+                if let Err(e) = router.send_multipart([sender, target, &reply.data].iter(), 0) {
+                    println!("Failed replying to {}: {}", String::from_utf8_lossy(sender), e);
+                }
+            }
+            continue;
+        }
+        if let Err(e) = router.send_multipart([target, sender, payload].iter(), 0) {
+            // Target not connected: the sender retries or times out
+            println!(
+                "Failed routing control message to {}: {}",
+                String::from_utf8_lossy(target),
+                e
+            );
+        }
+    }
+}
+
 fn main() {
     println!("0mq broker started!");
     let context = zmq::Context::new();
@@ -32,12 +135,19 @@ fn main() {
     capture
         .bind("inproc://trace")
         .expect("failed binding pair socket");
+    // Network events of the control task
+    sub_socket
+        .bind("inproc://events")
+        .expect("failed binding event socket");
 
     sub_socket.set_subscribe(b"").expect("failed to subscribe");
 
     let ctx = context.clone();
-
     thread::spawn(move || trace_task(&ctx));
+
+    let ctx = context.clone();
+    thread::spawn(move || control_task(&ctx));
+
     zmq::proxy_with_capture(&mut sub_socket, &mut pub_socket, &mut capture)
         .expect("failed proxying");
     //zmq::proxy(&frontend, &backend).unwrap();